@@ -0,0 +1,1753 @@
+use std::collections::HashMap;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Component, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::UNIX_EPOCH;
+use std::{fs, io, path::Path};
+
+use rayon::prelude::*;
+
+use serde_json::Value;
+
+use crate::checksum::md5;
+use crate::datetime::{format_offset, parse_offset, DateTime};
+use crate::diff::get_diff_rows;
+use crate::exif::{ExifImage, Field, IFDTypes, StripPreset, TagFilter, DISPLAY_TAGS};
+use crate::exiftool;
+use crate::formats::{get_image_format_for, ImageFormat};
+use crate::gps::{
+    get_position, get_position_time, is_coordinate_tag, set_coordinate_from_string, set_position,
+    set_position_from_string, Position, DEFAULT_SECONDS_DECIMALS, MAX_SECONDS_DECIMALS,
+};
+use crate::gpx::{get_position_at, parse_gpx, write_gpx, Waypoint};
+use crate::json;
+use crate::kml::write_kml;
+use crate::spreadsheet;
+use crate::tags::{Tag, Tags};
+use crate::template::{get_image_date, Context, Template};
+use crate::timeshift::{parse_duration, shift_times};
+use crate::tui;
+
+// Where rename writes its undo log, when no other file is given
+const RENAME_LOG: &str = "exifeditui-renames.csv";
+// The directory of sort for the images that don't have a tag of the template
+const UNKNOWN_DIRECTORY: &str = "Unknown";
+// The name that set takes for GPSLatitude and GPSLongitude together, as exiftool does
+const POSITION_NAME: &str = "GPSPosition";
+// The longest time in seconds between two points of a track that geotag interpolates across
+const DEFAULT_MAX_GAP: i64 = 30 * 60;
+
+pub const USAGE: &str = "Usage: ExifEdiTUI <command> [options] <args>
+
+Commands:
+  show [--json|--exiftool] <file>...
+                                   Print all the metadata of the files
+  get <tag>[,<tag>...] <file>...   Print the values of the given tags
+  set <tag>=<value>... <file>...   Set tags of the 0th, Exif or GPS IFD, XMP properties, IPTC
+                                   datasets, JFIF values or comments
+  delete <tag>[,<tag>...] <file>.. Remove tags
+  strip [--preset <preset>] <file>...
+                                   Remove metadata, by default the Exif data, the XMP, the IPTC
+                                   and the comments (see the presets below)
+  import <json> [<file>...]        Apply a JSON document in the format of show --json
+  export-csv <tag>[,...] <file>... Print the tags as CSV, with a row per file
+  import-csv <csv>                 Write the edited cells of a CSV back into the files
+  copy [--ifd <ifds>|--tags <tags>] <source> <target>...
+                                   Copy the tags of an image to other images, all of them or
+                                   only those of the given IFDs (ifd0, exif, gps) or tags
+  rename <template> <file>...      Rename the files from their tags (see Templates below)
+  rename --undo [<log>]            Rename the files of the log back
+  sort <template> <dest> <file>... Move the files into directories such as {year}/{month}/{day}
+                                   or {Make}/{Model} under dest, leaving out the duplicates
+  shift-time --by <duration> <file>...
+                                   Shift DateTime, DateTimeOriginal, DateTimeDigitized and the
+                                   GPS date and time, by +1d2h30m10s, -1:30...
+  shift-time --reference <file> --to <date> <file>...
+                                   Shift them so that the reference image has the true date
+  geotag <gpx> <file>...           Write the position of the track at the time each picture was
+                                   taken (DateTimeOriginal and OffsetTimeOriginal)
+  export-gpx <file>...             Print the positions of the images as a GPX track, in the
+                                   order they were taken
+  export-kml <file>...             Print them as KML placemarks, with their thumbnails
+  diff [--tui] <old> <new>         List the tags added, removed or changed in each IFD and
+                                   section, or show both images side by side
+  tui <file>                       Browse and edit the metadata interactively
+  conflicts <file>                 List the values that differ between Exif, XMP and JFIF
+  previews <file> [<dir>]          Extract the embedded JPEG previews
+  icc <file> [<dir>]               Extract the ICC profile to a .icc file
+
+Options:
+  -o, --output <file>  Write the edited image there instead of overwriting it
+  --json               Print the metadata as JSON, with an object per file
+  --exiftool           Print the tags as exiftool does, named as in EXIF:ExposureTime
+  --tui                Compare the images in the TUI, where the new one can be edited
+  --format <formats>   Only process the images of these formats (jpeg,png,webp,tiff,cr2...)
+  --preset <preset>    What strip removes:
+                         all             The Exif data, the XMP, the IPTC and the comments
+                         gps             The GPS IFD
+                         privacy         The GPS IFD, the serial numbers, CameraOwnerName,
+                                         ImageUniqueID and MakerNote, with their XMP
+                                         properties and the xmpMM document ids
+                         keep-copyright  Everything but Artist and Copyright
+  --everything         Strip Orientation and ColorSpace too, which are kept so that the images
+                       look the same (the ICC profile is always kept)
+  --dry-run            Print what rename, sort, shift-time and geotag would do without touching the files
+  --offset <offset>    Set the OffsetTime tags to this time zone (such as +02:00) with
+                       shift-time, the GPS date and time being in UTC are then left as they are.
+                       With geotag, the time zone of the images without OffsetTimeOriginal.
+  --precision <digits> The decimals of the seconds of the GPS coordinates written by set and
+                       geotag (4 by default, up to 7)
+  --max-gap <duration> The longest time between two points of the track, or between the track
+                       and a picture, over which geotag still places it (30m by default)
+  --copy               Copy the files with sort instead of moving them
+  --log <file>         The undo log of rename (exifeditui-renames.csv by default)
+  -h, --help           Print this help
+
+Files can be directories, which are walked recursively, or glob patterns such as 'shoot/*.jpg'.
+They are processed in parallel, and only the images found in directories and patterns are kept.
+
+Tags are named as they are displayed (Artist, DateTimeOriginal, GPSLatitude...), ignoring case.
+Numbers are separated by spaces or commas, rationals are written as num/den or as decimals.
+GPS coordinates are written in decimal degrees (48.8584, -2.2945) or in degrees, minutes and
+seconds (48° 51' 30.24\" N), which sets their Ref tag as well. GPSPosition=\"48.8584, 2.2945\" sets
+both the latitude and the longitude.
+XMP properties, IPTC datasets, JFIF values and comments are named after their section, as in
+XMP:dc:title, IPTC:By-line, JFIF:XResolution or Comments:Comment (PNG texts can have any keyword,
+such as Comments:Author), and the values of lists (XMP:dc:subject, IPTC:Keywords...) are
+separated by commas. The JFIF values can't be removed.
+exiftool command lines are accepted as well: -TAG prints a tag, -TAG=VALUE writes it and -TAG=
+removes it, with the values printed and written as exiftool does (or raw with -n or TAG#).
+The first column of a CSV holds the paths of the files, and an empty cell removes its tag.
+
+Templates:
+  {Tag}                The value of the tag, without spaces: {Model} gives CanonEOSR5
+  {Tag:format}         A date formatted as with strftime: {DateTimeOriginal:%Y-%m-%d_%H%M%S}
+  {year}, {month}...   A part of the date the picture was taken (year, month, day, hour,
+                       minute, second), or of when the file was modified (UTC) for sort
+  {date:format}        The date the picture was taken, formatted as with strftime
+  {counter:digits}     The position of the file, padded with zeros: {counter:4} gives 0042
+  {{ and }}            Braces
+The extension of the file is kept, and _1, _2... is added to the names that are already taken.
+sort puts the images that don't have a tag of the template in an Unknown directory.
+
+Exit codes:
+  0  Success
+  1  A tag wasn't found, or the images differ
+  2  Invalid command line, JSON document or CSV
+  3  A file couldn't be read or written
+  4  Unsupported or invalid image
+  5  The image couldn't be edited";
+
+pub enum CliError {
+    NotFound(String),
+    Usage(String),
+    Io(String),
+    Unsupported(String),
+    Edit(String),
+}
+
+impl CliError {
+    pub fn get_exit_code(&self) -> u8 {
+        match self {
+            CliError::NotFound(_) => 1,
+            CliError::Usage(_) => 2,
+            CliError::Io(_) => 3,
+            CliError::Unsupported(_) => 4,
+            CliError::Edit(_) => 5,
+        }
+    }
+
+    pub fn get_message(&self) -> &str {
+        match self {
+            CliError::NotFound(message)
+            | CliError::Usage(message)
+            | CliError::Io(message)
+            | CliError::Unsupported(message)
+            | CliError::Edit(message) => message,
+        }
+    }
+}
+
+// The arguments of a command, once the options are taken out
+struct Arguments {
+    positionals: Vec<String>,
+    output: Option<String>,
+    is_json: bool,
+    is_exiftool: bool,
+    is_tui: bool,
+    ifd_types: Vec<IFDTypes>, // Filters of copy
+    tags: Vec<Tag>,
+    preset: Option<StripPreset>,
+    is_everything: bool, // Orientation and ColorSpace are stripped too
+    is_dry_run: bool,
+    is_undo: bool,
+    log: Option<String>, // The undo log of rename
+    is_copy: bool,       // sort copies the files instead of moving them
+    duration: Option<String>,
+    reference: Option<String>, // The image of shift-time whose true date is given
+    true_date: Option<String>,
+    offset: Option<String>,
+    max_gap: Option<String>,   // Of geotag
+    precision: Option<usize>,  // The decimals of the seconds of the GPS coordinates
+    formats: Vec<ImageFormat>, // Only the images of these formats are processed, when given
+}
+
+impl Arguments {
+    fn from(args: &[String]) -> Result<Self, CliError> {
+        let mut positionals = Vec::new();
+        let mut output = None;
+        let mut is_json = false;
+        let mut is_exiftool = false;
+        let mut is_tui = false;
+        let mut ifd_types = Vec::new();
+        let mut tags = Vec::new();
+        let mut preset = None;
+        let mut is_everything = false;
+        let mut is_dry_run = false;
+        let mut is_undo = false;
+        let mut log = None;
+        let mut is_copy = false;
+        let mut duration = None;
+        let mut reference = None;
+        let mut true_date = None;
+        let mut offset = None;
+        let mut max_gap = None;
+        let mut precision = None;
+        let mut formats = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-o" | "--output" => match args.next() {
+                    Some(path) => output = Some(path.clone()),
+                    None => return Err(CliError::Usage(format!("{} needs a file", arg))),
+                },
+                "--json" => is_json = true,
+                "--exiftool" => is_exiftool = true,
+                "--tui" => is_tui = true,
+                "--ifd" => match args.next() {
+                    Some(names) => {
+                        for name in names.split(',').filter(|name| !name.is_empty()) {
+                            match IFDTypes::from_name(name) {
+                                Some(ifd_type) => ifd_types.push(ifd_type),
+                                None => {
+                                    return Err(CliError::Usage(format!("Unknown IFD {}", name)))
+                                }
+                            }
+                        }
+                    }
+                    None => return Err(CliError::Usage(format!("{} needs IFDs", arg))),
+                },
+                "--preset" => match args.next() {
+                    Some(name) => match StripPreset::from_name(name) {
+                        Some(strip_preset) => preset = Some(strip_preset),
+                        None => return Err(CliError::Usage(format!("Unknown preset {}", name))),
+                    },
+                    None => return Err(CliError::Usage(format!("{} needs a preset", arg))),
+                },
+                "--everything" => is_everything = true,
+                "--dry-run" => is_dry_run = true,
+                "--undo" => is_undo = true,
+                "--copy" => is_copy = true,
+                "--by" | "--reference" | "--to" | "--offset" | "--max-gap" => {
+                    let value = match args.next() {
+                        Some(value) => Some(value.clone()),
+                        None => return Err(CliError::Usage(format!("{} needs a value", arg))),
+                    };
+                    match arg.as_str() {
+                        "--by" => duration = value,
+                        "--reference" => reference = value,
+                        "--to" => true_date = value,
+                        "--offset" => offset = value,
+                        _ => max_gap = value,
+                    }
+                }
+                "--precision" => match args.next().map(|value| value.parse::<usize>()) {
+                    Some(Ok(decimals)) if decimals <= MAX_SECONDS_DECIMALS => {
+                        precision = Some(decimals)
+                    }
+                    Some(_) => {
+                        return Err(CliError::Usage(format!(
+                            "The precision must be a number of decimals up to {}",
+                            MAX_SECONDS_DECIMALS
+                        )))
+                    }
+                    None => return Err(CliError::Usage(format!("{} needs a value", arg))),
+                },
+                "--log" => match args.next() {
+                    Some(path) => log = Some(path.clone()),
+                    None => return Err(CliError::Usage(format!("{} needs a file", arg))),
+                },
+                "--tags" => match args.next() {
+                    Some(names) => tags.extend(parse_tag_list(names)?),
+                    None => return Err(CliError::Usage(format!("{} needs tags", arg))),
+                },
+                "--format" => match args.next() {
+                    Some(names) => {
+                        for name in names.split(',').filter(|name| !name.is_empty()) {
+                            match ImageFormat::from_name(name) {
+                                Some(format) => formats.push(format),
+                                None => {
+                                    return Err(CliError::Usage(format!(
+                                        "Unknown image format {}",
+                                        name
+                                    )))
+                                }
+                            }
+                        }
+                    }
+                    None => return Err(CliError::Usage(format!("{} needs formats", arg))),
+                },
+                // Everything after it is positional, for files whose name starts with a dash
+                "--" => positionals.extend(args.by_ref().cloned()),
+                _ if arg.starts_with('-') && arg.len() > 1 => {
+                    return Err(CliError::Usage(format!("Unknown option {}", arg)))
+                }
+                _ => positionals.push(arg.clone()),
+            }
+        }
+
+        Ok(Self {
+            positionals,
+            output,
+            is_json,
+            is_exiftool,
+            is_tui,
+            ifd_types,
+            tags,
+            preset,
+            is_everything,
+            is_dry_run,
+            is_undo,
+            log,
+            is_copy,
+            duration,
+            reference,
+            true_date,
+            offset,
+            max_gap,
+            precision,
+            formats,
+        })
+    }
+
+    // The output can't be used when several files are edited
+    fn get_output(&self, file_count: usize) -> Result<Option<&str>, CliError> {
+        match (&self.output, file_count) {
+            (Some(_), 2..) => Err(CliError::Usage(String::from(
+                "--output can only be used with a single file",
+            ))),
+            (output, _) => Ok(output.as_deref()),
+        }
+    }
+
+    fn get_precision(&self) -> usize {
+        self.precision.unwrap_or(DEFAULT_SECONDS_DECIMALS)
+    }
+
+    fn get_files(&self, files: &[String]) -> Result<Vec<String>, CliError> {
+        if files.is_empty() {
+            return Err(CliError::Usage(String::from("Missing file")));
+        }
+        let files = expand_files(files, &self.formats)?;
+        match files.is_empty() {
+            true => Err(CliError::NotFound(String::from("No image found"))),
+            false => Ok(files),
+        }
+    }
+
+    fn get_filter(&self) -> Result<TagFilter, CliError> {
+        match (self.ifd_types.is_empty(), self.tags.is_empty()) {
+            (true, true) => Ok(TagFilter::All),
+            (false, true) => Ok(TagFilter::IFDs(self.ifd_types.clone())),
+            (true, false) => match self.tags.iter().find(|tag| tag.get_ifd_type().is_none()) {
+                Some(tag) => Err(CliError::Usage(format!("{} can't be copied", tag))),
+                None => Ok(TagFilter::Tags(self.tags.clone())),
+            },
+            (false, false) => Err(CliError::Usage(String::from(
+                "--ifd and --tags can't be used together",
+            ))),
+        }
+    }
+
+    // Rejects the options that the command doesn't use
+    fn allow(self, command: &str, options: &[&str]) -> Result<Self, CliError> {
+        let given = [
+            ("--output", self.output.is_some()),
+            ("--json", self.is_json),
+            ("--exiftool", self.is_exiftool),
+            ("--tui", self.is_tui),
+            ("--ifd", !self.ifd_types.is_empty()),
+            ("--tags", !self.tags.is_empty()),
+            ("--preset", self.preset.is_some()),
+            ("--everything", self.is_everything),
+            ("--dry-run", self.is_dry_run),
+            ("--undo", self.is_undo),
+            ("--log", self.log.is_some()),
+            ("--copy", self.is_copy),
+            ("--by", self.duration.is_some()),
+            ("--reference", self.reference.is_some()),
+            ("--to", self.true_date.is_some()),
+            ("--offset", self.offset.is_some()),
+            ("--max-gap", self.max_gap.is_some()),
+            ("--precision", self.precision.is_some()),
+            ("--format", !self.formats.is_empty()),
+        ];
+        for (option, is_given) in given {
+            if is_given && !options.contains(&option) {
+                return Err(CliError::Usage(format!(
+                    "{} can't be used with {}",
+                    option, command
+                )));
+            }
+        }
+        Ok(self)
+    }
+}
+
+// Runs the command and returns the exit code
+pub fn run(all_args: &[String]) -> Result<u8, CliError> {
+    let (command, args) = match all_args.split_first() {
+        Some((command, args)) => (command.as_str(), args),
+        None => return Err(CliError::Usage(String::from("Missing command"))),
+    };
+    // Arguments such as -Artist=Jane or -EXIF:ExposureTime make an exiftool command line
+    let mut options = all_args.iter().take_while(|arg| *arg != "--");
+    let is_exiftool_arg = |arg: &String| {
+        !arg.starts_with("--")
+            && arg.starts_with('-')
+            && (arg.contains('=') || get_exiftool_tag(arg).is_some())
+    };
+    if command == "-n" || options.any(is_exiftool_arg) {
+        return exiftool_command(all_args);
+    }
+    let args = Arguments::from(args)?;
+
+    match command {
+        "-h" | "--help" | "help" => {
+            println!("{}", USAGE);
+            Ok(0)
+        }
+        "show" => show(args.allow(command, &["--json", "--exiftool", "--format"])?),
+        "get" => get(args.allow(command, &["--format"])?),
+        "set" => set(args.allow(command, &["--output", "--format", "--precision"])?),
+        "delete" => delete(args.allow(command, &["--output", "--format"])?),
+        "strip" => strip(args.allow(
+            command,
+            &["--output", "--format", "--preset", "--everything"],
+        )?),
+        "import" => import(args.allow(command, &["--output", "--format"])?),
+        "export-csv" => export_csv(args.allow(command, &["--format"])?),
+        "import-csv" => import_csv(args.allow(command, &[])?.positionals),
+        "copy" => copy(args.allow(command, &["--output", "--format", "--ifd", "--tags"])?),
+        "rename" => rename(args.allow(command, &["--format", "--dry-run", "--undo", "--log"])?),
+        "sort" => sort(args.allow(command, &["--format", "--dry-run", "--copy"])?),
+        "shift-time" => shift_time(args.allow(
+            command,
+            &[
+                "--output",
+                "--format",
+                "--dry-run",
+                "--by",
+                "--reference",
+                "--to",
+                "--offset",
+            ],
+        )?),
+        "geotag" => geotag(args.allow(
+            command,
+            &[
+                "--output",
+                "--format",
+                "--dry-run",
+                "--offset",
+                "--max-gap",
+                "--precision",
+            ],
+        )?),
+        "export-gpx" => export_track(args.allow(command, &["--format"])?, false),
+        "export-kml" => export_track(args.allow(command, &["--format"])?, true),
+        "diff" => diff(args.allow(command, &["--tui", "--output"])?),
+        "tui" => run_tui(args.allow(command, &["--output"])?),
+        "conflicts" => conflicts(args.allow(command, &[])?.positionals),
+        "previews" => extract_previews(args.allow(command, &[])?.positionals),
+        "icc" => extract_icc(args.allow(command, &[])?.positionals),
+        // A lone image is shown, as before the commands existed
+        _ if Path::new(command).is_file() => {
+            let mut args = args.allow("show", &["--json", "--exiftool", "--format"])?;
+            args.positionals.insert(0, command.to_string());
+            show(args)
+        }
+        _ => Err(CliError::Usage(format!("Unknown command {}", command))),
+    }
+}
+
+// Enough to detect the format of a file
+const IMAGE_HEADER_SIZE: u64 = 64 * 1024;
+
+fn open_image(path: &str) -> Result<ExifImage, CliError> {
+    let img_contents =
+        fs::read(path).map_err(|e| CliError::Io(format!("Couldn't read {}: {}", path, e)))?;
+    let img_format = match get_image_format_for(img_contents.as_ref()) {
+        Some(img_format) => img_format,
+        None => {
+            return Err(CliError::Unsupported(format!(
+                "{}: Unsupported image type",
+                path
+            )))
+        }
+    };
+
+    let img = ExifImage::from(img_contents, img_format)
+        .map_err(|e| CliError::Unsupported(format!("{}: {}", path, e)))?;
+    for warning in img.get_warnings() {
+        eprintln!("Warning: {}", warning);
+    }
+    Ok(img)
+}
+
+fn write_image(img: &ExifImage, path: &str, output: Option<&str>) -> Result<(), CliError> {
+    let out_path = output.unwrap_or(path);
+    fs::write(out_path, img.get_contents())
+        .map_err(|e| CliError::Io(format!("Couldn't write {}: {}", out_path, e)))
+}
+
+// Directories are walked recursively and glob patterns are expanded, keeping only the images (of
+// the given formats, if any). Files that are named directly are kept even when they aren't images,
+// so that their errors are reported, unless formats are given.
+fn expand_files(patterns: &[String], formats: &[ImageFormat]) -> Result<Vec<String>, CliError> {
+    let mut files = Vec::new();
+    for pattern in patterns {
+        let path = Path::new(pattern);
+        if path.is_dir() {
+            walk_directory(path, formats, &mut files)?;
+        } else if !path.exists() && pattern.contains(['*', '?', '[']) {
+            let paths =
+                glob::glob(pattern).map_err(|e| CliError::Usage(format!("{}: {}", pattern, e)))?;
+            for path in paths {
+                let path = path.map_err(|e| CliError::Io(e.to_string()))?;
+                if path.is_dir() {
+                    walk_directory(&path, formats, &mut files)?;
+                } else if is_image(&path, formats) {
+                    files.push(path.to_string_lossy().to_string());
+                }
+            }
+        } else if formats.is_empty() || is_image(path, formats) {
+            files.push(pattern.clone());
+        }
+    }
+    Ok(files)
+}
+
+// Hidden files and directories are skipped
+fn walk_directory(
+    dir: &Path,
+    formats: &[ImageFormat],
+    files: &mut Vec<String>,
+) -> Result<(), CliError> {
+    let read_error = |e: io::Error| CliError::Io(format!("Couldn't read {}: {}", dir.display(), e));
+    let mut paths = fs::read_dir(dir)
+        .map_err(read_error)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<PathBuf>, io::Error>>()
+        .map_err(read_error)?;
+    paths.sort();
+
+    for path in paths {
+        if path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        {
+            continue;
+        }
+        if path.is_dir() {
+            walk_directory(&path, formats, files)?;
+        } else if is_image(&path, formats) {
+            files.push(path.to_string_lossy().to_string());
+        }
+    }
+    Ok(())
+}
+
+// The format is detected from the start of the file, which holds the magic numbers and, for the
+// raw formats that are plain TIFF files, the camera maker
+fn is_image(path: &Path, formats: &[ImageFormat]) -> bool {
+    let mut header = Vec::new();
+    let is_read = fs::File::open(path)
+        .and_then(|file| file.take(IMAGE_HEADER_SIZE).read_to_end(&mut header))
+        .is_ok();
+    match get_image_format_for(&header) {
+        Some(format) => is_read && (formats.is_empty() || formats.contains(&format)),
+        None => false,
+    }
+}
+
+// Applies the operation to each file, in parallel. When there are several files, the progress is
+// shown on the terminal while they are processed.
+fn process_files<T: Sync, R: Send, F: Fn(&T) -> R + Sync>(files: &[T], operation: F) -> Vec<R> {
+    let show_progress = files.len() > 1 && io::stderr().is_terminal();
+    let done_count = AtomicUsize::new(0);
+    let results = files
+        .par_iter()
+        .map(|file| {
+            let result = operation(file);
+            let done_count = done_count.fetch_add(1, Ordering::Relaxed) + 1;
+            if show_progress {
+                eprint!("\r{}/{} files", done_count, files.len());
+            }
+            result
+        })
+        .collect();
+    if show_progress {
+        eprint!("\r\x1b[2K");
+    }
+    results
+}
+
+// Applies the operation to each file, carrying on after a failure. The outputs are printed in the
+// order of the files, followed by the errors and, for several files, a summary. The exit code is
+// the one of the first error.
+fn for_each_file<T: Sync, F: Fn(&T, &mut String) -> Result<(), CliError> + Sync>(
+    files: &[T],
+    operation: F,
+) -> u8 {
+    let results = process_files(files, |file| {
+        let mut output = String::new();
+        let result = operation(file, &mut output);
+        (output, result)
+    });
+
+    let mut errors = Vec::new();
+    for (output, result) in results {
+        print!("{}", output);
+        if let Err(e) = result {
+            errors.push(e);
+        }
+    }
+    report_errors(&errors, files.len())
+}
+
+fn report_errors(errors: &[CliError], file_count: usize) -> u8 {
+    for e in errors {
+        eprintln!("{}", e.get_message());
+    }
+    if file_count > 1 {
+        eprintln!(
+            "{} files: {} done, {} failed",
+            file_count,
+            file_count - errors.len(),
+            errors.len()
+        );
+    }
+    errors.first().map_or(0, CliError::get_exit_code)
+}
+
+// exiftool names (ISO, EXIF:CreateDate...) are accepted as well
+fn parse_tag(name: &str) -> Result<Tag, CliError> {
+    Tag::from_name(name)
+        .or_else(|| Tag::from_exiftool_name(name))
+        .ok_or_else(|| CliError::Usage(format!("Unknown tag {}", name)))
+}
+
+// The values of the other sections are named after them, as in XMP:dc:title
+fn parse_field(name: &str) -> Result<Field, CliError> {
+    match Field::from_name(name) {
+        Some(field) => Ok(field),
+        None => parse_tag(name).map(Field::Tag),
+    }
+}
+
+fn parse_field_list(names: &str) -> Result<Vec<Field>, CliError> {
+    names
+        .split(',')
+        .filter(|name| !name.is_empty())
+        .map(parse_field)
+        .collect()
+}
+
+fn parse_tag_list(names: &str) -> Result<Vec<Tag>, CliError> {
+    names
+        .split(',')
+        .filter(|name| !name.is_empty())
+        .map(parse_tag)
+        .collect()
+}
+
+fn show(args: Arguments) -> Result<u8, CliError> {
+    let files = &args.get_files(&args.positionals)?;
+    if args.is_json {
+        return match args.is_exiftool {
+            true => Err(CliError::Usage(String::from(
+                "--json and --exiftool can't be used together",
+            ))),
+            false => Ok(show_json(files)),
+        };
+    }
+
+    Ok(for_each_file(files, |path, output| {
+        let img = open_image(path)?;
+        if files.len() > 1 {
+            output.push_str(&format!("======== {}\n", path));
+        }
+        match args.is_exiftool {
+            true => output.push_str(&get_exiftool_lines(&exiftool::get_all_tags(&img, false))),
+            false => output.push_str(&img.get_all_tags_as_string()),
+        }
+        Ok(())
+    }))
+}
+
+// Lines as exiftool prints them, so that the scripts that parse its output keep working
+fn get_exiftool_lines(tags: &[(String, String)]) -> String {
+    tags.iter()
+        .map(|(name, value)| format!("{:<32}: {}\n", name, value))
+        .collect()
+}
+
+// An argument that names a tag the way exiftool does: `-TAG`, `-TAG=VALUE` or `-TAG=`
+fn get_exiftool_tag(arg: &str) -> Option<(Tag, Option<&str>)> {
+    let arg = arg.strip_prefix('-')?;
+    let (name, value) = match arg.split_once('=') {
+        Some((name, value)) => (name, Some(value)),
+        None => (arg, None),
+    };
+    Tag::from_exiftool_name(name).map(|tag| (tag, value))
+}
+
+// Reads the tags given as `-TAG`, or every tag when none is given, and writes the ones given as
+// `-TAG=VALUE`, an empty value removing the tag. Values are written as exiftool prints them,
+// unless -n is given or the name ends with `#`. As with exiftool, tags that are missing aren't
+// reported and the number of files that were updated is printed.
+fn exiftool_command(args: &[String]) -> Result<u8, CliError> {
+    let mut files = Vec::new();
+    let mut read_tags = Vec::new();
+    let mut assignments = Vec::new();
+    let mut output = None;
+    let mut is_numeric = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => match args.next() {
+                Some(path) => output = Some(path.as_str()),
+                None => return Err(CliError::Usage(format!("{} needs a file", arg))),
+            },
+            "-n" => is_numeric = true,
+            // The original is never kept
+            "-overwrite_original" | "-overwrite_original_in_place" => (),
+            "--" => files.extend(args.by_ref().cloned()),
+            _ if arg.starts_with('-') && arg.len() > 1 => match get_exiftool_tag(arg) {
+                Some((tag, None)) => read_tags.push(tag),
+                Some((tag, Some(value))) => {
+                    let is_raw = arg.split_once('=').unwrap().0.ends_with('#');
+                    assignments.push((tag, value, is_raw));
+                }
+                None => {
+                    return Err(CliError::Usage(match arg.split_once('=') {
+                        Some((name, _)) => format!("Unknown tag {}", &name[1..]),
+                        None => format!("Unknown option {}", arg),
+                    }))
+                }
+            },
+            _ => files.push(arg.clone()),
+        }
+    }
+    if files.is_empty() {
+        return Err(CliError::Usage(String::from("Missing file")));
+    }
+    let files = expand_files(&files, &[])?;
+
+    if assignments.is_empty() {
+        if output.is_some() {
+            return Err(CliError::Usage(String::from(
+                "-o can only be used when tags are written",
+            )));
+        }
+        return Ok(for_each_file(&files, |path, output| {
+            let img = open_image(path)?;
+            if files.len() > 1 {
+                output.push_str(&format!("======== {}\n", path));
+            }
+            let tags = exiftool::get_all_tags(&img, is_numeric);
+            let tags: Vec<(String, String)> = match read_tags.is_empty() {
+                true => tags,
+                false => read_tags
+                    .iter()
+                    .filter_map(|tag| {
+                        let name = format!("{:#}", tag);
+                        tags.iter().find(|(other, _)| *other == name).cloned()
+                    })
+                    .collect(),
+            };
+            output.push_str(&get_exiftool_lines(&tags));
+            Ok(())
+        }));
+    }
+    if !read_tags.is_empty() {
+        return Err(CliError::Usage(String::from(
+            "Tags can't be read and written at once",
+        )));
+    }
+    let output = match (output, files.len()) {
+        (Some(_), 2..) => {
+            return Err(CliError::Usage(String::from(
+                "-o can only be used with a single file",
+            )))
+        }
+        (output, _) => output,
+    };
+
+    let results = process_files(&files, |path| {
+        let mut img = open_image(path)?;
+        for (tag, value, is_raw) in assignments.iter() {
+            let result = match (value.is_empty(), tag.get_ifd_type()) {
+                (true, Some(ifd_type)) => img.remove_tag(ifd_type, *tag),
+                (_, None) => Err(format!("{} can't be written", tag)),
+                // The printed and the raw forms are both accepted, with a sign or a hemisphere
+                (false, _) if is_coordinate_tag(*tag) => {
+                    set_coordinate_from_string(&mut img, *tag, value, DEFAULT_SECONDS_DECIMALS)
+                }
+                (false, _) if is_numeric || *is_raw => img.set_tag_from_string(*tag, value),
+                (false, _) => {
+                    img.set_tag_from_string(*tag, &exiftool::parse_print_value(*tag, value))
+                }
+            };
+            result.map_err(|e| CliError::Edit(format!("{}: {}", path, e)))?;
+        }
+        write_image(&img, path, output)
+    });
+
+    let errors: Vec<CliError> = results.into_iter().filter_map(Result::err).collect();
+    for e in errors.iter() {
+        eprintln!("{}", e.get_message());
+    }
+    println!("{:5} image files updated", files.len() - errors.len());
+    if !errors.is_empty() {
+        println!("{:5} files weren't updated due to errors", errors.len());
+    }
+    Ok(errors.first().map_or(0, CliError::get_exit_code))
+}
+
+// Prints a JSON array with an object per file. Files that couldn't be read get an object with the
+// error, so that the output always matches the files given.
+fn show_json(files: &[String]) -> u8 {
+    let results = process_files(files, |path| {
+        open_image(path).map(|img| json::get_image_json(&img, path))
+    });
+
+    let mut objects = Vec::new();
+    let mut errors = Vec::new();
+    for (path, result) in files.iter().zip(results) {
+        match result {
+            Ok(object) => objects.push(object),
+            Err(e) => {
+                objects.push(json::get_error_json(path, e.get_message()));
+                errors.push(e);
+            }
+        }
+    }
+    println!("{}", json::to_string(&objects));
+    report_errors(&errors, files.len())
+}
+
+// Prints the bare value when a single tag of a single file is asked for, so that scripts don't
+// have to parse the output
+fn get(args: Arguments) -> Result<u8, CliError> {
+    let (tags, files) = match args.positionals.split_first() {
+        Some((tags, files)) => (parse_field_list(tags)?, args.get_files(files)?),
+        None => return Err(CliError::Usage(String::from("Missing tag"))),
+    };
+
+    Ok(for_each_file(&files, |path, output| {
+        let img = open_image(path)?;
+        let mut missing = Vec::new();
+        for tag in tags.iter() {
+            let value = match tag.get_value(&img) {
+                Some(value) => value,
+                None => {
+                    missing.push(tag.to_string());
+                    continue;
+                }
+            };
+            output.push_str(&match (files.len(), tags.len()) {
+                (1, 1) => format!("{}\n", value),
+                (1, _) => format!("{}: {}\n", tag, value),
+                _ => format!("{}: {}: {}\n", path, tag, value),
+            });
+        }
+
+        match missing.is_empty() {
+            true => Ok(()),
+            false => Err(CliError::NotFound(format!(
+                "{}: {} not found",
+                path,
+                missing.join(", ")
+            ))),
+        }
+    }))
+}
+
+// The files are only written once all the tags were set
+fn set(args: Arguments) -> Result<u8, CliError> {
+    let split = args
+        .positionals
+        .iter()
+        .position(|arg| !arg.contains('='))
+        .unwrap_or(args.positionals.len());
+    let (assignments, files) = args.positionals.split_at(split);
+    if assignments.is_empty() {
+        return Err(CliError::Usage(String::from("Missing <tag>=<value>")));
+    }
+    let files = args.get_files(files)?;
+    let output = args.get_output(files.len())?;
+
+    // Without a tag, the value is a position
+    let assignments = assignments
+        .iter()
+        .map(|assignment| {
+            let (name, value) = assignment.split_once('=').unwrap();
+            match name.eq_ignore_ascii_case(POSITION_NAME) {
+                true => Ok((None, value)),
+                false => Ok((Some(parse_field(name)?), value)),
+            }
+        })
+        .collect::<Result<Vec<(Option<Field>, &str)>, CliError>>()?;
+    let precision = args.get_precision();
+
+    Ok(for_each_file(&files, |path, _| {
+        let mut img = open_image(path)?;
+        for (tag, value) in assignments.iter() {
+            match tag {
+                None => set_position_from_string(&mut img, value, precision),
+                Some(Field::Tag(tag)) if is_coordinate_tag(*tag) => {
+                    set_coordinate_from_string(&mut img, *tag, value, precision)
+                }
+                Some(Field::Tag(tag)) => img.set_tag_from_string(*tag, value),
+                Some(Field::Section(section, name)) => img.set_section_value(section, name, value),
+            }
+            .map_err(|e| CliError::Edit(format!("{}: {}", path, e)))?;
+        }
+        write_image(&img, path, output)
+    }))
+}
+
+// Tags that aren't in the file are ignored, and files without any of them are left untouched
+fn delete(args: Arguments) -> Result<u8, CliError> {
+    let (tags, files) = match args.positionals.split_first() {
+        Some((tags, files)) => (parse_field_list(tags)?, args.get_files(files)?),
+        None => return Err(CliError::Usage(String::from("Missing tag"))),
+    };
+    let output = args.get_output(files.len())?;
+
+    Ok(for_each_file(&files, |path, _| {
+        let mut img = open_image(path)?;
+        let mut is_modified = false;
+        for tag in tags.iter() {
+            let tag = match tag {
+                Field::Tag(tag) => tag,
+                Field::Section(..) => {
+                    if tag.get_value(&img).is_some() {
+                        tag.remove(&mut img)
+                            .map_err(|e| CliError::Edit(format!("{}: {}", path, e)))?;
+                        is_modified = true;
+                    }
+                    continue;
+                }
+            };
+            let ifd_type = match tag.get_ifd_type() {
+                Some(ifd_type) => ifd_type,
+                None => {
+                    return Err(CliError::Edit(format!(
+                        "{}: {} can't be removed",
+                        path, tag
+                    )))
+                }
+            };
+            if img.get_interop(ifd_type, *tag).is_some() {
+                img.remove_tag(ifd_type, *tag)
+                    .map_err(|e| CliError::Edit(format!("{}: {}", path, e)))?;
+                is_modified = true;
+            }
+        }
+
+        match (is_modified, output) {
+            (false, None) => Ok(()),
+            _ => write_image(&img, path, output),
+        }
+    }))
+}
+
+fn strip(args: Arguments) -> Result<u8, CliError> {
+    let files = args.get_files(&args.positionals)?;
+    let output = args.get_output(files.len())?;
+    let kept_tags: &[Tag] = match args.is_everything {
+        true => &[],
+        false => &DISPLAY_TAGS,
+    };
+
+    Ok(for_each_file(&files, |path, _| {
+        let mut img = open_image(path)?;
+        img.strip(args.preset.unwrap_or(StripPreset::All), kept_tags)
+            .map_err(|e| CliError::Edit(format!("{}: {}", path, e)))?;
+        write_image(&img, path, output)
+    }))
+}
+
+// The objects of the document are applied to the files they name or, when images are given, the
+// document must hold a single object, which is applied to each of them. `-` reads the document
+// from the standard input.
+fn import(args: Arguments) -> Result<u8, CliError> {
+    let (json_path, files) = match args.positionals.split_first() {
+        Some((json_path, files)) => (json_path, files),
+        None => return Err(CliError::Usage(String::from("Missing JSON document"))),
+    };
+    let text = match json_path.as_str() {
+        "-" => io::read_to_string(io::stdin()),
+        _ => fs::read_to_string(json_path),
+    }
+    .map_err(|e| CliError::Io(format!("Couldn't read {}: {}", json_path, e)))?;
+    let objects =
+        json::parse(&text).map_err(|e| CliError::Usage(format!("{}: {}", json_path, e)))?;
+
+    let targets: Vec<(String, &Value)> = match (files, objects.as_slice()) {
+        ([], objects) => objects
+            .iter()
+            .map(|object| match object.get("file").and_then(Value::as_str) {
+                Some(path) => Ok((path.to_string(), object)),
+                None => Err(CliError::Usage(format!(
+                    "{}: Each object needs a \"file\" when no image is given",
+                    json_path
+                ))),
+            })
+            .collect::<Result<_, _>>()?,
+        (files, [object]) => args
+            .get_files(files)?
+            .into_iter()
+            .map(|path| (path, object))
+            .collect(),
+        _ => {
+            return Err(CliError::Usage(format!(
+                "{}: The document must hold a single object to be applied to the images",
+                json_path
+            )))
+        }
+    };
+    let output = args.get_output(targets.len())?;
+
+    Ok(for_each_file(&targets, |(path, object), _| {
+        let mut img = open_image(path)?;
+        let is_modified = json::apply_image_json(&mut img, object)
+            .map_err(|e| CliError::Edit(format!("{}: {}", path, e)))?;
+        match (is_modified, output) {
+            (false, None) => Ok(()),
+            _ => write_image(&img, path, output),
+        }
+    }))
+}
+
+fn export_csv(args: Arguments) -> Result<u8, CliError> {
+    let (tags, files) = match args.positionals.split_first() {
+        Some((tags, files)) => (parse_tag_list(tags)?, args.get_files(files)?),
+        None => return Err(CliError::Usage(String::from("Missing tag"))),
+    };
+
+    print!("{}", get_csv_line(spreadsheet::get_header(&tags))?);
+    Ok(for_each_file(&files, |path, output| {
+        let img = open_image(path)?;
+        output.push_str(&get_csv_line(spreadsheet::get_row(&img, path, &tags))?);
+        Ok(())
+    }))
+}
+
+fn get_csv_line(record: Vec<String>) -> Result<String, CliError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(record)
+        .map_err(|e| CliError::Io(format!("Couldn't write the CSV: {}", e)))?;
+    let line = writer
+        .into_inner()
+        .map_err(|e| CliError::Io(format!("Couldn't write the CSV: {}", e)))?;
+    Ok(String::from_utf8_lossy(&line).to_string())
+}
+
+// Each row is applied to the file it names, and a file is only written when all its cells are
+// valid. `-` reads the CSV from the standard input.
+fn import_csv(args: Vec<String>) -> Result<u8, CliError> {
+    let csv_path = match args.as_slice() {
+        [csv_path] => csv_path,
+        _ => return Err(CliError::Usage(String::from("Expected a single CSV"))),
+    };
+    let text = match csv_path.as_str() {
+        "-" => io::read_to_string(io::stdin()),
+        _ => fs::read_to_string(csv_path),
+    }
+    .map_err(|e| CliError::Io(format!("Couldn't read {}: {}", csv_path, e)))?;
+
+    // Rows of the wrong length are reported with the other errors of their file
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let invalid = |e: csv::Error| CliError::Usage(format!("{}: {}", csv_path, e));
+    let (file_column, columns) = spreadsheet::parse_header(reader.headers().map_err(invalid)?)
+        .map_err(|e| CliError::Usage(format!("{}: {}", csv_path, e)))?;
+    let records = reader
+        .records()
+        .collect::<Result<Vec<csv::StringRecord>, csv::Error>>()
+        .map_err(invalid)?;
+
+    Ok(for_each_file(&records, |record, _| {
+        let line = record
+            .position()
+            .map(|position| position.line())
+            .unwrap_or(0);
+        let path = match record.get(file_column) {
+            Some(path) if !path.is_empty() => path,
+            _ => {
+                return Err(CliError::Usage(format!(
+                    "{}: Line {}: The file is missing",
+                    csv_path, line
+                )))
+            }
+        };
+        let mut img = open_image(path)?;
+        match spreadsheet::apply_row(&mut img, &columns, record) {
+            Ok(true) => write_image(&img, path, None),
+            Ok(false) => Ok(()),
+            Err(errors) => Err(CliError::Edit(
+                errors
+                    .iter()
+                    .map(|e| format!("{} (line {}): {}", path, line, e))
+                    .collect::<Vec<String>>()
+                    .join("\n"),
+            )),
+        }
+    }))
+}
+
+fn copy(args: Arguments) -> Result<u8, CliError> {
+    let (source, targets) = match args.positionals.split_first() {
+        Some((source, targets)) => (source, targets),
+        None => return Err(CliError::Usage(String::from("Missing source image"))),
+    };
+    let targets = args.get_files(targets)?;
+    let output = args.get_output(targets.len())?;
+    let filter = args.get_filter()?;
+    let source_path = source;
+    let source = open_image(source)?;
+    if source.get_entries_to_copy(&filter).is_empty() {
+        return Err(CliError::NotFound(format!(
+            "{}: No tag to copy",
+            source_path
+        )));
+    }
+
+    Ok(for_each_file(&targets, |path, _| {
+        let mut img = open_image(path)?;
+        img.copy_tags_from(&source, &filter)
+            .map_err(|e| CliError::Edit(format!("{}: {}", path, e)))?;
+        write_image(&img, path, output)
+    }))
+}
+
+// Renames the files in their directory, from the template and their extension. A suffix is added
+// to names that are already taken, and the renames are logged so that they can be undone.
+fn rename(args: Arguments) -> Result<u8, CliError> {
+    if args.is_undo {
+        return undo_renames(args);
+    }
+    let (template, files) = match args.positionals.split_first() {
+        Some((template, files)) => (template, files),
+        None => return Err(CliError::Usage(String::from("Missing template"))),
+    };
+    let template = Template::parse(template).map_err(CliError::Usage)?;
+    let files = args.get_files(files)?;
+
+    let indexed_files: Vec<(usize, &String)> = files.iter().enumerate().collect();
+    let names = process_files(&indexed_files, |(i, path)| {
+        let img = open_image(path)?;
+        let context = Context {
+            counter: i + 1,
+            date: get_image_date(&img),
+            missing: None,
+        };
+        template
+            .render(&img, &context)
+            .map_err(|e| CliError::NotFound(format!("{}: {}", path, e)))
+    });
+
+    let mut errors = Vec::new();
+    let mut renames: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let mut taken_paths = Vec::new();
+    for (path, name) in files.iter().zip(names) {
+        let name = match name {
+            Ok(name) => name,
+            Err(e) => {
+                errors.push(e);
+                continue;
+            }
+        };
+        let old_path = PathBuf::from(path);
+        let mut new_path = old_path.with_file_name(name);
+        if let Some(extension) = old_path.extension() {
+            new_path.as_mut_os_string().push(".");
+            new_path.as_mut_os_string().push(extension);
+        }
+        let new_path = get_free_path(new_path, &old_path, &taken_paths);
+        taken_paths.push(new_path.clone());
+        if new_path != old_path {
+            renames.push((old_path, new_path));
+        }
+    }
+
+    let mut log = Vec::new();
+    for (old_path, new_path) in renames {
+        if !args.is_dry_run {
+            if let Err(e) = fs::rename(&old_path, &new_path) {
+                errors.push(CliError::Io(format!(
+                    "Couldn't rename {}: {}",
+                    old_path.display(),
+                    e
+                )));
+                continue;
+            }
+        }
+        println!("{} -> {}", old_path.display(), new_path.display());
+        log.push((old_path, new_path));
+    }
+
+    if !args.is_dry_run && !log.is_empty() {
+        let log_path = args.log.as_deref().unwrap_or(RENAME_LOG);
+        if let Err(e) = write_rename_log(log_path, &log) {
+            errors.push(e);
+        }
+    }
+    Ok(report_errors(&errors, files.len()))
+}
+
+// The path, or the path with _1, _2... added to the name when another file has it. The file that
+// is being moved doesn't count.
+fn get_free_path(path: PathBuf, moved_path: &Path, taken_paths: &[PathBuf]) -> PathBuf {
+    let is_free =
+        |path: &PathBuf| *path == moved_path || (!path.exists() && !taken_paths.contains(path));
+    if is_free(&path) {
+        return path;
+    }
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = match path.extension() {
+        Some(extension) => format!(".{}", extension.to_string_lossy()),
+        None => String::new(),
+    };
+    (1..)
+        .map(|i| path.with_file_name(format!("{}_{}{}", stem, i, extension)))
+        .find(is_free)
+        .unwrap_or(path)
+}
+
+// The log holds the absolute paths of the files before and after each rename. The renames are
+// appended to it, so that several runs can be undone.
+fn write_rename_log(log_path: &str, renames: &[(PathBuf, PathBuf)]) -> Result<(), CliError> {
+    let get_absolute = |path: &PathBuf| {
+        std::path::absolute(path)
+            .unwrap_or(path.clone())
+            .to_string_lossy()
+            .to_string()
+    };
+    let mut text = match Path::new(log_path).exists() {
+        true => String::new(),
+        false => get_csv_line(vec![String::from("Old"), String::from("New")])?,
+    };
+    for (old_path, new_path) in renames {
+        text.push_str(&get_csv_line(vec![
+            get_absolute(old_path),
+            get_absolute(new_path),
+        ])?);
+    }
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)
+        .and_then(|mut file| file.write_all(text.as_bytes()))
+        .map_err(|e| CliError::Io(format!("Couldn't write {}: {}", log_path, e)))
+}
+
+// Renames the files of the log back, the last renamed first. The log is removed once all of them
+// are.
+fn undo_renames(args: Arguments) -> Result<u8, CliError> {
+    let log_path = match (args.positionals.as_slice(), args.log.as_deref()) {
+        ([], log_path) => log_path.unwrap_or(RENAME_LOG),
+        ([log_path], None) => log_path,
+        _ => return Err(CliError::Usage(String::from("--undo only needs the log"))),
+    };
+    let text = fs::read_to_string(log_path)
+        .map_err(|e| CliError::Io(format!("Couldn't read {}: {}", log_path, e)))?;
+    let records = csv::Reader::from_reader(text.as_bytes())
+        .records()
+        .collect::<Result<Vec<csv::StringRecord>, csv::Error>>()
+        .map_err(|e| CliError::Usage(format!("{}: {}", log_path, e)))?;
+
+    let mut errors = Vec::new();
+    for record in records.iter().rev() {
+        let (old_path, new_path) = match (record.get(0), record.get(1)) {
+            (Some(old_path), Some(new_path)) => (old_path, new_path),
+            _ => {
+                errors.push(CliError::Usage(format!("{}: Invalid row", log_path)));
+                continue;
+            }
+        };
+        if Path::new(old_path).exists() {
+            errors.push(CliError::Io(format!("{} already exists", old_path)));
+            continue;
+        }
+        if !args.is_dry_run {
+            if let Err(e) = fs::rename(new_path, old_path) {
+                errors.push(CliError::Io(format!("Couldn't rename {}: {}", new_path, e)));
+                continue;
+            }
+        }
+        println!("{} -> {}", new_path, old_path);
+    }
+
+    if !args.is_dry_run && errors.is_empty() {
+        fs::remove_file(log_path)
+            .map_err(|e| CliError::Io(format!("Couldn't remove {}: {}", log_path, e)))?;
+    }
+    Ok(report_errors(&errors, records.len()))
+}
+
+// Moves or copies the files into the directories built from the template, under the destination.
+// Files whose contents are already there are left where they are.
+fn sort(args: Arguments) -> Result<u8, CliError> {
+    let (template, destination, files) = match args.positionals.as_slice() {
+        [template, destination, files @ ..] => (template, destination, files),
+        _ => {
+            return Err(CliError::Usage(String::from(
+                "sort needs a template and a destination",
+            )))
+        }
+    };
+    let components = template
+        .split('/')
+        .filter(|component| !component.is_empty())
+        .map(Template::parse)
+        .collect::<Result<Vec<Template>, String>>()
+        .map_err(CliError::Usage)?;
+    let files = args.get_files(files)?;
+
+    let indexed_files: Vec<(usize, &String)> = files.iter().enumerate().collect();
+    let targets = process_files(&indexed_files, |(i, path)| {
+        let img = open_image(path)?;
+        let context = Context {
+            counter: i + 1,
+            date: get_image_date(&img).or_else(|| get_modified_date(path)),
+            missing: Some(String::from(UNKNOWN_DIRECTORY)),
+        };
+        let mut directory = PathBuf::from(destination);
+        for component in components.iter() {
+            let name = component
+                .render(&img, &context)
+                .map_err(|e| CliError::NotFound(format!("{}: {}", path, e)))?;
+            // Each part must stay a single directory under the destination
+            match Path::new(&name).components().collect::<Vec<_>>().as_slice() {
+                [Component::Normal(_)] => directory.push(name),
+                _ => {
+                    return Err(CliError::Edit(format!(
+                        "{}: {} isn't a valid directory name",
+                        path, name
+                    )))
+                }
+            }
+        }
+        Ok((directory, md5(img.get_contents())))
+    });
+
+    let mut errors = Vec::new();
+    let mut sorted_count = 0;
+    let mut duplicate_count = 0;
+    // The contents of the files in the directories the files are sorted into
+    let mut hashes: HashMap<[u8; 16], PathBuf> = HashMap::new();
+    let mut scanned_directories = Vec::new();
+    let mut taken_paths = Vec::new();
+    for (path, target) in files.iter().zip(targets) {
+        let (directory, hash) = match target {
+            Ok(target) => target,
+            Err(e) => {
+                errors.push(e);
+                continue;
+            }
+        };
+        if !scanned_directories.contains(&directory) {
+            for entry in fs::read_dir(&directory).into_iter().flatten().flatten() {
+                if let Ok(contents) = fs::read(entry.path()) {
+                    hashes.entry(md5(&contents)).or_insert(entry.path());
+                }
+            }
+            scanned_directories.push(directory.clone());
+        }
+
+        let path = Path::new(path);
+        if let Some(existing_path) = hashes.get(&hash) {
+            let is_same_file = fs::canonicalize(existing_path).ok() == fs::canonicalize(path).ok();
+            if !is_same_file {
+                println!(
+                    "{}: Duplicate of {}",
+                    path.display(),
+                    existing_path.display()
+                );
+                duplicate_count += 1;
+            }
+            continue;
+        }
+
+        let target_path = get_free_path(
+            directory.join(path.file_name().unwrap_or_default()),
+            path,
+            &taken_paths,
+        );
+        if !args.is_dry_run {
+            let result = fs::create_dir_all(&directory).and_then(|()| match args.is_copy {
+                true => fs::copy(path, &target_path).map(|_| ()),
+                false => move_file(path, &target_path),
+            });
+            if let Err(e) = result {
+                errors.push(CliError::Io(format!(
+                    "Couldn't write {}: {}",
+                    target_path.display(),
+                    e
+                )));
+                continue;
+            }
+        }
+        println!("{} -> {}", path.display(), target_path.display());
+        hashes.insert(hash, target_path.clone());
+        taken_paths.push(target_path);
+        sorted_count += 1;
+    }
+
+    for e in errors.iter() {
+        eprintln!("{}", e.get_message());
+    }
+    eprintln!(
+        "{} files: {} {}, {} duplicates, {} failed",
+        files.len(),
+        sorted_count,
+        if args.is_copy { "copied" } else { "moved" },
+        duplicate_count,
+        errors.len()
+    );
+    Ok(errors.first().map_or(0, CliError::get_exit_code))
+}
+
+// The modification time of the file, for the images that have no date
+fn get_modified_date(path: &str) -> Option<DateTime> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    let timestamp = modified.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    Some(DateTime::from_timestamp(timestamp as i64))
+}
+
+// Renaming fails across file systems, where the file is copied instead
+fn move_file(path: &Path, target_path: &Path) -> io::Result<()> {
+    if fs::rename(path, target_path).is_ok() {
+        return Ok(());
+    }
+    fs::copy(path, target_path)?;
+    fs::remove_file(path)
+}
+
+// The dates are shifted by the duration or, to sync the clocks of two cameras, by the difference
+// between the true date of the reference image and the one it has
+fn shift_time(args: Arguments) -> Result<u8, CliError> {
+    let seconds = match (&args.duration, &args.reference, &args.true_date) {
+        (Some(duration), None, None) => parse_duration(duration).map_err(CliError::Usage)?,
+        (None, Some(reference), Some(true_date)) => {
+            let true_date = DateTime::from_exif(true_date).ok_or_else(|| {
+                CliError::Usage(format!(
+                    "Invalid date {}, expected YYYY:MM:DD HH:MM:SS",
+                    true_date
+                ))
+            })?;
+            let img = open_image(reference)?;
+            let date = get_image_date(&img)
+                .ok_or_else(|| CliError::NotFound(format!("{}: No date", reference)))?;
+            true_date.get_timestamp() - date.get_timestamp()
+        }
+        _ => {
+            return Err(CliError::Usage(String::from(
+                "shift-time needs either --by or both --reference and --to",
+            )))
+        }
+    };
+    // Written as in the OffsetTime tags
+    let offset = match &args.offset {
+        Some(offset) => Some(parse_offset(offset).map(format_offset).ok_or_else(|| {
+            CliError::Usage(format!(
+                "Invalid time zone {}, expected something like +02:00",
+                offset
+            ))
+        })?),
+        None => None,
+    };
+    let files = args.get_files(&args.positionals)?;
+    let output = args.get_output(files.len())?;
+
+    Ok(for_each_file(&files, |path, out| {
+        let mut img = open_image(path)?;
+        let changes = shift_times(&mut img, seconds, offset.as_deref())
+            .map_err(|e| CliError::Edit(format!("{}: {}", path, e)))?;
+        if changes.is_empty() {
+            return Err(CliError::NotFound(format!("{}: No date to shift", path)));
+        }
+        for change in changes {
+            out.push_str(&format!("{}: {}\n", path, change));
+        }
+        match args.is_dry_run {
+            true => Ok(()),
+            false => write_image(&img, path, output),
+        }
+    }))
+}
+
+// The pictures are placed at their time in UTC, from DateTimeOriginal and OffsetTimeOriginal or
+// the time zone given for the cameras that don't write it
+fn geotag(args: Arguments) -> Result<u8, CliError> {
+    let (gpx_path, files) = match args.positionals.split_first() {
+        Some((gpx_path, files)) => (gpx_path, files),
+        None => return Err(CliError::Usage(String::from("Missing GPX track"))),
+    };
+    let max_gap = match &args.max_gap {
+        Some(max_gap) => parse_duration(max_gap).map_err(CliError::Usage)?.abs(),
+        None => DEFAULT_MAX_GAP,
+    };
+    let default_offset = match &args.offset {
+        Some(offset) => Some(parse_offset(offset).ok_or_else(|| {
+            CliError::Usage(format!(
+                "Invalid time zone {}, expected something like +02:00",
+                offset
+            ))
+        })?),
+        None => None,
+    };
+    let text = fs::read_to_string(gpx_path)
+        .map_err(|e| CliError::Io(format!("Couldn't read {}: {}", gpx_path, e)))?;
+    let points = parse_gpx(&text).map_err(|e| CliError::Usage(format!("{}: {}", gpx_path, e)))?;
+    let files = args.get_files(files)?;
+    let output = args.get_output(files.len())?;
+
+    Ok(for_each_file(&files, |path, out| {
+        let mut img = open_image(path)?;
+        let date = img
+            .get_value_as_string(Tags::DateTimeOriginal)
+            .and_then(|date| DateTime::from_exif(&date))
+            .ok_or_else(|| CliError::NotFound(format!("{}: No DateTimeOriginal", path)))?;
+        let offset = img
+            .get_value_as_string(Tags::OffsetTimeOriginal)
+            .and_then(|offset| parse_offset(offset.trim_end_matches(['\0', ' '])))
+            .or(default_offset)
+            .ok_or_else(|| {
+                CliError::NotFound(format!(
+                    "{}: No OffsetTimeOriginal, the time zone of the camera is needed (--offset)",
+                    path
+                ))
+            })?;
+        let point = get_position_at(&points, date.get_timestamp() - offset, max_gap)
+            .map_err(|e| CliError::NotFound(format!("{}: {}", path, e)))?;
+        let position = Position {
+            latitude: point.latitude,
+            longitude: point.longitude,
+            altitude: point.altitude,
+        };
+        set_position(
+            &mut img,
+            &position,
+            Some(point.timestamp),
+            args.get_precision(),
+        )
+        .map_err(|e| CliError::Edit(format!("{}: {}", path, e)))?;
+
+        let altitude = match point.altitude {
+            Some(altitude) => format!(", {:.1} m", altitude),
+            None => String::new(),
+        };
+        out.push_str(&format!(
+            "{}: {:.6}, {:.6}{}\n",
+            path, point.latitude, point.longitude, altitude
+        ));
+        match args.is_dry_run {
+            true => Ok(()),
+            false => write_image(&img, path, output),
+        }
+    }))
+}
+
+// The positions are ordered by the time they were measured at, the images without a time coming
+// last. The images without a position are reported, as they may have been missed by a geotagging.
+fn export_track(args: Arguments, is_kml: bool) -> Result<u8, CliError> {
+    let files = args.get_files(&args.positionals)?;
+    let results = process_files(&files, |path| {
+        let img = open_image(path)?;
+        let position = get_position(&img)
+            .ok_or_else(|| CliError::NotFound(format!("{}: No GPS position", path)))?;
+        Ok(Waypoint {
+            path: path.clone(),
+            position,
+            timestamp: get_position_time(&img),
+            thumbnail: match is_kml {
+                true => img.get_thumbnail().map(<[u8]>::to_vec),
+                false => None,
+            },
+        })
+    });
+
+    let mut waypoints = Vec::new();
+    let mut errors = Vec::new();
+    for result in results {
+        match result {
+            Ok(waypoint) => waypoints.push(waypoint),
+            Err(e) => errors.push(e),
+        }
+    }
+    waypoints.sort_by_key(|waypoint| (waypoint.timestamp.is_none(), waypoint.timestamp));
+    match is_kml {
+        true => print!("{}", write_kml(&waypoints)),
+        false => print!("{}", write_gpx(&waypoints)),
+    }
+    Ok(report_errors(&errors, files.len()))
+}
+
+// Tags are matched by name in each section, and printed with `-` when they were removed, `+` when
+// they were added and `~` when their value changed. Offsets are left out, as they change whenever
+// a file is rewritten.
+fn diff(args: Arguments) -> Result<u8, CliError> {
+    let (old_path, new_path) = match args.positionals.as_slice() {
+        [old_path, new_path] => (old_path, new_path),
+        _ => return Err(CliError::Usage(String::from("diff needs two images"))),
+    };
+    let (old, new) = (open_image(old_path)?, open_image(new_path)?);
+    if args.is_tui {
+        let output = args.get_output(1)?;
+        tui::run(new, new_path, output, Some((old, old_path))).map_err(CliError::Io)?;
+        return Ok(0);
+    }
+
+    let mut sections: Vec<(String, Vec<String>)> = Vec::new();
+    for row in get_diff_rows(&old, &new) {
+        let line = match (&row.old_value, &row.new_value) {
+            (Some(old_value), Some(new_value)) if old_value == new_value => continue,
+            (Some(old_value), Some(new_value)) => {
+                format!("~ {}: {} -> {}", row.tag, old_value, new_value)
+            }
+            (Some(old_value), None) => format!("- {}: {}", row.tag, old_value),
+            (None, Some(new_value)) => format!("+ {}: {}", row.tag, new_value),
+            (None, None) => continue,
+        };
+        match sections.last_mut() {
+            Some((section, lines)) if *section == row.section => lines.push(line),
+            _ => sections.push((row.section, vec![line])),
+        }
+    }
+
+    for (i, (section, lines)) in sections.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("[{}]", section);
+        for line in lines {
+            println!("{}", line);
+        }
+    }
+    Ok(!sections.is_empty() as u8)
+}
+
+fn run_tui(args: Arguments) -> Result<u8, CliError> {
+    let output = args.get_output(1)?.map(|output| output.to_string());
+    let path = match args.positionals.as_slice() {
+        [path] => path,
+        _ => return Err(CliError::Usage(String::from("tui needs a single image"))),
+    };
+    let img = open_image(path)?;
+
+    tui::run(img, path, output.as_deref(), None).map_err(CliError::Io)?;
+    Ok(0)
+}
+
+fn conflicts(files: Vec<String>) -> Result<u8, CliError> {
+    let img = match files.as_slice() {
+        [path] => open_image(path)?,
+        _ => {
+            return Err(CliError::Usage(String::from(
+                "conflicts needs a single image",
+            )))
+        }
+    };
+
+    let conflicts = img.get_xmp_conflicts();
+    let jfif_conflicts = img.get_jfif_conflicts();
+    if conflicts.is_empty() && jfif_conflicts.is_empty() {
+        println!("No conflicts between the Exif, XMP and JFIF metadata");
+    }
+    for conflict in conflicts {
+        println!(
+            "{} (Exif): {}\n{} (XMP): {}\n",
+            conflict.tag, conflict.exif_value, conflict.property, conflict.xmp_value
+        );
+    }
+    for conflict in jfif_conflicts {
+        println!(
+            "{} (Exif): {}\n{} (JFIF): {}\n",
+            conflict.tag, conflict.exif_value, conflict.tag, conflict.jfif_value
+        );
+    }
+    Ok(0)
+}
+
+// The image and the directory where the extracted files go, which defaults to the current one
+fn get_extraction_args(files: &[String]) -> Result<(&str, &Path), CliError> {
+    match files {
+        [path] => Ok((path, Path::new("."))),
+        [path, out_dir] => Ok((path, Path::new(out_dir))),
+        _ => Err(CliError::Usage(String::from(
+            "Expected an image and an optional directory",
+        ))),
+    }
+}
+
+fn get_stem(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn extract_previews(files: Vec<String>) -> Result<u8, CliError> {
+    let (path, out_dir) = get_extraction_args(&files)?;
+    let img = open_image(path)?;
+
+    for (name, preview) in img.get_preview_images() {
+        let out_path = out_dir.join(format!("{}_{}.jpg", get_stem(path), name.replace(' ', "_")));
+        fs::write(&out_path, preview)
+            .map_err(|e| CliError::Io(format!("Couldn't write {}: {}", out_path.display(), e)))?;
+        println!("{} ({} bytes)", out_path.display(), preview.len());
+    }
+    Ok(0)
+}
+
+fn extract_icc(files: Vec<String>) -> Result<u8, CliError> {
+    let (path, out_dir) = get_extraction_args(&files)?;
+    let img = open_image(path)?;
+    let icc_profile = match img.get_icc_profile() {
+        Some(icc_profile) => icc_profile,
+        None => {
+            return Err(CliError::NotFound(String::from(
+                "The image has no ICC profile",
+            )))
+        }
+    };
+
+    let out_path = out_dir.join(format!("{}.icc", get_stem(path)));
+    fs::write(&out_path, icc_profile.get_data())
+        .map_err(|e| CliError::Io(format!("Couldn't write {}: {}", out_path.display(), e)))?;
+    println!(
+        "{} ({} bytes)",
+        out_path.display(),
+        icc_profile.get_data().len()
+    );
+    Ok(0)
+}
@@ -0,0 +1,282 @@
+use core::fmt;
+
+// A date and time of the Exif format (YYYY:MM:DD HH:MM:SS), without time zone
+#[derive(PartialEq, PartialOrd, Clone, Copy)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+const MIN_DATE: DateTime = DateTime {
+    year: 1,
+    month: 1,
+    day: 1,
+    hour: 0,
+    minute: 0,
+    second: 0,
+};
+const MAX_DATE: DateTime = DateTime {
+    year: 9999,
+    month: 12,
+    day: 31,
+    hour: 23,
+    minute: 59,
+    second: 59,
+};
+
+impl DateTime {
+    // Unknown dates are written with spaces or colons only, they are rejected as well
+    pub fn from_exif(value: &str) -> Option<Self> {
+        let value = value.trim_end_matches(['\0', ' ']);
+        let (date, time) = value.split_once(' ')?;
+        let date: Vec<&str> = date.split(':').collect();
+        let time: Vec<&str> = time.split(':').collect();
+        let (year, month, day, hour, minute, second) = match (date.as_slice(), time.as_slice()) {
+            ([year, month, day], [hour, minute, second]) => (
+                year.parse().ok()?,
+                month.parse().ok()?,
+                day.parse().ok()?,
+                hour.parse().ok()?,
+                minute.parse().ok()?,
+                // Some cameras add fractions of seconds
+                second.split('.').next()?.parse().ok()?,
+            ),
+            _ => return None,
+        };
+
+        let date_time = Self {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        };
+        date_time.is_valid().then_some(date_time)
+    }
+
+    // The date in UTC of a number of seconds since 1970-01-01
+    // See https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    pub fn from_timestamp(timestamp: i64) -> Self {
+        let days = timestamp.div_euclid(86400);
+        let seconds = timestamp.rem_euclid(86400);
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let day_of_era = z.rem_euclid(146097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153; // Starting in March
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+
+        Self {
+            year: (year_of_era + era * 400 + (month <= 2) as i64) as u16,
+            month: month as u8,
+            day: (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u8,
+            hour: (seconds / 3600) as u8,
+            minute: (seconds / 60 % 60) as u8,
+            second: (seconds % 60) as u8,
+        }
+    }
+
+    // The number of seconds since 1970-01-01, the date being in UTC
+    // See https://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    pub fn get_timestamp(&self) -> i64 {
+        let year = self.year as i64 - (self.month <= 2) as i64;
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let shifted_month = (self.month as i64 + 9) % 12; // Starting in March
+        let day_of_year = (153 * shifted_month + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146097 + day_of_era - 719468;
+        days * 86400 + self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64
+    }
+
+    // The date some seconds later (or earlier), if it can still be written with 4 digits
+    pub fn shift(&self, seconds: i64) -> Option<Self> {
+        let timestamp = self.get_timestamp().checked_add(seconds)?;
+        let (min, max) = (MIN_DATE.get_timestamp(), MAX_DATE.get_timestamp());
+        (min..=max)
+            .contains(&timestamp)
+            .then(|| Self::from_timestamp(timestamp))
+    }
+
+    fn is_valid(&self) -> bool {
+        (1..=12).contains(&self.month)
+            && self.day >= 1
+            && self.day <= get_days_in_month(self.year, self.month)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+    }
+
+    // Supports the %Y, %y, %m, %d, %H, %M, %S and %% conversions of strftime
+    pub fn format(&self, format: &str) -> Result<String, String> {
+        let mut s = String::new();
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                s.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('Y') => s.push_str(&format!("{:04}", self.year)),
+                Some('y') => s.push_str(&format!("{:02}", self.year % 100)),
+                Some('m') => s.push_str(&format!("{:02}", self.month)),
+                Some('d') => s.push_str(&format!("{:02}", self.day)),
+                Some('H') => s.push_str(&format!("{:02}", self.hour)),
+                Some('M') => s.push_str(&format!("{:02}", self.minute)),
+                Some('S') => s.push_str(&format!("{:02}", self.second)),
+                Some('%') => s.push('%'),
+                Some(other) => return Err(format!("Unknown date conversion %{}", other)),
+                None => return Err(String::from("The date format ends with %")),
+            }
+        }
+        Ok(s)
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}:{:02}:{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+fn is_leap_year(year: u16) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+fn get_days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// The seconds to add to UTC in a time zone, written as Z, +02:00, +0200 or +02
+pub fn parse_offset(text: &str) -> Option<i64> {
+    if text == "Z" {
+        return Some(0);
+    }
+    let (sign, rest) = match text.split_at_checked(1)? {
+        ("+", rest) => (1, rest),
+        ("-", rest) => (-1, rest),
+        _ => return None,
+    };
+    let (hours, minutes) = match (rest.len(), rest.split_once(':')) {
+        (_, Some((hours, minutes))) => (hours, minutes),
+        (4, None) => rest.split_at(2),
+        (2, None) => (rest, "00"),
+        _ => return None,
+    };
+    let is_two_digits = |part: &str| part.len() == 2 && part.bytes().all(|b| b.is_ascii_digit());
+    if !is_two_digits(hours) || !is_two_digits(minutes) {
+        return None;
+    }
+    let (hours, minutes) = (hours.parse::<i64>().ok()?, minutes.parse::<i64>().ok()?);
+    (hours <= 14 && minutes < 60).then_some(sign * (hours * 3600 + minutes * 60))
+}
+
+// A time zone as written in the OffsetTime tags, such as +02:00
+pub fn format_offset(seconds: i64) -> String {
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let minutes = seconds.abs() / 60;
+    format!("{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_timestamp() {
+        assert_eq!(
+            DateTime::from_timestamp(0).to_string(),
+            "1970:01:01 00:00:00"
+        );
+        assert_eq!(
+            DateTime::from_timestamp(-1).to_string(),
+            "1969:12:31 23:59:59"
+        );
+        assert_eq!(
+            DateTime::from_timestamp(951782400).to_string(),
+            "2000:02:29 00:00:00"
+        );
+        assert_eq!(
+            DateTime::from_timestamp(1709251199).to_string(),
+            "2024:02:29 23:59:59"
+        );
+        assert_eq!(
+            DateTime::from_timestamp(1714746612).to_string(),
+            "2024:05:03 14:30:12"
+        );
+    }
+
+    #[test]
+    fn timestamp_round_trip() {
+        let (min, max) = (MIN_DATE.get_timestamp(), MAX_DATE.get_timestamp());
+        let mut timestamp = min;
+        while timestamp <= max {
+            assert_eq!(
+                DateTime::from_timestamp(timestamp).get_timestamp(),
+                timestamp
+            );
+            timestamp += 86400 * 7 + 3601;
+        }
+        assert_eq!(
+            DateTime::from_timestamp(max).to_string(),
+            "9999:12:31 23:59:59"
+        );
+        assert_eq!(
+            DateTime::from_timestamp(min).to_string(),
+            "0001:01:01 00:00:00"
+        );
+    }
+
+    #[test]
+    fn leap_years() {
+        for (year, is_leap) in [(1900, false), (2000, true), (2023, false), (2024, true)] {
+            assert_eq!(is_leap_year(year), is_leap);
+            let date = format!("{}:02:29 12:00:00", year);
+            assert_eq!(DateTime::from_exif(&date).is_some(), is_leap);
+        }
+        // The day after February 28th
+        let date = DateTime::from_exif("2024:02:28 12:00:00").unwrap();
+        assert_eq!(
+            date.shift(86400).unwrap().to_string(),
+            "2024:02:29 12:00:00"
+        );
+        let date = DateTime::from_exif("2100:02:28 12:00:00").unwrap();
+        assert_eq!(
+            date.shift(86400).unwrap().to_string(),
+            "2100:03:01 12:00:00"
+        );
+    }
+
+    #[test]
+    fn offsets() {
+        assert_eq!(parse_offset("Z"), Some(0));
+        assert_eq!(parse_offset("+02:00"), Some(7200));
+        assert_eq!(parse_offset("-0930"), Some(-34200));
+        assert_eq!(parse_offset("+14"), Some(50400));
+        assert_eq!(parse_offset("+15:00"), None);
+        assert_eq!(parse_offset("+2:00"), None);
+        assert_eq!(parse_offset("02:00"), None);
+        assert_eq!(format_offset(-34200), "-09:30");
+        assert_eq!(format_offset(0), "+00:00");
+    }
+}
@@ -0,0 +1,70 @@
+use crate::exif::ExifImage;
+use crate::tags::Tag;
+
+// A tag of a section (an IFD, the XMP, the IPTC...) with its decoded value in each image, if it
+// has one
+pub struct DiffRow {
+    pub section: String,
+    pub tag: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+impl DiffRow {
+    pub fn is_changed(&self) -> bool {
+        self.old_value != self.new_value
+    }
+}
+
+// Tags are matched by name in each section, in the order of the old image followed by the tags
+// that only the new one has. Offsets are left out, as they change whenever a file is rewritten.
+pub fn get_diff_rows(old: &ExifImage, new: &ExifImage) -> Vec<DiffRow> {
+    let old_sections = old.get_all_sections();
+    let new_sections = new.get_all_sections();
+
+    let mut names: Vec<&String> = old_sections.iter().map(|(name, _)| name).collect();
+    for (name, _) in new_sections.iter() {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    let mut rows = Vec::new();
+    for name in names {
+        let get_tags = |sections: &[(String, Vec<(String, String)>)]| {
+            sections
+                .iter()
+                .find(|(other, _)| other == name)
+                .map(|(_, tags)| tags.clone())
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|(tag, _)| !Tag::from_name(tag).is_some_and(Tag::is_offset))
+                .collect::<Vec<(String, String)>>()
+        };
+        // Names can appear several times in a section (PNG keywords), each one is matched once
+        let mut new_tags: Vec<Option<(String, String)>> =
+            get_tags(&new_sections).into_iter().map(Some).collect();
+        for (tag, old_value) in get_tags(&old_sections) {
+            let new_value = new_tags
+                .iter_mut()
+                .find(|new_tag| new_tag.as_ref().is_some_and(|(other, _)| *other == tag))
+                .and_then(|new_tag| new_tag.take())
+                .map(|(_, new_value)| new_value);
+            rows.push(DiffRow {
+                section: name.clone(),
+                tag,
+                old_value: Some(old_value),
+                new_value,
+            });
+        }
+        for (tag, new_value) in new_tags.into_iter().flatten() {
+            rows.push(DiffRow {
+                section: name.clone(),
+                tag,
+                old_value: None,
+                new_value: Some(new_value),
+            });
+        }
+    }
+    rows
+}
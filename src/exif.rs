@@ -1,98 +1,1917 @@
 use core::fmt;
 
+use crate::checksum::md5;
 use crate::formats::ImageFormat;
+use crate::gps::{get_coordinate_as_string, is_coordinate_tag};
+use crate::icc::{read_jpeg_icc, read_png_icc, ICCProfile};
+use crate::iptc::{is_repeatable, IPTCData};
+use crate::jfif::{
+    get_density_conflicts, read_jpeg_jfif, write_jpeg_jfif, DensityConflict, JFIFSegment,
+    JFXXThumbnail,
+};
+use crate::jpeg::{
+    build_segment, get_comments, get_exif_segment, replace_segments, APP1, COM,
+    EXIF_IDENTIFIER_CODE,
+};
+use crate::mpf::{update_mpf_offsets, MPFSegment};
+use crate::mwg::{XMPConflict, SYNCED_FIELDS};
+use crate::photoshop::{
+    read_jpeg_resources, write_jpeg_resources, PhotoshopResource, IPTC_DIGEST_RESOURCE_ID,
+    IPTC_RESOURCE_ID,
+};
+use crate::png::{
+    self, build_chunk, build_text_chunk, is_raw_profile, is_raw_profile_exif_chunk, is_text_chunk,
+    is_xmp_chunk, InternationalText, COMMENT_KEYWORD, XMP_KEYWORD,
+};
+use crate::raw::RAFHeader;
 use crate::tags::{Tag, Tags};
+use crate::webp;
+use crate::writer::{get_ifd_size, write_ifd, IFDEntry};
+use crate::xmp::{read_jpeg_xmp, write_jpeg_xmp, XMPMeta, XMPPackets};
 
-use crate::arrays::{get_tuples_vec_as_string, get_vec_as_string, index_of_sub_array};
+use crate::arrays::{get_tuples_vec_as_string, get_vec_as_string};
 
 // In bytes
 pub const TIFF_HEADER_SIZE: usize = 8;
 pub const INTEROPERABILITY_FIELD_SIZE: usize = 12;
 
+// Protects against IFDs pointing to each other
+const MAX_CHAINED_IFDS: usize = 16;
+const JPEG_SOI: [u8; 2] = [0xFF, 0xD8];
+
+// Tags that only describe the image they are in, which aren't copied to other images
+const IMAGE_DATA_TAGS: [Tag; 11] = [
+    Tags::ImageWidth,
+    Tags::ImageLength,
+    Tags::BitsPerSample,
+    Tags::Compression,
+    Tags::PhotometricInterpretation,
+    Tags::SamplesPerPixel,
+    Tags::RowsPerStrip,
+    Tags::PlanarConfiguration,
+    Tags::YCbCrSubSampling,
+    Tags::PixelXDimension,
+    Tags::PixelYDimension,
+];
+
+// Tags pointing to the strips or the tiles of the image data
+const IMAGE_DATA_OFFSET_TAGS: [usize; 2] = [
+    Tags::StripOffsets.0,
+    324, // TileOffsets
+];
+
+// Tags of the 0th IFD that don't describe the image data, which strip removes from TIFF-based files
+const DESCRIPTIVE_TAGS: [Tag; 7] = [
+    Tags::ImageDescription,
+    Tags::Make,
+    Tags::Model,
+    Tags::Software,
+    Tags::DateTime,
+    Tags::Artist,
+    Tags::Copyright,
+];
+
+// Tags that identify the camera or its owner
+const PRIVATE_TAGS: [Tag; 5] = [
+    Tags::BodySerialNumber,
+    Tags::LensSerialNumber,
+    Tags::CameraOwnerName,
+    Tags::ImageUniqueID,
+    Tags::MakerNote,
+];
+
+// The XMP properties that identify the camera, its owner or the file, removed along the tags
+const PRIVATE_XMP_PROPERTIES: [&str; 9] = [
+    "aux:SerialNumber",
+    "aux:LensSerialNumber",
+    "exifEX:BodySerialNumber",
+    "exifEX:LensSerialNumber",
+    "exifEX:CameraOwnerName",
+    "exifEX:ImageUniqueID",
+    "xmpMM:DocumentID",
+    "xmpMM:InstanceID",
+    "xmpMM:OriginalDocumentID",
+];
+
+// Tags that change how the image is displayed, which strip keeps unless asked otherwise
+pub const DISPLAY_TAGS: [Tag; 2] = [Tags::Orientation, Tags::ColorSpace];
+
+// The sections other than the IFDs whose values can be edited, see set_section_value
+pub const EDITABLE_SECTIONS: [&str; 4] = ["JFIF", "IPTC", "Comments", "XMP"];
+
+// A tag, or a value of one of the other sections
+pub enum Field {
+    Tag(Tag),
+    Section(String, String),
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Field::Tag(tag) => write!(f, "{}", tag),
+            Field::Section(section, name) => write!(f, "{}:{}", section, name),
+        }
+    }
+}
+
+impl Field {
+    // A value of an editable section, named after it as in XMP:dc:title
+    pub fn from_name(name: &str) -> Option<Self> {
+        let (section, name) = name.split_once(':')?;
+        let section = EDITABLE_SECTIONS
+            .iter()
+            .find(|other| other.eq_ignore_ascii_case(section))?;
+        Some(Field::Section(section.to_string(), name.to_string()))
+    }
+
+    // The field of a value listed by get_all_sections, if it can be edited. Only the tags of the
+    // IFDs that ExifImage can rewrite and the top-level XMP properties are.
+    pub fn from_section(section: &str, name: &str) -> Option<Self> {
+        if let Some(ifd_type) = IFDTypes::from_name(section) {
+            let tag = Tag::from_name(name)?;
+            return (tag.get_ifd_type() == Some(ifd_type)).then_some(Field::Tag(tag));
+        }
+        if section == "XMP" && check_xmp_property_name(name).is_err() {
+            return None;
+        }
+        EDITABLE_SECTIONS
+            .contains(&section)
+            .then(|| Field::Section(section.to_string(), name.to_string()))
+    }
+
+    pub fn get_value(&self, img: &ExifImage) -> Option<String> {
+        match self {
+            Field::Tag(tag) => img.get_value_as_string(*tag),
+            Field::Section(section, name) => img.get_section_value(section, name),
+        }
+    }
+
+    pub fn remove(&self, img: &mut ExifImage) -> Result<(), String> {
+        match self {
+            Field::Tag(tag) => match tag.get_ifd_type() {
+                Some(ifd_type) => img.remove_tag(ifd_type, *tag),
+                None => Err(format!("{} can't be removed", tag)),
+            },
+            Field::Section(section, name) => img.remove_section_value(section, name),
+        }
+    }
+}
+
 pub struct ExifImage {
-    //image_format: ImageFormat,
+    image_format: ImageFormat,
     tiff: TIFFHeader,
     ifd_0: IFD,
-    ifd_exif: IFD,
-    ifd_gps: IFD,
-    //ifd_1: IFD,
-    slice: Vec<u8>,
+    ifd_exif: Option<IFD>,
+    ifd_gps: Option<IFD>,
+    ifd_interop: Option<IFD>,
+    ifd_1: Option<IFD>,
+    // IFD2 and beyond (CR2) and the SubIFDs (NEF, ARW)
+    other_ifds: Vec<(String, IFD)>,
+    contents: Vec<u8>,
+    // Not in the spec
+    tiff_start: Option<usize>, // Offset of the TIFF header in the file, if there is Exif data
+    // Exif data that isn't stored as is in the file, such as the hex encoded profile that
+    // ImageMagick writes in PNG text chunks. It is only used when there is no regular Exif data.
+    decoded_exif: Option<Vec<u8>>,
+    xmp: Option<String>,
+    extended_xmp: Option<String>,
+    xmp_meta: Option<XMPMeta>,
+    extended_xmp_meta: Option<XMPMeta>,
+    iptc: Option<IPTCData>,
+    icc_profile: Option<ICCProfile>,
+    jfif: Option<JFIFSegment>,
+    jfxx: Option<JFXXThumbnail>,
+    mpf: Option<MPFSegment>,
+    comments: Vec<(String, String)>, // Keyword and text
+    warnings: Vec<String>,
 }
 
-impl ExifImage {
-    pub fn from(img_contents: Vec<u8>, img_format: ImageFormat) -> Self {
-        let exif_identifier_code = match img_format {
-            ImageFormat::Jpeg => vec![0x45, 0x78, 0x69, 0x66], // Exif
-            ImageFormat::Png => vec![0x65, 0x58, 0x49, 0x66],  // eXIf
-        };
-        let exif_chunk_start =
-            match index_of_sub_array(img_contents.clone(), exif_identifier_code.clone()) {
-                Some(magic_start) => {
-                    magic_start
-                        + exif_identifier_code.len()
-                        + if img_format == ImageFormat::Jpeg {
-                            2
-                        } else {
-                            0
+impl ExifImage {
+    pub fn from(img_contents: Vec<u8>, img_format: ImageFormat) -> Result<Self, String> {
+        let (tiff_start, mut warnings) = get_tiff_start(img_contents.as_ref(), img_format)?;
+        let decoded_exif = match (img_format, tiff_start) {
+            (ImageFormat::Png, None) => match png::get_raw_profile_exif(img_contents.as_ref()) {
+                Some(Ok(tiff)) => Some(tiff),
+                Some(Err(e)) => {
+                    warnings.push(format!("Couldn't decode the raw Exif profile: {}", e));
+                    None
+                }
+                None => None,
+            },
+            _ => None,
+        };
+        let slice = match (tiff_start, &decoded_exif) {
+            (Some(tiff_start), _) => &img_contents[tiff_start..],
+            (None, Some(decoded_exif)) => decoded_exif.as_ref(),
+            (None, None) => &[],
+        };
+
+        // Files without Exif data get an empty 0th IFD, which is written on the first edit
+        let (tiff, ifd_0) = match tiff_start.or(decoded_exif.as_ref().map(|_| 0)) {
+            Some(_) => {
+                if slice.len() < TIFF_HEADER_SIZE {
+                    return Err(String::from(
+                        "The exif chunk is too small to contain a TIFF header",
+                    ));
+                }
+                let tiff = TIFFHeader::from(slice[0..TIFF_HEADER_SIZE].as_ref());
+                let ifd_0 = IFD::from_tiff(
+                    slice,
+                    tiff.zero_th_ifd_offset as usize,
+                    tiff.is_little_endian,
+                )?;
+                (tiff, ifd_0)
+            }
+            None => (TIFFHeader::new(true), IFD::new(true)),
+        };
+        let is_little_endian = tiff.is_little_endian;
+        let ifd_exif = get_sub_ifd(slice, &ifd_0, Tags::ExifOffset, is_little_endian);
+        let ifd_gps = get_sub_ifd(slice, &ifd_0, Tags::GPSOffset, is_little_endian);
+        let ifd_interop = ifd_exif.as_ref().and_then(|ifd_exif| {
+            get_sub_ifd(slice, ifd_exif, Tags::InteroperabilityIFD, is_little_endian)
+        });
+
+        // Follow the chain of IFDs, making sure that a malformed file can't make us loop forever
+        let mut chained_ifds = Vec::new();
+        let mut visited = vec![ifd_0.get_offset()];
+        let mut next_ifd_offset = ifd_0.get_next_ifd_offset();
+        while next_ifd_offset != 0
+            && !visited.contains(&next_ifd_offset)
+            && chained_ifds.len() < MAX_CHAINED_IFDS
+        {
+            let ifd = match IFD::from_tiff(slice, next_ifd_offset, is_little_endian) {
+                Ok(ifd) => ifd,
+                Err(_) => break,
+            };
+            visited.push(next_ifd_offset);
+            next_ifd_offset = ifd.get_next_ifd_offset();
+            chained_ifds.push(ifd);
+        }
+        let mut chained_ifds = chained_ifds.into_iter();
+        let ifd_1 = chained_ifds.next();
+        let mut other_ifds: Vec<(String, IFD)> = chained_ifds
+            .enumerate()
+            .map(|(i, ifd)| (format!("IFD{}", i + 2), ifd))
+            .collect();
+
+        if let Some(interop) = ifd_0.get_interop_for_tag(Tags::SubIFDs.0) {
+            for (i, offset) in interop.get_uints(slice).into_iter().enumerate() {
+                if let Ok(ifd) = IFD::from_tiff(slice, offset as usize, is_little_endian) {
+                    other_ifds.push((format!("SubIFD{}", i), ifd));
+                }
+            }
+        }
+
+        let xmp_packets = match img_format {
+            ImageFormat::Jpeg => read_jpeg_xmp(img_contents.as_ref()),
+            _ => get_xmp_packet(img_contents.as_ref(), img_format, &ifd_0, slice),
+        };
+        warnings.extend(xmp_packets.warnings);
+        let (xmp, extended_xmp) = (xmp_packets.standard, xmp_packets.extended);
+
+        let mut parse_xmp = |packet: &Option<String>| {
+            packet
+                .as_deref()
+                .and_then(|packet| match XMPMeta::from(packet) {
+                    Ok(meta) => Some(meta),
+                    Err(e) => {
+                        warnings.push(format!("Couldn't parse the XMP packet: {}", e));
+                        None
+                    }
+                })
+        };
+        let xmp_meta = parse_xmp(&xmp);
+        let extended_xmp_meta = parse_xmp(&extended_xmp);
+
+        let iptc = match img_format {
+            ImageFormat::Jpeg => get_iptc(img_contents.as_ref(), &mut warnings),
+            _ => None,
+        };
+
+        let (jfif, jfxx) = match img_format {
+            ImageFormat::Jpeg => match read_jpeg_jfif(img_contents.as_ref()) {
+                Ok(segments) => segments,
+                Err(e) => {
+                    warnings.push(format!("Couldn't read the JFIF segment: {}", e));
+                    (None, None)
+                }
+            },
+            _ => (None, None),
+        };
+
+        let mpf = match img_format {
+            ImageFormat::Jpeg => MPFSegment::from_jpeg(img_contents.as_ref()).unwrap_or_else(|e| {
+                warnings.push(format!("Couldn't read the MPF segment: {}", e));
+                None
+            }),
+            _ => None,
+        };
+
+        let comments = get_text_comments(img_contents.as_ref(), img_format, &mut warnings);
+
+        let icc_profile = get_icc_profile(
+            img_contents.as_ref(),
+            img_format,
+            &ifd_0,
+            slice,
+            &mut warnings,
+        );
+        let color_space = ifd_exif
+            .as_ref()
+            .and_then(|ifd_exif| ifd_exif.get_interop_for_tag(Tags::ColorSpace.0))
+            .and_then(|interop| interop.get_uints(slice).first().copied());
+        if color_space == Some(1) && icc_profile.as_ref().is_some_and(|icc| icc.is_display_p3()) {
+            warnings.push(String::from(
+                "The ColorSpace tag says sRGB but the embedded ICC profile is Display P3",
+            ));
+        }
+
+        Ok(Self {
+            image_format: img_format,
+            tiff,
+            ifd_0,
+            ifd_exif,
+            ifd_gps,
+            ifd_interop,
+            ifd_1,
+            other_ifds,
+            contents: img_contents,
+            tiff_start,
+            decoded_exif,
+            xmp,
+            extended_xmp,
+            xmp_meta,
+            extended_xmp_meta,
+            iptc,
+            icc_profile,
+            jfif,
+            jfxx,
+            mpf,
+            comments,
+            warnings,
+        })
+    }
+
+    // Problems found in the structure of the file that didn't prevent reading it
+    pub fn get_warnings(&self) -> &[String] {
+        self.warnings.as_ref()
+    }
+
+    pub fn get_image_format(&self) -> ImageFormat {
+        self.image_format
+    }
+
+    // The whole file, including the modifications made to the tags
+    pub fn get_contents(&self) -> &[u8] {
+        self.contents.as_ref()
+    }
+
+    pub fn is_little_endian(&self) -> bool {
+        self.tiff.is_little_endian
+    }
+
+    pub fn has_exif(&self) -> bool {
+        self.tiff_start.is_some() || self.decoded_exif.is_some()
+    }
+
+    // The TIFF structure, all the offsets of the IFDs are relative to its start
+    pub fn get_slice(&self) -> &[u8] {
+        match (self.tiff_start, &self.decoded_exif) {
+            (Some(tiff_start), _) => &self.contents[tiff_start..],
+            (None, Some(decoded_exif)) => decoded_exif.as_ref(),
+            (None, None) => &[],
+        }
+    }
+
+    pub fn get_xmp(&self) -> Option<&str> {
+        self.xmp.as_deref()
+    }
+
+    // The part of the XMP that didn't fit in the standard packet of a JPEG file
+    pub fn get_extended_xmp(&self) -> Option<&str> {
+        self.extended_xmp.as_deref()
+    }
+
+    pub fn get_xmp_meta(&self) -> Option<&XMPMeta> {
+        self.xmp_meta.as_ref()
+    }
+
+    pub fn get_extended_xmp_meta(&self) -> Option<&XMPMeta> {
+        self.extended_xmp_meta.as_ref()
+    }
+
+    // Replaces the XMP packets of the file, or removes them if `standard` is None. Only JPEG files
+    // have an extended packet, which is split in as many segments as needed.
+    pub fn set_xmp(
+        &mut self,
+        standard: Option<&str>,
+        extended: Option<&str>,
+    ) -> Result<(), String> {
+        if extended.is_some() && self.image_format != ImageFormat::Jpeg {
+            return Err(String::from(
+                "Only JPEG files can have an extended XMP packet",
+            ));
+        }
+
+        match self.image_format {
+            ImageFormat::Jpeg => {
+                let contents = write_jpeg_xmp(self.contents.as_ref(), standard, extended)?;
+                self.set_contents(contents)
+            }
+            ImageFormat::Png => {
+                let chunks = match standard {
+                    Some(standard) => vec![build_chunk(
+                        png::ITXT,
+                        &InternationalText::new(XMP_KEYWORD, standard).get_data(),
+                    )?],
+                    None => Vec::new(),
+                };
+                let contents = png::replace_chunks(self.contents.as_ref(), is_xmp_chunk, &chunks)?;
+                self.set_contents(contents)
+            }
+            ImageFormat::Webp => {
+                let contents = webp::replace_chunk(
+                    self.contents.as_ref(),
+                    webp::XMP,
+                    standard.map(|standard| standard.as_bytes()),
+                )?;
+                self.set_contents(contents)
+            }
+            _ if self.image_format.is_tiff_based() => match standard {
+                Some(standard) => self.set_tag(
+                    IFDTypes::TIFF,
+                    IFDEntry {
+                        tag: Tags::XMLPacket.0 as u16,
+                        data_type: 1,
+                        count: standard.len() as u32,
+                        data: standard.as_bytes().to_vec(),
+                    },
+                ),
+                None => self.remove_tag(IFDTypes::TIFF, Tags::XMLPacket),
+            },
+            _ => Err(format!(
+                "Writing XMP is not supported for {} files",
+                self.image_format
+            )),
+        }
+    }
+
+    // Sets an XMP property (such as dc:title or xmp:Rating) from a single value, see
+    // XMPMeta::set_text
+    pub fn set_xmp_property(&mut self, name: &str, value: &str) -> Result<(), String> {
+        self.edit_xmp(|meta| meta.set_text(name, value))
+    }
+
+    // Sets an XMP property holding a list, such as the keywords (dc:subject)
+    pub fn set_xmp_list(&mut self, name: &str, values: &[String]) -> Result<(), String> {
+        self.edit_xmp(|meta| meta.set_list(name, values))
+    }
+
+    pub fn remove_xmp_property(&mut self, name: &str) -> Result<(), String> {
+        self.edit_xmp(|meta| match meta.remove_property(name)? {
+            true => Ok(()),
+            false => Err(format!("There is no {} property", name)),
+        })
+    }
+
+    // Applies the modification to the standard XMP packet, the extended one is kept as is
+    fn edit_xmp<F: FnOnce(&mut XMPMeta) -> Result<(), String>>(
+        &mut self,
+        edit: F,
+    ) -> Result<(), String> {
+        // Rewriting a packet that couldn't be parsed would lose its contents
+        let mut meta = match (&self.xmp, &self.xmp_meta) {
+            (_, Some(meta)) => meta.clone(),
+            (None, None) => XMPMeta::new(),
+            (Some(_), None) => {
+                return Err(String::from(
+                    "The XMP packet of the file is invalid, it can't be edited",
+                ))
+            }
+        };
+        edit(&mut meta)?;
+
+        let extended = self.extended_xmp.clone();
+        self.set_xmp(Some(meta.to_packet().as_str()), extended.as_deref())
+    }
+
+    pub fn get_iptc(&self) -> Option<&IPTCData> {
+        self.iptc.as_ref()
+    }
+
+    // The values of an IPTC dataset, such as By-line or Keywords
+    pub fn get_iptc_values(&self, name: &str) -> Result<Vec<String>, String> {
+        match &self.iptc {
+            Some(iptc) => iptc.get_values(name),
+            None => IPTCData::new().get_values(name),
+        }
+    }
+
+    // Sets the values of an IPTC dataset, or removes it if `values` is empty. The IPTC data is
+    // stored in the Photoshop image resources, only JPEG files are supported.
+    pub fn set_iptc(&mut self, name: &str, values: &[String]) -> Result<(), String> {
+        if self.image_format != ImageFormat::Jpeg {
+            return Err(format!(
+                "Writing IPTC is not supported for {} files",
+                self.image_format
+            ));
+        }
+
+        let mut iptc = self.iptc.clone().unwrap_or_else(IPTCData::new);
+        iptc.set_values(name, values)?;
+        self.write_iptc(&iptc)
+    }
+
+    pub fn remove_iptc(&mut self, name: &str) -> Result<(), String> {
+        match self.get_iptc_values(name)?.is_empty() {
+            true => Err(format!("There is no {} dataset", name)),
+            false => self.set_iptc(name, &[]),
+        }
+    }
+
+    // Replaces the IPTC resource, the other image resources are kept as they are
+    fn write_iptc(&mut self, iptc: &IPTCData) -> Result<(), String> {
+        // Rewriting resources that couldn't be parsed would lose them
+        let mut resources = read_jpeg_resources(self.contents.as_ref())?.unwrap_or_default();
+        let has_iptc = resources
+            .iter()
+            .any(|resource| resource.id == IPTC_RESOURCE_ID);
+        if has_iptc && self.iptc.is_none() {
+            return Err(String::from(
+                "The IPTC data of the file is invalid, it can't be edited",
+            ));
+        }
+
+        let data = iptc.to_bytes();
+        if iptc.is_empty() {
+            resources.retain(|resource| {
+                resource.id != IPTC_RESOURCE_ID && resource.id != IPTC_DIGEST_RESOURCE_ID
+            });
+        } else {
+            match resources
+                .iter_mut()
+                .find(|resource| resource.id == IPTC_RESOURCE_ID)
+            {
+                Some(resource) => resource.data = data.clone(),
+                None => resources.push(PhotoshopResource::new(IPTC_RESOURCE_ID, data.clone())),
+            }
+            // Photoshop would otherwise consider that the IPTC data is outdated
+            if let Some(resource) = resources
+                .iter_mut()
+                .find(|resource| resource.id == IPTC_DIGEST_RESOURCE_ID)
+            {
+                resource.data = md5(&data).to_vec();
+            }
+        }
+
+        let contents = write_jpeg_resources(self.contents.as_ref(), &resources)?;
+        self.set_contents(contents)
+    }
+
+    pub fn get_icc_profile(&self) -> Option<&ICCProfile> {
+        self.icc_profile.as_ref()
+    }
+
+    pub fn get_jfif(&self) -> Option<&JFIFSegment> {
+        self.jfif.as_ref()
+    }
+
+    // Replaces the JFIF segment, or adds it right after SOI
+    pub fn set_jfif(&mut self, jfif: &JFIFSegment) -> Result<(), String> {
+        if self.image_format != ImageFormat::Jpeg {
+            return Err(format!(
+                "{} files don't have JFIF segments",
+                self.image_format
+            ));
+        }
+
+        let contents = write_jpeg_jfif(self.contents.as_ref(), jfif)?;
+        self.set_contents(contents)
+    }
+
+    // Differences between the JFIF densities and the resolution tags of the 0th IFD
+    pub fn get_jfif_conflicts(&self) -> Vec<DensityConflict> {
+        let jfif = match &self.jfif {
+            Some(jfif) => jfif,
+            None => return Vec::new(),
+        };
+
+        let get_resolution = |tag| {
+            self.get_rationals(IFDTypes::TIFF, tag)
+                .and_then(|values| values.first().copied())
+        };
+        get_density_conflicts(
+            jfif,
+            self.get_uints(IFDTypes::TIFF, Tags::ResolutionUnit)
+                .and_then(|values| values.first().copied()),
+            [
+                get_resolution(Tags::XResolution),
+                get_resolution(Tags::YResolution),
+            ],
+        )
+    }
+
+    // Removes what the preset covers, except the `kept_tags`
+    pub fn strip(&mut self, preset: StripPreset, kept_tags: &[Tag]) -> Result<(), String> {
+        match preset {
+            StripPreset::All => self.strip_all(kept_tags),
+            StripPreset::GPS => self.remove_present_tags(IFDTypes::GPS, |_| true),
+            StripPreset::Privacy => {
+                self.remove_present_tags(IFDTypes::GPS, |_| true)?;
+                self.remove_present_tags(IFDTypes::Exif, |tag| PRIVATE_TAGS.contains(&tag))?;
+                self.remove_present_xmp_properties(&PRIVATE_XMP_PROPERTIES)
+            }
+            StripPreset::KeepCopyright => {
+                self.strip_all(&[kept_tags, &[Tags::Artist, Tags::Copyright]].concat())
+            }
+        }
+    }
+
+    // Removes the Exif data, the XMP packets, the IPTC datasets and the comments. The 0th IFD of
+    // TIFF-based files describes the image data, so only its descriptive tags are removed.
+    fn strip_all(&mut self, kept_tags: &[Tag]) -> Result<(), String> {
+        let is_kept = |entry: &IFDEntry| kept_tags.contains(&Tag(entry.tag as usize));
+        if self.image_format.is_tiff_based() {
+            self.remove_present_tags(IFDTypes::TIFF, |tag| {
+                DESCRIPTIVE_TAGS.contains(&tag) && !kept_tags.contains(&tag)
+            })?;
+            for ifd_type in [IFDTypes::Exif, IFDTypes::GPS] {
+                if self.get_ifd(ifd_type).is_some() {
+                    self.edit_ifd(ifd_type, |entries| entries.retain(is_kept))?;
+                }
+            }
+        } else if self.has_exif() {
+            let kept_entries: Vec<(IFDTypes, Vec<IFDEntry>)> = [IFDTypes::TIFF, IFDTypes::Exif]
+                .into_iter()
+                .map(|ifd_type| {
+                    let mut entries = self.get_entries(&ifd_type);
+                    entries.retain(|entry| {
+                        is_kept(entry) && Tag(entry.tag as usize).get_ifd_type() == Some(ifd_type)
+                    });
+                    (ifd_type, entries)
+                })
+                .collect();
+            let is_little_endian = self.is_little_endian();
+            let contents = self.replace_exif_chunk(None)?;
+            self.set_contents(contents)?;
+
+            // The kept tags are written in a new Exif chunk
+            for (ifd_type, mut entries) in kept_entries {
+                if entries.is_empty() {
+                    continue;
+                }
+                if is_little_endian != self.is_little_endian() {
+                    entries.iter_mut().for_each(IFDEntry::swap_byte_order);
+                }
+                self.set_tags(ifd_type, entries)?;
+            }
+        }
+
+        if self.xmp.is_some() {
+            self.set_xmp(None, None)?;
+        }
+        if self.iptc.is_some() {
+            self.write_iptc(&IPTCData::new())?;
+        }
+
+        let mut keywords: Vec<String> = self
+            .comments
+            .iter()
+            .map(|(keyword, _)| keyword.clone())
+            .collect();
+        keywords.sort();
+        keywords.dedup();
+        match self.image_format {
+            ImageFormat::Jpeg if !keywords.is_empty() => self.set_comment(None)?,
+            ImageFormat::Png => {
+                for keyword in keywords {
+                    self.set_png_text(&keyword, None)?;
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    // Removes the tags of the IFD that match, without creating the IFD if the image doesn't have it
+    fn remove_present_tags<F: Fn(Tag) -> bool>(
+        &mut self,
+        ifd_type: IFDTypes,
+        is_removed: F,
+    ) -> Result<(), String> {
+        let tags: Vec<Tag> = self
+            .get_entries(&ifd_type)
+            .iter()
+            .map(|entry| Tag(entry.tag as usize))
+            .filter(|tag| is_removed(*tag))
+            .collect();
+        match tags.is_empty() {
+            true => Ok(()),
+            false => self.remove_tags(ifd_type, &tags),
+        }
+    }
+
+    // Removes the properties from both XMP packets, which are only rewritten if they had some
+    fn remove_present_xmp_properties(&mut self, names: &[&str]) -> Result<(), String> {
+        let mut is_modified = false;
+        let mut packets = Vec::new();
+        for (i, (packet, meta)) in [
+            (&self.xmp, &self.xmp_meta),
+            (&self.extended_xmp, &self.extended_xmp_meta),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let mut meta = match meta {
+                Some(meta) => meta.clone(),
+                None => {
+                    packets.push(packet.clone());
+                    continue;
+                }
+            };
+            let mut is_removed = false;
+            for name in names {
+                is_removed |= meta.remove_property(name)?;
+            }
+            is_modified |= is_removed;
+            // The extended packet is an x:xmpmeta element, without the xpacket wrapper and padding
+            packets.push(match (is_removed, i) {
+                (true, 0) => Some(meta.to_packet()),
+                (true, _) => Some(meta.to_xml()),
+                (false, _) => packet.clone(),
+            });
+        }
+
+        match is_modified {
+            true => self.set_xmp(packets[0].as_deref(), packets[1].as_deref()),
+            false => Ok(()),
+        }
+    }
+
+    // Replaces the contents of the file after an edit, and parses them again
+    fn set_contents(&mut self, mut contents: Vec<u8>) -> Result<(), String> {
+        if self.image_format == ImageFormat::Jpeg {
+            update_mpf_offsets(self.contents.as_ref(), &mut contents)?;
+        }
+
+        *self = ExifImage::from(contents, self.image_format)?;
+        Ok(())
+    }
+
+    // The JPEG comments or the PNG text chunks, as keywords and texts
+    pub fn get_comments(&self) -> &[(String, String)] {
+        self.comments.as_ref()
+    }
+
+    // Replaces the comments of the file (the COM segments of JPEG files, the Comment text of PNG
+    // files), or removes them if `text` is None
+    pub fn set_comment(&mut self, text: Option<&str>) -> Result<(), String> {
+        match self.image_format {
+            ImageFormat::Jpeg => {
+                let segments = match text {
+                    Some(text) => vec![build_segment(COM, text.as_bytes())?],
+                    None => Vec::new(),
+                };
+                let contents = replace_segments(
+                    self.contents.as_ref(),
+                    |segment, _| segment.marker == COM,
+                    &segments,
+                )?;
+                self.set_contents(contents)
+            }
+            ImageFormat::Png => self.set_png_text(COMMENT_KEYWORD, text),
+            _ => Err(format!(
+                "Comments are not supported for {} files",
+                self.image_format
+            )),
+        }
+    }
+
+    // Replaces the text chunks with the given keyword, or removes them if `text` is None
+    pub fn set_png_text(&mut self, keyword: &str, text: Option<&str>) -> Result<(), String> {
+        if self.image_format != ImageFormat::Png {
+            return Err(format!(
+                "{} files don't have text chunks",
+                self.image_format
+            ));
+        }
+        if keyword == XMP_KEYWORD {
+            return Err(String::from(
+                "The XMP packet can only be edited through its properties",
+            ));
+        }
+
+        let chunks = match text {
+            Some(text) => vec![build_text_chunk(keyword, text)?],
+            None => Vec::new(),
+        };
+        let contents = png::replace_chunks(
+            self.contents.as_ref(),
+            |chunk, slice| is_text_chunk(chunk, slice, keyword),
+            &chunks,
+        )?;
+        self.set_contents(contents)
+    }
+
+    pub fn get_ifds(&self) -> Vec<(String, &IFD)> {
+        if !self.has_exif() {
+            return Vec::new();
+        }
+
+        let mut ifds = vec![(String::from("IFD0"), &self.ifd_0)];
+        if let Some(ifd_exif) = &self.ifd_exif {
+            ifds.push((String::from("Exif"), ifd_exif));
+        }
+        if let Some(ifd_gps) = &self.ifd_gps {
+            ifds.push((String::from("GPS"), ifd_gps));
+        }
+        if let Some(ifd_interop) = &self.ifd_interop {
+            ifds.push((String::from("Interop"), ifd_interop));
+        }
+        if let Some(ifd_1) = &self.ifd_1 {
+            ifds.push((String::from("IFD1"), ifd_1));
+        }
+        for (name, ifd) in self.other_ifds.iter() {
+            ifds.push((name.clone(), ifd));
+        }
+
+        ifds
+    }
+
+    pub fn get_infos_as_string(&self) -> String {
+        let mut s = format!("{}\n", self.tiff.get_as_string());
+        for (name, ifd) in self.get_ifds() {
+            s.push_str(format!("{}: {}\n", name, ifd.get_as_string()).as_str());
+        }
+
+        s
+    }
+
+    // The JFIF segment and the tags of each IFD, followed by the MPF index, the IPTC datasets, the
+    // ICC profile and the XMP properties
+    pub fn get_all_sections(&self) -> Vec<(String, Vec<(String, String)>)> {
+        let mut sections: Vec<(String, Vec<(String, String)>)> = Vec::new();
+        if let Some(jfif) = &self.jfif {
+            let mut tags = jfif.get_all_tags();
+            if let Some(jfxx) = &self.jfxx {
+                tags.push((String::from("JFXXThumbnail"), jfxx.get_as_string()));
+            }
+            sections.push((String::from("JFIF"), tags));
+        }
+
+        for (name, ifd) in self.get_ifds() {
+            let tags = match name.as_str() {
+                // Its tags would be named and decoded as the GPS tags of the same ids
+                "Interop" => ifd
+                    .get_entries(self.get_slice())
+                    .iter()
+                    .map(|entry| {
+                        let tag = Tag(entry.tag as usize);
+                        let name = match tag.get_interop_name() {
+                            Some(name) => name.to_string(),
+                            None => tag.to_string(),
+                        };
+                        (name, entry.get_value_as_text(self.is_little_endian()))
+                    })
+                    .collect(),
+                _ => ifd.get_all_tags(self.get_slice()),
+            };
+            sections.push((name, tags));
+        }
+
+        // The coordinates are shown in decimal degrees too, with the hemisphere of their Ref tag
+        for (_, tags) in sections.iter_mut().filter(|(name, _)| name == "GPS") {
+            for (name, value) in tags.iter_mut() {
+                if let Some(coordinate) = Tag::from_name(name)
+                    .filter(|tag| is_coordinate_tag(*tag))
+                    .and_then(|tag| get_coordinate_as_string(self, tag))
+                {
+                    *value = coordinate;
+                }
+            }
+        }
+
+        // The embedded profile is what applications actually use, so it goes next to ColorSpace
+        if let Some(icc_profile) = &self.icc_profile {
+            let color_space = format!("{}", Tags::ColorSpace);
+            for (_, tags) in sections.iter_mut() {
+                if let Some(i) = tags.iter().position(|(name, _)| *name == color_space) {
+                    let description = icc_profile.get_description().unwrap_or("Unnamed");
+                    tags.insert(i + 1, (String::from("ICCProfile"), description.to_string()));
+                }
+            }
+        }
+
+        if let Some(mpf) = &self.mpf {
+            sections.push((
+                String::from("MPF"),
+                mpf.get_all_tags(self.contents.as_ref()),
+            ));
+        }
+
+        if let Some(iptc) = self.iptc.as_ref().filter(|iptc| !iptc.is_empty()) {
+            sections.push((String::from("IPTC"), iptc.get_all_tags()));
+        }
+
+        if let Some(icc_profile) = &self.icc_profile {
+            sections.push((String::from("ICC"), icc_profile.get_all_tags()));
+        }
+
+        if !self.comments.is_empty() {
+            sections.push((String::from("Comments"), self.comments.clone()));
+        }
+
+        let mut xmp_properties = Vec::new();
+        for meta in [&self.xmp_meta, &self.extended_xmp_meta]
+            .into_iter()
+            .flatten()
+        {
+            xmp_properties.extend(meta.get_all_properties());
+        }
+        if !xmp_properties.is_empty() {
+            sections.push((String::from("XMP"), xmp_properties));
+        }
+
+        sections
+    }
+
+    // A value of the sections other than the IFDs, named as in get_all_sections, in the form
+    // set_section_value accepts: the values of lists are separated by commas
+    pub fn get_section_value(&self, section: &str, name: &str) -> Option<String> {
+        match section.to_uppercase().as_str() {
+            "XMP" => [&self.xmp_meta, &self.extended_xmp_meta]
+                .into_iter()
+                .flatten()
+                .find_map(|meta| {
+                    meta.get_list(name)
+                        .map(|values| values.join(", "))
+                        .or_else(|| meta.get_text(name))
+                }),
+            "IPTC" => {
+                let values = self.get_iptc_values(name).ok()?;
+                (!values.is_empty()).then(|| values.join(", "))
+            }
+            "JFIF" => self
+                .jfif
+                .as_ref()?
+                .get_all_tags()
+                .into_iter()
+                .find(|(other, _)| other.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value),
+            "COMMENTS" => self
+                .comments
+                .iter()
+                .find(|(keyword, _)| keyword.eq_ignore_ascii_case(name))
+                .map(|(_, text)| text.clone()),
+            _ => None,
+        }
+    }
+
+    // Sets a value of the sections other than the IFDs: a value of the JFIF segment (which is added
+    // if needed), an IPTC dataset, whose values are separated by commas if it is repeatable, a
+    // comment (the JPEG comments are all replaced by the new one, PNG files can have any keyword)
+    // or an XMP property (only the top-level ones can be edited)
+    pub fn set_section_value(
+        &mut self,
+        section: &str,
+        name: &str,
+        value: &str,
+    ) -> Result<(), String> {
+        let split_list = || -> Vec<String> {
+            value
+                .split(',')
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+                .collect()
+        };
+
+        match section.to_uppercase().as_str() {
+            "XMP" => {
+                check_xmp_property_name(name)?;
+                let is_list = match &self.xmp_meta {
+                    Some(meta) => meta.is_list(name),
+                    None => XMPMeta::new().is_list(name),
+                };
+                match is_list {
+                    true => self.set_xmp_list(name, &split_list()),
+                    false => self.set_xmp_property(name, value),
+                }
+            }
+            "IPTC" => match is_repeatable(name) {
+                true => self.set_iptc(name, &split_list()),
+                false => self.set_iptc(name, &[value.to_string()]),
+            },
+            "JFIF" => {
+                let mut jfif = self.jfif.clone().unwrap_or_else(JFIFSegment::new);
+                jfif.set_value(name, value)?;
+                self.set_jfif(&jfif)
+            }
+            "COMMENTS" => match self.image_format {
+                // Only a summary of the raw profiles is shown
+                ImageFormat::Png if is_raw_profile(name) => {
+                    Err(format!("{} can't be edited", name))
+                }
+                ImageFormat::Png => {
+                    let keyword = self.get_comment_keyword(name);
+                    self.set_png_text(&keyword, Some(value))
+                }
+                _ if name.eq_ignore_ascii_case(COMMENT_KEYWORD) => self.set_comment(Some(value)),
+                _ => Err(format!(
+                    "The comments of {} files are named {}",
+                    self.image_format, COMMENT_KEYWORD
+                )),
+            },
+            _ => Err(format!("The {} values can't be edited", section)),
+        }
+    }
+
+    pub fn remove_section_value(&mut self, section: &str, name: &str) -> Result<(), String> {
+        match section.to_uppercase().as_str() {
+            "XMP" => {
+                check_xmp_property_name(name)?;
+                self.remove_xmp_property(name)
+            }
+            "IPTC" => self.remove_iptc(name),
+            "COMMENTS" => match self.image_format {
+                ImageFormat::Png => {
+                    let keyword = self.get_comment_keyword(name);
+                    self.set_png_text(&keyword, None)
+                }
+                _ => self.set_comment(None),
+            },
+            _ => Err(format!("The {} values can't be removed", section)),
+        }
+    }
+
+    // PNG keywords are case-sensitive, the one of an existing text is kept
+    fn get_comment_keyword(&self, name: &str) -> String {
+        self.comments
+            .iter()
+            .find(|(keyword, _)| keyword.eq_ignore_ascii_case(name))
+            .map_or(name, |(keyword, _)| keyword)
+            .to_string()
+    }
+
+    pub fn get_all_tags_as_string(&self) -> String {
+        let mut s = String::new();
+        for (i, (name, tags_and_values)) in self.get_all_sections().into_iter().enumerate() {
+            if i != 0 {
+                s.push('\n');
+            }
+            s.push_str(format!("[{}]\n", name).as_str());
+            if let Some(max_len) = tags_and_values.iter().map(|split| split.0.len()).max() {
+                for t in tags_and_values.iter() {
+                    s.push_str(
+                        format!("{}{}: {}\n", t.0, " ".repeat(max_len - t.0.len()), t.1).as_str(),
+                    );
+                }
+            };
+        }
+        s
+    }
+
+    // The JPEG thumbnail of the 1st IFD
+    pub fn get_thumbnail(&self) -> Option<&[u8]> {
+        self.ifd_1
+            .as_ref()
+            .and_then(|ifd_1| ifd_1.get_jpeg_thumbnail(self.get_slice()))
+    }
+
+    // Returns the JPEG previews embedded in the file, along with where they were found
+    pub fn get_preview_images(&self) -> Vec<(String, &[u8])> {
+        let slice = self.get_slice();
+        let mut previews = Vec::new();
+
+        for (name, ifd) in self.get_ifds() {
+            if let Some(preview) = ifd.get_jpeg_preview(slice) {
+                previews.push((name, preview));
+            }
+        }
+
+        // Panasonic stores a full size JPEG (with its own Exif data) in the 0th IFD
+        if let Some(interop) = self.ifd_0.get_interop_for_tag(Tags::JpgFromRaw.0) {
+            if let Some(jpeg) = get_jpeg_at(slice, interop.cvalue_offset, interop.ccount) {
+                previews.push((String::from("JpgFromRaw"), jpeg));
+            }
+        }
+
+        // Secondary images of the Multi-Picture Format, such as depth maps and large previews
+        if let Some(mpf) = &self.mpf {
+            previews.extend(mpf.get_images(self.contents.as_ref()));
+        }
+
+        if let Some(jpeg) = self.jfxx.as_ref().and_then(|jfxx| jfxx.get_jpeg()) {
+            previews.push((String::from("JFXX thumbnail"), jpeg));
+        }
+
+        if self.image_format == ImageFormat::Raf {
+            if let Some(header) = RAFHeader::from(self.contents.as_ref()) {
+                previews.push((
+                    String::from("Embedded JPEG"),
+                    header.get_jpeg(self.contents.as_ref()),
+                ));
+            }
+        }
+
+        previews
+    }
+
+    pub fn set_tag(&mut self, ifd_type: IFDTypes, entry: IFDEntry) -> Result<(), String> {
+        let tag = Tag(entry.tag as usize);
+        self.edit_ifd(ifd_type, |entries| {
+            entries.retain(|e| e.tag != entry.tag);
+            entries.push(entry);
+        })?;
+        self.sync_xmp(ifd_type, tag)
+    }
+
+    // Sets several tags of the same IFD at once, the entries must use the byte order of the file
+    pub fn set_tags(
+        &mut self,
+        ifd_type: IFDTypes,
+        new_entries: Vec<IFDEntry>,
+    ) -> Result<(), String> {
+        let tags: Vec<Tag> = new_entries
+            .iter()
+            .map(|entry| Tag(entry.tag as usize))
+            .collect();
+        self.edit_ifd(ifd_type, |entries| {
+            entries.retain(|e| !tags.contains(&Tag(e.tag as usize)));
+            entries.extend(new_entries);
+        })?;
+        for tag in tags {
+            self.sync_xmp(ifd_type, tag)?;
+        }
+        Ok(())
+    }
+
+    // The tags of the 0th, Exif and GPS IFDs that the filter keeps, except the ones that describe
+    // the image data, in the byte order of this image
+    pub fn get_entries_to_copy(&self, filter: &TagFilter) -> Vec<(IFDTypes, Vec<IFDEntry>)> {
+        [IFDTypes::TIFF, IFDTypes::Exif, IFDTypes::GPS]
+            .into_iter()
+            .map(|ifd_type| {
+                let entries: Vec<IFDEntry> = self
+                    .get_entries(&ifd_type)
+                    .into_iter()
+                    .filter(|entry| {
+                        let tag = Tag(entry.tag as usize);
+                        tag.get_ifd_type() == Some(ifd_type)
+                            && !IMAGE_DATA_TAGS.contains(&tag)
+                            && filter.is_kept(ifd_type, tag)
+                    })
+                    .collect();
+                (ifd_type, entries)
+            })
+            .filter(|(_, entries)| !entries.is_empty())
+            .collect()
+    }
+
+    // Copies the tags of another image that the filter keeps. The values are converted if the
+    // byte orders differ.
+    pub fn copy_tags_from(&mut self, source: &ExifImage, filter: &TagFilter) -> Result<(), String> {
+        for (ifd_type, mut entries) in source.get_entries_to_copy(filter) {
+            if source.is_little_endian() != self.is_little_endian() {
+                entries.iter_mut().for_each(IFDEntry::swap_byte_order);
+            }
+            self.set_tags(ifd_type, entries)?;
+        }
+        Ok(())
+    }
+
+    // Sets a tag from its text form (see IFDEntry::from_string), keeping the type it already has
+    // in the file, or using the one the specs give it
+    pub fn set_tag_from_string(&mut self, tag: Tag, value: &str) -> Result<(), String> {
+        let (ifd_type, entry) = self.get_entry_from_string(tag, value)?;
+        self.set_tag(ifd_type, entry)
+    }
+
+    // The entry of the tag with the value, in the IFD the tag belongs to. The tag keeps the type
+    // it has in the file.
+    pub fn get_entry_from_string(
+        &self,
+        tag: Tag,
+        value: &str,
+    ) -> Result<(IFDTypes, IFDEntry), String> {
+        let ifd_type = match tag.get_ifd_type() {
+            Some(ifd_type) => ifd_type,
+            None => return Err(format!("{} can't be edited", tag)),
+        };
+        let data_type = match self.get_interop(ifd_type, tag) {
+            Some(interop) => *interop.get_data_type(),
+            None => match tag.get_data_type() {
+                Some(data_type) => data_type,
+                None => return Err(format!("The type of {} is unknown", tag)),
+            },
+        };
+
+        let entry = IFDEntry::from_string(tag, data_type, value, self.tiff.is_little_endian)?;
+        Ok((ifd_type, entry))
+    }
+
+    pub fn remove_tag(&mut self, ifd_type: IFDTypes, tag: Tag) -> Result<(), String> {
+        self.remove_tags(ifd_type, &[tag])
+    }
+
+    pub fn remove_tags(&mut self, ifd_type: IFDTypes, tags: &[Tag]) -> Result<(), String> {
+        self.edit_ifd(ifd_type, |entries| {
+            entries.retain(|e| !tags.contains(&Tag(e.tag as usize)));
+        })?;
+        for tag in tags {
+            self.sync_xmp(ifd_type, *tag)?;
+        }
+        Ok(())
+    }
+
+    // Updates the XMP properties matching the edited tag, so that readers of either store see the
+    // same value. Files without XMP are left as they are.
+    fn sync_xmp(&mut self, ifd_type: IFDTypes, tag: Tag) -> Result<(), String> {
+        let mut meta = match &self.xmp_meta {
+            Some(meta) => meta.clone(),
+            None => return Ok(()),
+        };
+
+        let mut is_synced = false;
+        for field in SYNCED_FIELDS.iter() {
+            let (field_ifd_type, tags) = field.get_tags();
+            if field_ifd_type == ifd_type && tags.contains(&tag) {
+                field.write_xmp(&mut meta, self)?;
+                is_synced = true;
+            }
+        }
+
+        if is_synced {
+            self.edit_xmp(|xmp_meta| {
+                *xmp_meta = meta;
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+
+    // Values that differ between the Exif tags and their XMP counterparts
+    pub fn get_xmp_conflicts(&self) -> Vec<XMPConflict> {
+        match &self.xmp_meta {
+            Some(meta) => SYNCED_FIELDS
+                .iter()
+                .flat_map(|field| field.get_conflicts(meta, self))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn get_ifd(&self, ifd_type: IFDTypes) -> Option<&IFD> {
+        match ifd_type {
+            IFDTypes::TIFF => Some(&self.ifd_0),
+            IFDTypes::Exif => self.ifd_exif.as_ref(),
+            IFDTypes::GPS => self.ifd_gps.as_ref(),
+        }
+    }
+
+    pub fn get_interop(&self, ifd_type: IFDTypes, tag: Tag) -> Option<&InteroperabilityField> {
+        self.get_ifd(ifd_type)?.get_interop_for_tag(tag.0)
+    }
+
+    pub fn get_ascii(&self, ifd_type: IFDTypes, tag: Tag) -> Option<String> {
+        self.get_interop(ifd_type, tag)?
+            .get_value_ascii(self.get_slice())
+    }
+
+    pub fn get_rationals(&self, ifd_type: IFDTypes, tag: Tag) -> Option<Vec<(u32, u32)>> {
+        self.get_interop(ifd_type, tag)?
+            .get_value_rational(self.get_slice())
+    }
+
+    // Values of BYTE, SHORT and LONG tags
+    pub fn get_uints(&self, ifd_type: IFDTypes, tag: Tag) -> Option<Vec<u32>> {
+        Some(self.get_interop(ifd_type, tag)?.get_uints(self.get_slice()))
+    }
+
+    // The field of the tag in the IFD it belongs to. The GPS and Interoperability IFDs reuse the
+    // same ids, so no other IFD is searched. The tags that describe the image data belong to no
+    // IFD, they are taken from the 0th IFD, or else from the 1st one.
+    fn find_interop(&self, tag: Tag) -> Option<&InteroperabilityField> {
+        match tag.get_ifd_type() {
+            Some(ifd_type) => self.get_interop(ifd_type, tag),
+            None => self
+                .ifd_0
+                .get_interop_for_tag(tag.0)
+                .or_else(|| self.ifd_1.as_ref()?.get_interop_for_tag(tag.0)),
+        }
+    }
+
+    // The decoded value of a tag, looked for in the IFD it belongs to. The coordinates are given
+    // in signed decimal degrees, followed by their degrees, minutes and seconds.
+    pub fn get_value_as_string(&self, tag: Tag) -> Option<String> {
+        if let Some(coordinate) = is_coordinate_tag(tag)
+            .then(|| get_coordinate_as_string(self, tag))
+            .flatten()
+        {
+            return Some(coordinate);
+        }
+        let interop = self.find_interop(tag)?;
+        Some(interop.get_value_as_string(self.get_slice()))
+    }
+
+    pub fn get_entry(&self, tag: Tag) -> Option<IFDEntry> {
+        let interop = self.find_interop(tag)?;
+        Some(interop.get_entry(self.get_slice()))
+    }
+
+    // The raw value as text, in the form `set_tag_from_string` accepts
+    pub fn get_value_as_text(&self, tag: Tag) -> Option<String> {
+        let entry = self.get_entry(tag)?;
+        Some(entry.get_value_as_text(self.is_little_endian()))
+    }
+
+    pub fn get_entries(&self, ifd_type: &IFDTypes) -> Vec<IFDEntry> {
+        match self.get_ifd(*ifd_type) {
+            Some(ifd) => ifd.get_entries(self.get_slice()),
+            None => Vec::new(),
+        }
+    }
+
+    // Applies the modification to the entries of the given IFD, writes the result back into the
+    // file and parses it again
+    fn edit_ifd<F: FnOnce(&mut Vec<IFDEntry>)>(
+        &mut self,
+        ifd_type: IFDTypes,
+        edit: F,
+    ) -> Result<(), String> {
+        if !self.image_format.is_editable() {
+            return Err(format!(
+                "Editing is not supported for {} files",
+                self.image_format
+            ));
+        }
+
+        let mut entries = self.get_entries(&ifd_type);
+        edit(&mut entries);
+
+        let contents = if self.image_format.is_tiff_based() {
+            self.append_ifd(ifd_type, entries)?
+        } else {
+            let tiff = self.build_tiff(ifd_type, entries)?;
+            self.replace_exif_chunk(Some(tiff.as_ref()))?
+        };
+
+        self.set_contents(contents)
+    }
+
+    // Rebuilds the whole TIFF structure of the Exif chunk, with the given IFD replaced by `entries`.
+    // The IFDs are laid out one after the other, followed by the thumbnail and by the SubIFDs and
+    // the IFDs chained after the 1st one
+    fn build_tiff(&self, ifd_type: IFDTypes, entries: Vec<IFDEntry>) -> Result<Vec<u8>, String> {
+        let is_little_endian = self.tiff.is_little_endian;
+        let pointer_tags = [
+            Tags::ExifOffset.0,
+            Tags::GPSOffset.0,
+            Tags::InteroperabilityIFD.0,
+            Tags::SubIFDs.0,
+            Tags::JPEGInterchangeFormat.0,
+        ];
+        let without_pointers = |mut entries: Vec<IFDEntry>| {
+            entries.retain(|e| !pointer_tags.contains(&(e.tag as usize)));
+            entries
+        };
+
+        let mut ifd_0 = without_pointers(self.get_entries(&IFDTypes::TIFF));
+        let mut ifd_exif = without_pointers(self.get_entries(&IFDTypes::Exif));
+        let mut ifd_gps = without_pointers(self.get_entries(&IFDTypes::GPS));
+        match ifd_type {
+            IFDTypes::TIFF => ifd_0 = without_pointers(entries),
+            IFDTypes::Exif => ifd_exif = without_pointers(entries),
+            IFDTypes::GPS => ifd_gps = without_pointers(entries),
+        }
+        // The Interoperability IFD can only be reached through the Exif IFD
+        let ifd_interop = match (&self.ifd_interop, ifd_exif.is_empty()) {
+            (Some(ifd), false) => without_pointers(ifd.get_entries(self.get_slice())),
+            _ => Vec::new(),
+        };
+
+        // Only JPEG thumbnails can be moved around, the strips of an uncompressed one would
+        // point to the old data
+        let thumbnail = self.get_thumbnail();
+        let mut ifd_1 = match (&self.ifd_1, thumbnail) {
+            (Some(ifd), Some(_)) => without_pointers(ifd.get_entries(self.get_slice())),
+            _ => Vec::new(),
+        };
+
+        // The other IFDs are carried over as they are, unless they point to image data or to
+        // other IFDs, which would be left behind
+        let mut sub_ifds = Vec::new();
+        let mut chained_ifds = Vec::new();
+        for (name, ifd) in self.other_ifds.iter() {
+            let entries = ifd.get_entries(self.get_slice());
+            if entries.iter().any(|e| {
+                pointer_tags.contains(&(e.tag as usize))
+                    || IMAGE_DATA_OFFSET_TAGS.contains(&(e.tag as usize))
+            }) {
+                return Err(format!(
+                    "The {} of the exif data points to image data or to other IFDs, it can't be rewritten",
+                    name
+                ));
+            }
+            match name.starts_with("SubIFD") {
+                true => sub_ifds.push(entries),
+                false => chained_ifds.push(entries),
+            }
+        }
+        if !chained_ifds.is_empty() && ifd_1.is_empty() {
+            return Err(String::from(
+                "The IFDs chained after the 1st one can't be kept without its thumbnail",
+            ));
+        }
+
+        // Placeholders, so that the size of each IFD is known before computing the offsets
+        if !ifd_exif.is_empty() {
+            ifd_0.push(IFDEntry::long(Tags::ExifOffset, 0, is_little_endian));
+        }
+        if !ifd_gps.is_empty() {
+            ifd_0.push(IFDEntry::long(Tags::GPSOffset, 0, is_little_endian));
+        }
+        let get_sub_ifds_entry = |offsets: &[u32]| IFDEntry {
+            tag: Tags::SubIFDs.0 as u16,
+            data_type: 4,
+            count: offsets.len() as u32,
+            data: offsets
+                .iter()
+                .flat_map(|offset| match is_little_endian {
+                    true => offset.to_le_bytes(),
+                    false => offset.to_be_bytes(),
+                })
+                .collect(),
+        };
+        if !sub_ifds.is_empty() {
+            ifd_0.push(get_sub_ifds_entry(&vec![0; sub_ifds.len()]));
+        }
+        if !ifd_interop.is_empty() {
+            ifd_exif.push(IFDEntry::long(
+                Tags::InteroperabilityIFD,
+                0,
+                is_little_endian,
+            ));
+        }
+        if !ifd_1.is_empty() {
+            ifd_1.push(IFDEntry::long(
+                Tags::JPEGInterchangeFormat,
+                0,
+                is_little_endian,
+            ));
+        }
+
+        // Empty IFDs are not written at all
+        let get_size = |entries: &Vec<IFDEntry>| {
+            if entries.is_empty() {
+                0
+            } else {
+                get_ifd_size(entries)
+            }
+        };
+        let ifd_0_offset = TIFF_HEADER_SIZE;
+        let ifd_exif_offset = ifd_0_offset + get_size(&ifd_0);
+        let ifd_interop_offset = ifd_exif_offset + get_size(&ifd_exif);
+        let ifd_gps_offset = ifd_interop_offset + get_size(&ifd_interop);
+        let ifd_1_offset = ifd_gps_offset + get_size(&ifd_gps);
+        let thumbnail_offset = ifd_1_offset + get_size(&ifd_1);
+        let mut other_offset = match (ifd_1.is_empty(), thumbnail) {
+            (false, Some(thumbnail)) => thumbnail_offset + thumbnail.len(),
+            _ => thumbnail_offset,
+        };
+        // IFDs start on a word boundary
+        let padding = other_offset % 2;
+        other_offset += padding;
+        let mut get_offsets = |ifds: &Vec<Vec<IFDEntry>>| {
+            ifds.iter()
+                .map(|entries| {
+                    let offset = other_offset;
+                    other_offset += get_ifd_size(entries);
+                    offset as u32
+                })
+                .collect::<Vec<u32>>()
+        };
+        let sub_ifd_offsets = get_offsets(&sub_ifds);
+        let chained_ifd_offsets = get_offsets(&chained_ifds);
+        if other_offset > u32::MAX as usize {
+            return Err(String::from("The exif data is too big"));
+        }
+
+        let set_pointer = |entries: &mut Vec<IFDEntry>, tag: Tag, offset: usize| {
+            if let Some(entry) = entries.iter_mut().find(|e| e.tag as usize == tag.0) {
+                *entry = IFDEntry::long(tag, offset as u32, is_little_endian);
+            }
+        };
+        set_pointer(&mut ifd_0, Tags::ExifOffset, ifd_exif_offset);
+        set_pointer(&mut ifd_0, Tags::GPSOffset, ifd_gps_offset);
+        set_pointer(&mut ifd_exif, Tags::InteroperabilityIFD, ifd_interop_offset);
+        set_pointer(&mut ifd_1, Tags::JPEGInterchangeFormat, thumbnail_offset);
+        if let Some(entry) = ifd_0.iter_mut().find(|e| e.tag as usize == Tags::SubIFDs.0) {
+            *entry = get_sub_ifds_entry(&sub_ifd_offsets);
+        }
+
+        let mut tiff = Vec::new();
+        tiff.extend_from_slice(if is_little_endian {
+            &[0x49, 0x49, 0x2A, 0x00]
+        } else {
+            &[0x4D, 0x4D, 0x00, 0x2A]
+        });
+        tiff.extend_from_slice(&if is_little_endian {
+            (ifd_0_offset as u32).to_le_bytes()
+        } else {
+            (ifd_0_offset as u32).to_be_bytes()
+        });
+        tiff.extend(write_ifd(
+            &ifd_0,
+            ifd_0_offset,
+            if ifd_1.is_empty() {
+                0
+            } else {
+                ifd_1_offset as u32
+            },
+            is_little_endian,
+        ));
+        if !ifd_exif.is_empty() {
+            tiff.extend(write_ifd(&ifd_exif, ifd_exif_offset, 0, is_little_endian));
+        }
+        if !ifd_interop.is_empty() {
+            tiff.extend(write_ifd(
+                &ifd_interop,
+                ifd_interop_offset,
+                0,
+                is_little_endian,
+            ));
+        }
+        if !ifd_gps.is_empty() {
+            tiff.extend(write_ifd(&ifd_gps, ifd_gps_offset, 0, is_little_endian));
+        }
+        if let (false, Some(thumbnail)) = (ifd_1.is_empty(), thumbnail) {
+            let next_ifd_offset = chained_ifd_offsets.first().copied().unwrap_or(0);
+            tiff.extend(write_ifd(
+                &ifd_1,
+                ifd_1_offset,
+                next_ifd_offset,
+                is_little_endian,
+            ));
+            tiff.extend_from_slice(thumbnail);
+        }
+        tiff.extend(vec![0; padding]);
+        for (entries, offset) in sub_ifds.iter().zip(sub_ifd_offsets) {
+            tiff.extend(write_ifd(entries, offset as usize, 0, is_little_endian));
+        }
+        for (i, (entries, offset)) in chained_ifds.iter().zip(&chained_ifd_offsets).enumerate() {
+            let next_ifd_offset = chained_ifd_offsets.get(i + 1).copied().unwrap_or(0);
+            tiff.extend(write_ifd(
+                entries,
+                *offset as usize,
+                next_ifd_offset,
+                is_little_endian,
+            ));
+        }
+
+        Ok(tiff)
+    }
+
+    // Replaces the chunk holding the TIFF structure in the JPEG, PNG or WebP file, adds one if
+    // the file had no Exif data, or removes it if `tiff` is None
+    fn replace_exif_chunk(&self, tiff: Option<&[u8]>) -> Result<Vec<u8>, String> {
+        let contents = self.contents.as_ref();
+        match self.image_format {
+            ImageFormat::Jpeg => {
+                let segments =
+                    match tiff {
+                        Some(tiff) => {
+                            vec![build_segment(APP1, &[&EXIF_IDENTIFIER_CODE, tiff].concat())
+                                .map_err(|_| {
+                                    String::from(
+                                        "The exif data doesn't fit in a single APP1 segment",
+                                    )
+                                })?]
                         }
-                }
-                None => panic!("Couldn't get the start of the exif chunk"),
-            };
-        let tiff = TIFFHeader::from(
-            img_contents[exif_chunk_start..exif_chunk_start + TIFF_HEADER_SIZE].as_ref(),
-        );
-        let is_little_endian = tiff.is_little_endian;
+                        None => Vec::new(),
+                    };
+                replace_segments(
+                    contents,
+                    |segment, slice| segment.get_tiff_start(slice) == self.tiff_start,
+                    &segments,
+                )
+            }
+            ImageFormat::Png => {
+                // A raw Exif profile would be outdated once the eXIf chunk is written
+                let contents = png::replace_chunks(contents, is_raw_profile_exif_chunk, &[])?;
+                let chunks = match tiff {
+                    Some(tiff) => vec![build_chunk(png::EXIF, tiff)?],
+                    None => Vec::new(),
+                };
+                png::replace_chunks(
+                    &contents,
+                    |chunk, _| chunk.chunk_type == *png::EXIF,
+                    &chunks,
+                )
+            }
+            ImageFormat::Webp => webp::replace_chunk(contents, webp::EXIF, tiff),
+            _ => Err(format!(
+                "{} files don't have an exif chunk",
+                self.image_format
+            )),
+        }
+    }
 
-        let ifd_0_start = exif_chunk_start + TIFF_HEADER_SIZE + tiff.zero_th_ifd_offset as usize;
-        let ifd_0 = IFD::from(img_contents[ifd_0_start..].as_ref(), is_little_endian);
+    // The image data of raw files is referenced by absolute offsets all over the file, so instead
+    // of rebuilding the TIFF structure, the modified IFD is appended to the file and the pointer
+    // to it is updated. The old IFD is left untouched.
+    fn append_ifd(&self, ifd_type: IFDTypes, entries: Vec<IFDEntry>) -> Result<Vec<u8>, String> {
+        let is_little_endian = self.tiff.is_little_endian;
+        let tiff_start = self.tiff_start.unwrap_or(0);
+        let mut contents = self.contents.clone();
+        let mut append = |entries: &[IFDEntry], next_ifd_offset: usize| {
+            if !contents.len().is_multiple_of(2) {
+                contents.push(0);
+            }
+            let offset = contents.len() - tiff_start;
+            contents.extend(write_ifd(
+                entries,
+                offset,
+                next_ifd_offset as u32,
+                is_little_endian,
+            ));
+            offset
+        };
 
-        let ifd_exif_start = match ifd_0.get_offset_for_tag(Tags::ExifOffset) {
-            Some(idf_exif_start) => exif_chunk_start + idf_exif_start,
-            None => panic!("Exif IFD start not found"),
+        // The MakerNotes of Canon, Sony or Nikon raw files hold offsets relative to the start of
+        // the file, so an unchanged MakerNote is left where it is and the new IFD points to it
+        let maker_note = self
+            .ifd_exif
+            .as_ref()
+            .and_then(|ifd| ifd.get_interop_for_tag(Tags::MakerNote.0));
+        let entries: Vec<IFDEntry> = match (ifd_type, maker_note) {
+            (IFDTypes::Exif, Some(maker_note)) => {
+                let data = maker_note.get_entry(self.get_slice()).data;
+                entries
+                    .into_iter()
+                    .map(|entry| match entry.tag as usize == Tags::MakerNote.0 {
+                        true if data.len() > 4 && entry.data == data => IFDEntry {
+                            data: maker_note.value_offset.to_vec(),
+                            ..entry
+                        },
+                        _ => entry,
+                    })
+                    .collect()
+            }
+            _ => entries,
         };
-        let ifd_exif = IFD::from(img_contents[ifd_exif_start..].as_ref(), is_little_endian);
 
-        let ifd_gps_start = match ifd_0.get_offset_for_tag(Tags::GPSOffset) {
-            Some(idf_gps_start) => exif_chunk_start + idf_gps_start,
-            None => panic!("GPS IFD start not found"),
+        let ifd_0 = match ifd_type {
+            IFDTypes::TIFF => entries,
+            IFDTypes::Exif | IFDTypes::GPS => {
+                let pointer_tag = match ifd_type {
+                    IFDTypes::Exif => Tags::ExifOffset,
+                    _ => Tags::GPSOffset,
+                };
+                let mut ifd_0 = self.get_entries(&IFDTypes::TIFF);
+                ifd_0.retain(|e| e.tag as usize != pointer_tag.0);
+                if !entries.is_empty() {
+                    let offset = append(&entries, 0);
+                    ifd_0.push(IFDEntry::long(pointer_tag, offset as u32, is_little_endian));
+                }
+                ifd_0
+            }
         };
-        let ifd_gps = IFD::from(img_contents[ifd_gps_start..].as_ref(), is_little_endian);
+        let ifd_0_offset = append(&ifd_0, self.ifd_0.get_next_ifd_offset());
 
-        Self {
-            tiff,
-            ifd_0,
-            ifd_exif,
-            ifd_gps,
-            slice: Vec::from(img_contents[exif_chunk_start..].as_ref()),
+        if contents.len() - tiff_start > u32::MAX as usize {
+            return Err(String::from("The file is too big to be edited"));
         }
-    }
 
-    pub fn get_infos_as_string(&self) -> String {
-        format!(
-            "{}\n{}\n{}\n{}\n",
-            self.tiff.get_as_string(),
-            self.ifd_0.get_as_string(),
-            self.ifd_exif.get_as_string(),
-            self.ifd_gps.get_as_string(),
-        )
+        contents[tiff_start + 4..tiff_start + 8].copy_from_slice(&if is_little_endian {
+            (ifd_0_offset as u32).to_le_bytes()
+        } else {
+            (ifd_0_offset as u32).to_be_bytes()
+        });
+        Ok(contents)
     }
+}
 
-    pub fn print_all_tags(&self) {
-        let tags_and_values = [
-            self.ifd_0.get_all_tags(self.slice.as_slice()),
-            self.ifd_exif.get_all_tags(self.slice.as_slice()),
-            self.ifd_gps.get_all_tags(self.slice.as_slice()),
-        ]
-        .concat();
-        if let Some(max_len) = tags_and_values.iter().map(|split| split.0.len()).max() {
-            for s in tags_and_values.iter() {
-                println!("{}{}: {}", s.0, " ".repeat(max_len - s.0.len()), s.1);
+// Returns the offset of the TIFF header inside the file, if the file has Exif data
+fn get_tiff_start(
+    img_contents: &[u8],
+    img_format: ImageFormat,
+) -> Result<(Option<usize>, Vec<String>), String> {
+    match img_format {
+        ImageFormat::Jpeg => get_tiff_start_in_jpeg(img_contents),
+        ImageFormat::Png => {
+            let mut warnings = Vec::new();
+            let mut tiff_start = None;
+            for chunk in png::get_chunks(img_contents) {
+                match chunk {
+                    Ok(chunk) if &chunk.chunk_type == png::EXIF => {
+                        tiff_start = Some(chunk.get_data_offset());
+                        break;
+                    }
+                    Ok(_) => {}
+                    Err(e) => warnings.push(e),
+                }
             }
-        };
+            Ok((tiff_start, warnings))
+        }
+        ImageFormat::Webp => {
+            let chunk = webp::get_chunks(img_contents)
+                .map_while(Result::ok)
+                .find(|chunk| &chunk.fourcc == webp::EXIF);
+            // Some writers keep the identifier code of the JPEG segment
+            Ok((
+                chunk.map(|chunk| {
+                    if chunk
+                        .get_data(img_contents)
+                        .starts_with(&EXIF_IDENTIFIER_CODE)
+                    {
+                        chunk.get_data_offset() + EXIF_IDENTIFIER_CODE.len()
+                    } else {
+                        chunk.get_data_offset()
+                    }
+                }),
+                Vec::new(),
+            ))
+        }
+        ImageFormat::Raf => {
+            let header = match RAFHeader::from(img_contents) {
+                Some(header) => header,
+                None => return Err(String::from("Invalid RAF header")),
+            };
+            get_tiff_start_in_jpeg(header.get_jpeg(img_contents)).map(|(tiff_start, warnings)| {
+                (
+                    tiff_start.map(|tiff_start| header.jpeg_offset + tiff_start),
+                    warnings,
+                )
+            })
+        }
+        _ => Ok((Some(0), Vec::new())),
     }
 }
 
-#[derive(PartialEq)]
+fn get_tiff_start_in_jpeg(jpeg: &[u8]) -> Result<(Option<usize>, Vec<String>), String> {
+    let (segment, warnings) = get_exif_segment(jpeg)?;
+    Ok((
+        segment.and_then(|segment| segment.get_tiff_start(jpeg)),
+        warnings,
+    ))
+}
+
+// Reads the XMP packet of the formats that only have a standard packet
+fn get_xmp_packet(
+    img_contents: &[u8],
+    img_format: ImageFormat,
+    ifd_0: &IFD,
+    slice: &[u8],
+) -> XMPPackets {
+    let mut warnings = Vec::new();
+    let packet = match img_format {
+        ImageFormat::Png => png::get_chunks(img_contents)
+            .map_while(Result::ok)
+            .find(|chunk| is_xmp_chunk(chunk, img_contents))
+            .and_then(|chunk| InternationalText::from(chunk.get_data(img_contents)))
+            .and_then(|text| match text.get_text() {
+                Ok(text) => Some(text.into_bytes()),
+                Err(e) => {
+                    warnings.push(format!("Couldn't read the XMP packet: {}", e));
+                    None
+                }
+            }),
+        ImageFormat::Webp => webp::get_chunk(img_contents, webp::XMP).map(|data| data.to_vec()),
+        _ if img_format.is_tiff_based() => ifd_0
+            .get_interop_for_tag(Tags::XMLPacket.0)
+            .map(|interop| interop.get_raw_value(slice)),
+        _ => None,
+    };
+
+    XMPPackets {
+        // Some writers add a null terminator
+        standard: packet.map(|packet| {
+            String::from_utf8_lossy(&packet)
+                .trim_end_matches('\0')
+                .to_string()
+        }),
+        extended: None,
+        warnings,
+    }
+}
+
+// The fields of structures and the items of arrays are listed with the XMP properties, as in
+// Iptc4xmpCore:CreatorContactInfo/Iptc4xmpCore:CiEmailWork or dc:subject[1]
+fn check_xmp_property_name(name: &str) -> Result<(), String> {
+    match name.contains(['/', '[']) {
+        true => Err(format!(
+            "{} is part of a property, only the top-level XMP properties can be edited",
+            name
+        )),
+        false => Ok(()),
+    }
+}
+
+// Reads the JPEG comments and the PNG text chunks. The XMP packet has its own section and the raw
+// profiles are only summarized, as they are long hex dumps.
+fn get_text_comments(
+    img_contents: &[u8],
+    img_format: ImageFormat,
+    warnings: &mut Vec<String>,
+) -> Vec<(String, String)> {
+    match img_format {
+        ImageFormat::Jpeg => get_comments(img_contents)
+            .into_iter()
+            .map(|comment| (String::from("Comment"), comment))
+            .collect(),
+        ImageFormat::Png => png::get_chunks(img_contents)
+            .map_while(Result::ok)
+            .filter_map(|chunk| png::get_text(&chunk, img_contents))
+            .filter_map(|text| match text {
+                Ok((keyword, _)) if keyword == XMP_KEYWORD => None,
+                Ok((keyword, text)) if is_raw_profile(&keyword) => {
+                    let size = png::decode_raw_profile(&text).map_or(0, |profile| profile.len());
+                    Some((keyword, format!("Raw profile ({} bytes)", size)))
+                }
+                Ok(text) => Some(text),
+                Err(e) => {
+                    warnings.push(format!("Couldn't read a text chunk: {}", e));
+                    None
+                }
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Reassembles the ICC profile of the file, wherever the format stores it
+fn get_icc_profile(
+    img_contents: &[u8],
+    img_format: ImageFormat,
+    ifd_0: &IFD,
+    slice: &[u8],
+    warnings: &mut Vec<String>,
+) -> Option<ICCProfile> {
+    let data = match img_format {
+        ImageFormat::Jpeg => read_jpeg_icc(img_contents),
+        ImageFormat::Png => read_png_icc(img_contents),
+        ImageFormat::Webp => {
+            Ok(webp::get_chunk(img_contents, webp::ICCP).map(|data| data.to_vec()))
+        }
+        _ if img_format.is_tiff_based() => Ok(ifd_0
+            .get_interop_for_tag(Tags::InterColorProfile.0)
+            .map(|interop| interop.get_raw_value(slice))),
+        _ => Ok(None),
+    };
+
+    match data.and_then(|data| data.map(ICCProfile::from).transpose()) {
+        Ok(icc_profile) => icc_profile,
+        Err(e) => {
+            warnings.push(format!("Couldn't read the ICC profile: {}", e));
+            None
+        }
+    }
+}
+
+// Reads the IPTC data from the Photoshop image resources of a JPEG file
+fn get_iptc(img_contents: &[u8], warnings: &mut Vec<String>) -> Option<IPTCData> {
+    let resources = match read_jpeg_resources(img_contents) {
+        Ok(resources) => resources?,
+        Err(e) => {
+            warnings.push(format!(
+                "Couldn't parse the Photoshop image resources: {}",
+                e
+            ));
+            return None;
+        }
+    };
+    let data = &resources
+        .iter()
+        .find(|resource| resource.id == IPTC_RESOURCE_ID)?
+        .data;
+
+    if let Some(digest) = resources
+        .iter()
+        .find(|resource| resource.id == IPTC_DIGEST_RESOURCE_ID)
+    {
+        if digest.data != md5(data) {
+            warnings.push(String::from(
+                "The IPTC digest doesn't match, the IPTC data was modified by another program",
+            ));
+        }
+    }
+
+    match IPTCData::from(data) {
+        Ok(iptc) => Some(iptc),
+        Err(e) => {
+            warnings.push(format!("Couldn't parse the IPTC data: {}", e));
+            None
+        }
+    }
+}
+
+// Parses the IFD pointed to by the given tag, if it exists and is valid
+fn get_sub_ifd(slice: &[u8], ifd: &IFD, tag: Tag, is_little_endian: bool) -> Option<IFD> {
+    let offset = ifd.get_offset_for_tag(tag)?;
+    IFD::from_tiff(slice, offset, is_little_endian).ok()
+}
+
+#[derive(PartialEq, Clone, Copy)]
 pub enum ExifTypes {
     Byte,
     Ascii,
@@ -102,16 +1921,131 @@ pub enum ExifTypes {
     Undefined,
     Slong,
     Srational,
+    // Only defined by the TIFF spec, but used by some raw formats
+    Sbyte,
+    Sshort,
+    Float,
+    Double,
+    Ifd,
     // Not defined by the spec
     Error,
 }
 
+impl ExifTypes {
+    pub fn from_code(code: u16) -> Self {
+        match code {
+            1 => ExifTypes::Byte,
+            2 => ExifTypes::Ascii,
+            3 => ExifTypes::Short,
+            4 => ExifTypes::Long,
+            5 => ExifTypes::Rational,
+            6 => ExifTypes::Sbyte,
+            7 => ExifTypes::Undefined,
+            8 => ExifTypes::Sshort,
+            9 => ExifTypes::Slong,
+            10 => ExifTypes::Srational,
+            11 => ExifTypes::Float,
+            12 => ExifTypes::Double,
+            13 => ExifTypes::Ifd,
+            _ => ExifTypes::Error,
+        }
+    }
+
+    // Size of one value, in bytes
+    pub fn get_size(&self) -> Option<usize> {
+        match self {
+            ExifTypes::Byte | ExifTypes::Ascii | ExifTypes::Undefined | ExifTypes::Sbyte => Some(1),
+            ExifTypes::Short | ExifTypes::Sshort => Some(2),
+            ExifTypes::Long | ExifTypes::Slong | ExifTypes::Float | ExifTypes::Ifd => Some(4),
+            ExifTypes::Rational | ExifTypes::Srational | ExifTypes::Double => Some(8),
+            ExifTypes::Error => None,
+        }
+    }
+
+    // The reverse of Display, ignoring case
+    pub fn from_name(name: &str) -> Option<Self> {
+        (1..=13)
+            .map(ExifTypes::from_code)
+            .find(|data_type| data_type.to_string().eq_ignore_ascii_case(name))
+    }
+
+    // The value of the type field of the interoperability fields
+    pub fn get_code(&self) -> Option<u16> {
+        match self {
+            ExifTypes::Byte => Some(1),
+            ExifTypes::Ascii => Some(2),
+            ExifTypes::Short => Some(3),
+            ExifTypes::Long => Some(4),
+            ExifTypes::Rational => Some(5),
+            ExifTypes::Sbyte => Some(6),
+            ExifTypes::Undefined => Some(7),
+            ExifTypes::Sshort => Some(8),
+            ExifTypes::Slong => Some(9),
+            ExifTypes::Srational => Some(10),
+            ExifTypes::Float => Some(11),
+            ExifTypes::Double => Some(12),
+            ExifTypes::Ifd => Some(13),
+            ExifTypes::Error => None,
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
 pub enum IFDTypes {
     TIFF,
     Exif,
     GPS,
 }
 
+impl IFDTypes {
+    // The IFDs are named as in ExifImage::get_ifds, ignoring case
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "IFD0" => Some(IFDTypes::TIFF),
+            "EXIF" => Some(IFDTypes::Exif),
+            "GPS" => Some(IFDTypes::GPS),
+            _ => None,
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum StripPreset {
+    All,           // The Exif data, the XMP packets, the IPTC datasets and the comments
+    GPS,           // The GPS IFD
+    Privacy,       // The GPS IFD and the tags that identify the camera or its owner
+    KeepCopyright, // Everything but Artist and Copyright
+}
+
+impl StripPreset {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "all" => Some(StripPreset::All),
+            "gps" => Some(StripPreset::GPS),
+            "privacy" => Some(StripPreset::Privacy),
+            "keep-copyright" => Some(StripPreset::KeepCopyright),
+            _ => None,
+        }
+    }
+}
+
+// The tags an operation applies to
+pub enum TagFilter {
+    All,
+    IFDs(Vec<IFDTypes>),
+    Tags(Vec<Tag>),
+}
+
+impl TagFilter {
+    pub fn is_kept(&self, ifd_type: IFDTypes, tag: Tag) -> bool {
+        match self {
+            TagFilter::All => true,
+            TagFilter::IFDs(ifd_types) => ifd_types.contains(&ifd_type),
+            TagFilter::Tags(tags) => tags.contains(&tag),
+        }
+    }
+}
+
 pub struct TIFFHeader {
     // We will need them later, when the editing is implemented
     // byte_order: [u8; 2],
@@ -123,6 +2057,14 @@ pub struct TIFFHeader {
 }
 
 impl TIFFHeader {
+    // Header of a TIFF structure whose 0th IFD comes right after it
+    pub fn new(is_little_endian: bool) -> Self {
+        Self {
+            is_little_endian,
+            zero_th_ifd_offset: TIFF_HEADER_SIZE as u32,
+        }
+    }
+
     pub fn from(slice: &[u8]) -> Self {
         if slice.len() != TIFF_HEADER_SIZE {
             panic!(
@@ -135,15 +2077,15 @@ impl TIFFHeader {
         let byte_order: [u8; 2] = slice[0..2].try_into().unwrap();
         let ifd_offset = slice[4..8].try_into().unwrap();
         let is_little_endian = byte_order == [0x49, 0x49];
-        let off = if is_little_endian {
-            u32::from_le_bytes(ifd_offset)
-        } else {
-            u32::from_be_bytes(ifd_offset)
-        };
 
         Self {
             is_little_endian,
-            zero_th_ifd_offset: if off == 8 { 0 } else { off },
+            // Usually 8, but CR2 files have their own header in between
+            zero_th_ifd_offset: if is_little_endian {
+                u32::from_le_bytes(ifd_offset)
+            } else {
+                u32::from_be_bytes(ifd_offset)
+            },
         }
     }
 
@@ -165,9 +2107,22 @@ pub struct IFD {
     pub interoperability_arrays: Vec<InteroperabilityField>, // Vec of size number_of_fields
     // Not in the spec
     is_little_endian: bool,
+    offset: usize, // From the start of the TIFF header
+    next_ifd_offset: usize,
 } // 4 byte offset to the next IFD
 
 impl IFD {
+    // An IFD without any field
+    pub fn new(is_little_endian: bool) -> Self {
+        Self {
+            number_of_fields: [0, 0],
+            interoperability_arrays: Vec::new(),
+            is_little_endian,
+            offset: TIFF_HEADER_SIZE,
+            next_ifd_offset: 0,
+        }
+    }
+
     pub fn from(slice: &[u8], is_little_endian: bool) -> Self {
         if slice.len() < 2 {
             panic!(
@@ -199,11 +2154,43 @@ impl IFD {
             chunk_start_idx += INTEROPERABILITY_FIELD_SIZE;
         }
 
+        // Some writers omit the offset to the next IFD when there is none
+        let next_ifd_offset = match slice.get(chunk_start_idx..chunk_start_idx + 4) {
+            Some(next) if is_little_endian => u32::from_le_bytes(next.try_into().unwrap()),
+            Some(next) => u32::from_be_bytes(next.try_into().unwrap()),
+            None => 0,
+        } as usize;
+
         Self {
             number_of_fields: slice[0..2].try_into().unwrap(),
             interoperability_arrays: interoperatibility_array,
             is_little_endian,
+            offset: 0,
+            next_ifd_offset,
+        }
+    }
+
+    // Parses the IFD located at `offset` in the TIFF structure, checking that it fits
+    pub fn from_tiff(slice: &[u8], offset: usize, is_little_endian: bool) -> Result<Self, String> {
+        if offset < TIFF_HEADER_SIZE || offset + 2 > slice.len() {
+            return Err(format!("Invalid IFD offset {}", offset));
+        }
+
+        let number_of_fields = if is_little_endian {
+            u16::from_le_bytes(slice[offset..offset + 2].try_into().unwrap())
+        } else {
+            u16::from_be_bytes(slice[offset..offset + 2].try_into().unwrap())
+        };
+        if offset + 2 + INTEROPERABILITY_FIELD_SIZE * number_of_fields as usize > slice.len() {
+            return Err(format!(
+                "The IFD at offset {} goes past the end of the file",
+                offset
+            ));
         }
+
+        let mut ifd = IFD::from(slice[offset..].as_ref(), is_little_endian);
+        ifd.offset = offset;
+        Ok(ifd)
     }
 
     pub fn get_interops(&self) -> &Vec<InteroperabilityField> {
@@ -212,7 +2199,7 @@ impl IFD {
 
     fn get_array_as_string(&self) -> String {
         let mut res = String::from("[\n");
-        let last = self.interoperability_arrays.len() - 1;
+        let last = self.interoperability_arrays.len().saturating_sub(1);
         for (i, interop) in self.interoperability_arrays.iter().enumerate() {
             if i == last {
                 res.push_str(format!("{}", interop).as_str());
@@ -225,6 +2212,10 @@ impl IFD {
         res
     }
 
+    pub fn get_offset(&self) -> usize {
+        self.offset
+    }
+
     pub fn get_offset_to_next_ifd(&self) -> usize {
         2 + if self.is_little_endian {
             u16::from_le_bytes(self.number_of_fields) as usize
@@ -234,6 +2225,10 @@ impl IFD {
             + 4
     }
 
+    pub fn get_next_ifd_offset(&self) -> usize {
+        self.next_ifd_offset
+    }
+
     pub fn get_offset_for_tag(&self, tag: Tag) -> Option<usize> {
         self.interoperability_arrays
             .iter()
@@ -281,6 +2276,58 @@ impl IFD {
             })
             .collect()
     }
+
+    pub fn get_entries(&self, slice: &[u8]) -> Vec<IFDEntry> {
+        self.interoperability_arrays
+            .iter()
+            .map(|interop| interop.get_entry(slice))
+            .collect()
+    }
+
+    // The thumbnail of the 1st IFD, or the preview of the IFDs of raw files
+    pub fn get_jpeg_thumbnail<'a>(&self, slice: &'a [u8]) -> Option<&'a [u8]> {
+        let start = self.get_first_uint_for_tag(Tags::JPEGInterchangeFormat, slice)? as usize;
+        let length =
+            self.get_first_uint_for_tag(Tags::JPEGInterchangeFormatLength, slice)? as usize;
+        get_jpeg_at(slice, start, length)
+    }
+
+    // CR2 and ARW files store their previews as a single strip of old-style JPEG compressed data
+    pub fn get_jpeg_preview<'a>(&self, slice: &'a [u8]) -> Option<&'a [u8]> {
+        if let Some(thumbnail) = self.get_jpeg_thumbnail(slice) {
+            return Some(thumbnail);
+        }
+
+        if self.get_first_uint_for_tag(Tags::Compression, slice)? != 6 {
+            return None;
+        }
+        let offsets = self
+            .get_interop_for_tag(Tags::StripOffsets.0)?
+            .get_uints(slice);
+        let lengths = self
+            .get_interop_for_tag(Tags::StripByteCounts.0)?
+            .get_uints(slice);
+        if offsets.len() != 1 || lengths.len() != 1 {
+            return None;
+        }
+        get_jpeg_at(slice, offsets[0] as usize, lengths[0] as usize)
+    }
+
+    fn get_first_uint_for_tag(&self, tag: Tag, slice: &[u8]) -> Option<u32> {
+        self.get_interop_for_tag(tag.0)?
+            .get_uints(slice)
+            .first()
+            .copied()
+    }
+}
+
+fn get_jpeg_at(slice: &[u8], start: usize, length: usize) -> Option<&[u8]> {
+    let jpeg = slice.get(start..start.checked_add(length)?)?;
+    if jpeg.starts_with(&JPEG_SOI) {
+        Some(jpeg)
+    } else {
+        None
+    }
 }
 
 pub struct InteroperabilityField {
@@ -323,21 +2370,11 @@ impl InteroperabilityField {
             } else {
                 u16::from_be_bytes(tag)
             } as usize,
-            cdata_type: match if is_little_endian {
+            cdata_type: ExifTypes::from_code(if is_little_endian {
                 u16::from_le_bytes(data_type)
             } else {
                 u16::from_be_bytes(data_type)
-            } {
-                1 => ExifTypes::Byte,
-                2 => ExifTypes::Ascii,
-                3 => ExifTypes::Short,
-                4 => ExifTypes::Long,
-                5 => ExifTypes::Rational,
-                7 => ExifTypes::Undefined,
-                9 => ExifTypes::Slong,
-                10 => ExifTypes::Srational,
-                _ => ExifTypes::Error,
-            },
+            }),
             ccount: if is_little_endian {
                 u32::from_le_bytes(count)
             } else {
@@ -351,6 +2388,18 @@ impl InteroperabilityField {
         }
     }
 
+    pub fn get_tag(&self) -> Tag {
+        Tag(self.ctag)
+    }
+
+    pub fn get_data_type(&self) -> &ExifTypes {
+        &self.cdata_type
+    }
+
+    pub fn get_count(&self) -> usize {
+        self.ccount
+    }
+
     pub fn get_value_byte(&self, slice: &[u8]) -> Option<Vec<u8>> {
         if self.cdata_type == ExifTypes::Byte {
             return Some(self.get_bytes(slice));
@@ -401,26 +2450,34 @@ impl InteroperabilityField {
     }
 
     pub fn get_value_srational(&self, slice: &[u8]) -> Option<Vec<(i32, i32)>> {
-        if self.cdata_type == ExifTypes::Byte {
+        if self.cdata_type == ExifTypes::Srational {
             return Some(self.get_srational(slice));
         }
         None
     }
 
-    fn get_type_as_string(&self) -> String {
+    // Values of integer types, which are used for offsets and sizes
+    pub fn get_uints(&self, slice: &[u8]) -> Vec<u32> {
         match self.cdata_type {
-            ExifTypes::Byte => String::from("Byte"),
-            ExifTypes::Ascii => String::from("Ascii"),
-            ExifTypes::Short => String::from("Short"),
-            ExifTypes::Long => String::from("Long"),
-            ExifTypes::Rational => String::from("Rational"),
-            ExifTypes::Undefined => String::from("Undefined"),
-            ExifTypes::Slong => String::from("Slong"),
-            ExifTypes::Srational => String::from("Srational"),
-            ExifTypes::Error => String::from("Error"),
+            ExifTypes::Byte => self
+                .get_bytes(slice)
+                .into_iter()
+                .map(|v| v as u32)
+                .collect(),
+            ExifTypes::Short => self
+                .get_shorts(slice)
+                .into_iter()
+                .map(|v| v as u32)
+                .collect(),
+            ExifTypes::Long | ExifTypes::Ifd => self.get_longs(slice),
+            _ => Vec::new(),
         }
     }
 
+    fn get_type_as_string(&self) -> String {
+        self.cdata_type.to_string()
+    }
+
     pub fn get_value_as_string(&self, slice: &[u8]) -> String {
         let tag = Tag(self.ctag);
 
@@ -439,95 +2496,109 @@ impl InteroperabilityField {
             }
             ExifTypes::Slong => get_vec_as_string(self.get_slongs(slice)),
             ExifTypes::Srational => get_tuples_vec_as_string(self.get_srational(slice)),
+            ExifTypes::Sbyte => get_vec_as_string(
+                self.get_raw_value(slice)
+                    .into_iter()
+                    .map(|b| b as i8)
+                    .collect(),
+            ),
+            ExifTypes::Sshort => get_vec_as_string(
+                self.get_shorts(slice)
+                    .into_iter()
+                    .map(|v| v as i16)
+                    .collect(),
+            ),
+            ExifTypes::Float => get_vec_as_string(
+                self.get_longs(slice)
+                    .into_iter()
+                    .map(f32::from_bits)
+                    .collect(),
+            ),
+            ExifTypes::Double => get_vec_as_string(self.get_doubles(slice)),
+            ExifTypes::Ifd => get_vec_as_string(self.get_longs(slice)),
             ExifTypes::Error => String::from("N/A"),
         }
     }
 
-    fn get_bytes(&self, slice: &[u8]) -> Vec<u8> {
-        if self.ccount == 0 {
-            return Vec::with_capacity(0);
+    // Size of the value, in bytes
+    pub fn get_value_size(&self) -> Option<usize> {
+        self.cdata_type.get_size()?.checked_mul(self.ccount)
+    }
+
+    // The bytes of the value, in the byte order of the file. If the value doesn't fit in the
+    // value_offset field, it is read from the TIFF structure (`slice`)
+    pub fn get_raw_value(&self, slice: &[u8]) -> Vec<u8> {
+        let size = match self.get_value_size() {
+            Some(size) => size,
+            None => return self.value_offset.to_vec(),
+        };
+
+        if size <= 4 {
+            return self.value_offset[0..size].to_vec();
         }
 
-        if self.ccount <= 4 {
-            return Vec::from_iter(self.value_offset[0..self.ccount].iter().copied());
+        match self
+            .cvalue_offset
+            .checked_add(size)
+            .and_then(|end| slice.get(self.cvalue_offset..end))
+        {
+            Some(value) => value.to_vec(),
+            None => Vec::new(),
         }
+    }
 
-        if self.cvalue_offset + self.ccount <= slice.len() {
-            return slice[self.cvalue_offset..self.cvalue_offset + self.ccount].to_vec();
+    pub fn get_entry(&self, slice: &[u8]) -> IFDEntry {
+        IFDEntry {
+            tag: self.ctag as u16,
+            data_type: if self.is_little_endian {
+                u16::from_le_bytes(self.data_type)
+            } else {
+                u16::from_be_bytes(self.data_type)
+            },
+            count: self.ccount as u32,
+            data: self.get_raw_value(slice),
         }
-        Vec::new()
     }
 
-    fn get_ascii(&self, slice: &[u8]) -> String {
-        let start = self.cvalue_offset;
-        let end = start + self.ccount;
+    fn get_bytes(&self, slice: &[u8]) -> Vec<u8> {
+        self.get_raw_value(slice)
+    }
 
-        if start >= slice.len() || end >= slice.len() {
+    fn get_ascii(&self, slice: &[u8]) -> String {
+        let value = self.get_raw_value(slice);
+        if value.is_empty() && self.ccount != 0 {
             return String::from("ERROR");
         }
 
-        String::from_iter(slice[start..end].iter().map(|b| *b as char))
+        // Strings are NULL terminated
+        String::from_iter(value.iter().take_while(|b| **b != 0).map(|b| *b as char))
     }
 
     fn get_shorts(&self, slice: &[u8]) -> Vec<u16> {
-        match self.ccount {
-            0 => Vec::with_capacity(0),
-            1 => vec![u16::from_le_bytes(
-                self.value_offset[0..2].try_into().unwrap(),
-            )],
-            2 => vec![
-                u16::from_le_bytes(self.value_offset[0..2].try_into().unwrap()),
-                u16::from_le_bytes(self.value_offset[2..4].try_into().unwrap()),
-            ],
-            _ => {
-                let end_off = self.cvalue_offset + self.ccount * 2;
-                if end_off >= slice.len() {
-                    return Vec::new();
-                }
-
-                Vec::from_iter(
-                    slice[self.cvalue_offset..end_off]
-                        .rchunks_exact(2)
-                        .map(|chunk| u16::from_le_bytes(chunk[0..2].try_into().unwrap())),
-                )
+        Vec::from_iter(self.get_raw_value(slice).chunks_exact(2).map(|chunk| {
+            if self.is_little_endian {
+                u16::from_le_bytes(chunk.try_into().unwrap())
+            } else {
+                u16::from_be_bytes(chunk.try_into().unwrap())
             }
-        }
+        }))
     }
 
     fn get_longs(&self, slice: &[u8]) -> Vec<u32> {
-        match self.ccount {
-            0 => Vec::with_capacity(0),
-            1 => vec![u32::from_le_bytes(self.value_offset)],
-            _ => {
-                let end_off = self.cvalue_offset + self.ccount * 4;
-                if end_off >= slice.len() {
-                    return Vec::new();
-                }
-
-                Vec::from_iter(
-                    slice[self.cvalue_offset..end_off]
-                        .rchunks_exact(4)
-                        .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap())),
-                )
+        Vec::from_iter(self.get_raw_value(slice).chunks_exact(4).map(|chunk| {
+            if self.is_little_endian {
+                u32::from_le_bytes(chunk.try_into().unwrap())
+            } else {
+                u32::from_be_bytes(chunk.try_into().unwrap())
             }
-        }
+        }))
     }
 
     fn get_rationals(&self, slice: &[u8]) -> Vec<(u32, u32)> {
-        let end_off = self.cvalue_offset + self.ccount * 8;
-        if end_off >= slice.len() {
-            return Vec::new();
-        }
-
         Vec::from_iter(
-            slice[self.cvalue_offset..end_off]
-                .rchunks_exact(8)
-                .map(|chunk| {
-                    (
-                        u32::from_le_bytes(chunk[0..4].try_into().unwrap()),
-                        u32::from_le_bytes(chunk[4..8].try_into().unwrap()),
-                    )
-                }),
+            self.get_longs(slice)
+                .chunks_exact(2)
+                .map(|chunk| (chunk[0], chunk[1])),
         )
     }
 
@@ -536,39 +2607,49 @@ impl InteroperabilityField {
     }
 
     fn get_slongs(&self, slice: &[u8]) -> Vec<i32> {
-        match self.ccount {
-            0 => Vec::with_capacity(0),
-            1 => vec![i32::from_le_bytes(self.value_offset)],
-            _ => {
-                let end_off = self.cvalue_offset + self.ccount * 4;
-                if end_off >= slice.len() {
-                    return Vec::new();
-                }
-
-                Vec::from_iter(
-                    slice[self.cvalue_offset..end_off]
-                        .rchunks_exact(4)
-                        .map(|chunk| i32::from_le_bytes(chunk.try_into().unwrap())),
-                )
-            }
-        }
+        Vec::from_iter(self.get_longs(slice).into_iter().map(|v| v as i32))
     }
 
     fn get_srational(&self, slice: &[u8]) -> Vec<(i32, i32)> {
-        let end_off = self.cvalue_offset + self.ccount * 8;
-        if end_off >= slice.len() {
-            return Vec::new();
-        }
-
         Vec::from_iter(
-            slice[self.cvalue_offset..end_off]
-                .rchunks_exact(8)
-                .map(|chunk| {
-                    (
-                        i32::from_le_bytes(chunk[0..4].try_into().unwrap()),
-                        i32::from_le_bytes(chunk[4..8].try_into().unwrap()),
-                    )
-                }),
+            self.get_slongs(slice)
+                .chunks_exact(2)
+                .map(|chunk| (chunk[0], chunk[1])),
+        )
+    }
+
+    fn get_doubles(&self, slice: &[u8]) -> Vec<f64> {
+        Vec::from_iter(self.get_raw_value(slice).chunks_exact(8).map(|chunk| {
+            if self.is_little_endian {
+                f64::from_le_bytes(chunk.try_into().unwrap())
+            } else {
+                f64::from_be_bytes(chunk.try_into().unwrap())
+            }
+        }))
+    }
+}
+
+impl fmt::Display for ExifTypes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ExifTypes::Byte => "Byte",
+                ExifTypes::Ascii => "Ascii",
+                ExifTypes::Short => "Short",
+                ExifTypes::Long => "Long",
+                ExifTypes::Rational => "Rational",
+                ExifTypes::Undefined => "Undefined",
+                ExifTypes::Slong => "Slong",
+                ExifTypes::Srational => "Srational",
+                ExifTypes::Sbyte => "Sbyte",
+                ExifTypes::Sshort => "Sshort",
+                ExifTypes::Float => "Float",
+                ExifTypes::Double => "Double",
+                ExifTypes::Ifd => "IFD",
+                ExifTypes::Error => "Error",
+            }
         )
     }
 }
@@ -618,3 +2699,86 @@ impl fmt::Display for InteroperabilityField {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jpeg::{EOI, SOI};
+
+    fn build_jpeg(segments: &[Vec<u8>]) -> ExifImage {
+        let contents = [vec![0xFF, SOI], segments.concat(), vec![0xFF, EOI]].concat();
+        ExifImage::from(contents, ImageFormat::Jpeg).unwrap()
+    }
+
+    // The image as it would be read back from the file
+    fn reload(img: &ExifImage) -> ExifImage {
+        ExifImage::from(img.get_contents().to_vec(), img.get_image_format()).unwrap()
+    }
+
+    #[test]
+    fn xmp_rating_round_trip() {
+        let mut img = build_jpeg(&[]);
+        img.set_xmp_property("xmp:Rating", "4").unwrap();
+        img.set_xmp_list("dc:subject", &[String::from("a"), String::from("b")])
+            .unwrap();
+        assert!(img.set_xmp_property("xmp:Rating", "6").is_err());
+
+        let mut img = reload(&img);
+        let properties = img.get_xmp_meta().unwrap().get_all_properties();
+        assert!(properties.contains(&(String::from("xmp:Rating"), String::from("4"))));
+        assert!(properties.contains(&(String::from("dc:subject"), String::from("a, b"))));
+
+        img.remove_xmp_property("xmp:Rating").unwrap();
+        let img = reload(&img);
+        let properties = img.get_xmp_meta().unwrap().get_all_properties();
+        assert!(!properties.iter().any(|(name, _)| name == "xmp:Rating"));
+    }
+
+    #[test]
+    fn iptc_keeps_other_resources() {
+        let resolution = PhotoshopResource {
+            name: b"Resolution".to_vec(),
+            ..PhotoshopResource::new(
+                0x03ED,
+                vec![0, 72, 0, 0, 0, 1, 0, 1, 0, 72, 0, 0, 0, 1, 0, 1],
+            )
+        };
+        let digest = PhotoshopResource::new(IPTC_DIGEST_RESOURCE_ID, vec![0; 16]);
+        let img = build_jpeg(&[]);
+        let contents =
+            write_jpeg_resources(img.get_contents(), &[resolution.clone(), digest]).unwrap();
+        let mut img = ExifImage::from(contents, ImageFormat::Jpeg).unwrap();
+
+        img.set_iptc("By-line", &[String::from("Jane Doe")])
+            .unwrap();
+        let mut img = reload(&img);
+        assert_eq!(img.get_iptc_values("By-line").unwrap(), ["Jane Doe"]);
+        let resources = read_jpeg_resources(img.get_contents()).unwrap().unwrap();
+        let get_resource = |resources: &[PhotoshopResource], id| {
+            resources
+                .iter()
+                .find(|resource| resource.id == id)
+                .map(|resource| (resource.name.clone(), resource.data.clone()))
+        };
+        assert_eq!(
+            get_resource(&resources, 0x03ED),
+            Some((resolution.name.clone(), resolution.data.clone()))
+        );
+        let iptc = get_resource(&resources, IPTC_RESOURCE_ID).unwrap().1;
+        assert_eq!(
+            get_resource(&resources, IPTC_DIGEST_RESOURCE_ID).unwrap().1,
+            md5(&iptc)
+        );
+
+        img.remove_iptc("By-line").unwrap();
+        let img = reload(&img);
+        let resources = read_jpeg_resources(img.get_contents()).unwrap().unwrap();
+        assert_eq!(
+            resources
+                .iter()
+                .map(|resource| resource.id)
+                .collect::<Vec<u16>>(),
+            [0x03ED]
+        );
+    }
+}
@@ -0,0 +1,647 @@
+use crate::exif::{ExifImage, ExifTypes};
+use crate::formats::ImageFormat;
+use crate::tags::{Tag, Tags};
+use crate::writer::IFDEntry;
+
+// Settings stored as numbers, with the names exiftool prints for them
+const NUMBER_NAMES: &[(Tag, &[(u32, &str)])] = &[
+    (
+        Tags::Orientation,
+        &[
+            (1, "Horizontal (normal)"),
+            (2, "Mirror horizontal"),
+            (3, "Rotate 180"),
+            (4, "Mirror vertical"),
+            (5, "Mirror horizontal and rotate 270 CW"),
+            (6, "Rotate 90 CW"),
+            (7, "Mirror horizontal and rotate 90 CW"),
+            (8, "Rotate 270 CW"),
+        ],
+    ),
+    (
+        Tags::NewSubfileType,
+        &[
+            (0, "Full-resolution image"),
+            (1, "Reduced-resolution image"),
+            (2, "Single page of multi-page image"),
+            (3, "Single page of multi-page reduced-resolution image"),
+            (4, "Transparency mask"),
+        ],
+    ),
+    (
+        Tags::ResolutionUnit,
+        &[(1, "None"), (2, "inches"), (3, "cm")],
+    ),
+    (
+        Tags::FocalPlaneResolutionUnit,
+        &[(1, "None"), (2, "inches"), (3, "cm"), (4, "mm"), (5, "um")],
+    ),
+    (Tags::YCbCrPositioning, &[(1, "Centered"), (2, "Co-sited")]),
+    (
+        Tags::Compression,
+        &[
+            (1, "Uncompressed"),
+            (5, "LZW"),
+            (6, "JPEG (old-style)"),
+            (7, "JPEG"),
+            (8, "Adobe Deflate"),
+            (32773, "PackBits"),
+        ],
+    ),
+    (
+        Tags::PhotometricInterpretation,
+        &[
+            (0, "WhiteIsZero"),
+            (1, "BlackIsZero"),
+            (2, "RGB"),
+            (3, "RGB Palette"),
+            (5, "CMYK"),
+            (6, "YCbCr"),
+        ],
+    ),
+    (Tags::PlanarConfiguration, &[(1, "Chunky"), (2, "Planar")]),
+    (
+        Tags::ExposureProgram,
+        &[
+            (0, "Not Defined"),
+            (1, "Manual"),
+            (2, "Program AE"),
+            (3, "Aperture-priority AE"),
+            (4, "Shutter speed priority AE"),
+            (5, "Creative (Slow speed)"),
+            (6, "Action (High speed)"),
+            (7, "Portrait"),
+            (8, "Landscape"),
+            (9, "Bulb"),
+        ],
+    ),
+    (
+        Tags::SensitivityType,
+        &[
+            (0, "Unknown"),
+            (1, "Standard Output Sensitivity"),
+            (2, "Recommended Exposure Index"),
+            (3, "ISO Speed"),
+            (
+                4,
+                "Standard Output Sensitivity and Recommended Exposure Index",
+            ),
+            (5, "Standard Output Sensitivity and ISO Speed"),
+            (6, "Recommended Exposure Index and ISO Speed"),
+            (
+                7,
+                "Standard Output Sensitivity, Recommended Exposure Index and ISO Speed",
+            ),
+        ],
+    ),
+    (
+        Tags::MeteringMode,
+        &[
+            (0, "Unknown"),
+            (1, "Average"),
+            (2, "Center-weighted average"),
+            (3, "Spot"),
+            (4, "Multi-spot"),
+            (5, "Multi-segment"),
+            (6, "Partial"),
+            (255, "Other"),
+        ],
+    ),
+    (
+        Tags::LightSource,
+        &[
+            (0, "Unknown"),
+            (1, "Daylight"),
+            (2, "Fluorescent"),
+            (3, "Tungsten (Incandescent)"),
+            (4, "Flash"),
+            (9, "Fine Weather"),
+            (10, "Cloudy"),
+            (11, "Shade"),
+            (12, "Daylight Fluorescent"),
+            (13, "Day White Fluorescent"),
+            (14, "Cool White Fluorescent"),
+            (15, "White Fluorescent"),
+            (16, "Warm White Fluorescent"),
+            (17, "Standard Light A"),
+            (18, "Standard Light B"),
+            (19, "Standard Light C"),
+            (20, "D55"),
+            (21, "D65"),
+            (22, "D75"),
+            (23, "D50"),
+            (24, "ISO Studio Tungsten"),
+            (255, "Other"),
+        ],
+    ),
+    (
+        Tags::Flash,
+        &[
+            (0x00, "No Flash"),
+            (0x01, "Fired"),
+            (0x05, "Fired, Return not detected"),
+            (0x07, "Fired, Return detected"),
+            (0x08, "On, Did not fire"),
+            (0x09, "On, Fired"),
+            (0x0d, "On, Return not detected"),
+            (0x0f, "On, Return detected"),
+            (0x10, "Off, Did not fire"),
+            (0x14, "Off, Did not fire, Return not detected"),
+            (0x18, "Auto, Did not fire"),
+            (0x19, "Auto, Fired"),
+            (0x1d, "Auto, Fired, Return not detected"),
+            (0x1f, "Auto, Fired, Return detected"),
+            (0x20, "No flash function"),
+            (0x30, "Off, No flash function"),
+            (0x41, "Fired, Red-eye reduction"),
+            (0x45, "Fired, Red-eye reduction, Return not detected"),
+            (0x47, "Fired, Red-eye reduction, Return detected"),
+            (0x49, "On, Red-eye reduction"),
+            (0x4d, "On, Red-eye reduction, Return not detected"),
+            (0x4f, "On, Red-eye reduction, Return detected"),
+            (0x50, "Off, Red-eye reduction"),
+            (0x58, "Auto, Did not fire, Red-eye reduction"),
+            (0x59, "Auto, Fired, Red-eye reduction"),
+            (0x5d, "Auto, Fired, Red-eye reduction, Return not detected"),
+            (0x5f, "Auto, Fired, Red-eye reduction, Return detected"),
+        ],
+    ),
+    (
+        Tags::ColorSpace,
+        &[
+            (1, "sRGB"),
+            (2, "Adobe RGB"),
+            (0xfffd, "Wide Gamut RGB"),
+            (0xfffe, "ICC Profile"),
+            (0xffff, "Uncalibrated"),
+        ],
+    ),
+    (
+        Tags::SensingMethod,
+        &[
+            (1, "Not defined"),
+            (2, "One-chip color area"),
+            (3, "Two-chip color area"),
+            (4, "Three-chip color area"),
+            (5, "Color sequential area"),
+            (7, "Trilinear"),
+            (8, "Color sequential linear"),
+        ],
+    ),
+    (
+        Tags::FileSource,
+        &[
+            (1, "Film Scanner"),
+            (2, "Reflection Print Scanner"),
+            (3, "Digital Camera"),
+        ],
+    ),
+    (Tags::SceneType, &[(1, "Directly photographed")]),
+    (Tags::CustomRendered, &[(0, "Normal"), (1, "Custom")]),
+    (
+        Tags::ExposureMode,
+        &[(0, "Auto"), (1, "Manual"), (2, "Auto bracket")],
+    ),
+    (Tags::WhiteBalance, &[(0, "Auto"), (1, "Manual")]),
+    (
+        Tags::SceneCaptureType,
+        &[
+            (0, "Standard"),
+            (1, "Landscape"),
+            (2, "Portrait"),
+            (3, "Night"),
+            (4, "Other"),
+        ],
+    ),
+    (
+        Tags::GainControl,
+        &[
+            (0, "None"),
+            (1, "Low gain up"),
+            (2, "High gain up"),
+            (3, "Low gain down"),
+            (4, "High gain down"),
+        ],
+    ),
+    (Tags::Contrast, &[(0, "Normal"), (1, "Low"), (2, "High")]),
+    (Tags::Saturation, &[(0, "Normal"), (1, "Low"), (2, "High")]),
+    (Tags::Sharpness, &[(0, "Normal"), (1, "Soft"), (2, "Hard")]),
+    (
+        Tags::SubjectDistanceRange,
+        &[(0, "Unknown"), (1, "Macro"), (2, "Close"), (3, "Distant")],
+    ),
+    (
+        Tags::GPSAltitudeRef,
+        &[(0, "Above Sea Level"), (1, "Below Sea Level")],
+    ),
+    (
+        Tags::GPSDifferential,
+        &[(0, "No Correction"), (1, "Differential Corrected")],
+    ),
+];
+
+// Settings stored as letters (the references of the GPS values)
+const LETTER_NAMES: &[(Tag, &[(&str, &str)])] = &[
+    (Tags::GPSLatitudeRef, &[("N", "North"), ("S", "South")]),
+    (Tags::GPSLongitudeRef, &[("E", "East"), ("W", "West")]),
+    (Tags::GPSDestLatitudeRef, &[("N", "North"), ("S", "South")]),
+    (Tags::GPSDestLongitudeRef, &[("E", "East"), ("W", "West")]),
+    (
+        Tags::GPSStatus,
+        &[("A", "Measurement Active"), ("V", "Measurement Void")],
+    ),
+    (
+        Tags::GPSMeasureMode,
+        &[
+            ("2", "2-Dimensional Measurement"),
+            ("3", "3-Dimensional Measurement"),
+        ],
+    ),
+    (
+        Tags::GPSSpeedRef,
+        &[("K", "km/h"), ("M", "mph"), ("N", "knots")],
+    ),
+    (
+        Tags::GPSTrackRef,
+        &[("M", "Magnetic North"), ("T", "True North")],
+    ),
+    (
+        Tags::GPSImgDirectionRef,
+        &[("M", "Magnetic North"), ("T", "True North")],
+    ),
+    (
+        Tags::GPSDestBearingRef,
+        &[("M", "Magnetic North"), ("T", "True North")],
+    ),
+    (
+        Tags::GPSDestDistanceRef,
+        &[("K", "Kilometers"), ("M", "Miles"), ("N", "Nautical Miles")],
+    ),
+];
+
+// Tags printed with a unit after their value
+const UNITS: &[(Tag, &str)] = &[
+    (Tags::FocalLength, " mm"),
+    (Tags::FocalLengthIn35mmFilm, " mm"),
+    (Tags::SubjectDistance, " m"),
+    (Tags::GPSAltitude, " m"),
+    (Tags::GPSHPositioningError, " m"),
+];
+
+const COORDINATE_TAGS: [Tag; 4] = [
+    Tags::GPSLatitude,
+    Tags::GPSLongitude,
+    Tags::GPSDestLatitude,
+    Tags::GPSDestLongitude,
+];
+
+// The tags that point to other IFDs, which exiftool doesn't print
+const POINTER_TAGS: [Tag; 4] = [
+    Tags::ExifOffset,
+    Tags::GPSOffset,
+    Tags::InteroperabilityIFD,
+    Tags::SubIFDs,
+];
+
+// exiftool groups of the other sections
+const SECTION_GROUPS: &[(&str, &str)] = &[
+    ("JFIF", "JFIF"),
+    ("MPF", "MPF"),
+    ("IPTC", "IPTC"),
+    ("ICC", "ICC_Profile"),
+    ("XMP", "XMP"),
+    ("Comments", "File"),
+];
+
+// Rationals are converted to decimals rounded to 10 significant digits, as exiftool does. A zero
+// denominator gives `inf`, or `undef` for 0/0.
+fn parse_number(value: &str) -> Option<f64> {
+    let number = match value.split_once('/') {
+        Some((num, den)) => {
+            let (num, den) = (num.parse::<f64>().ok()?, den.parse::<f64>().ok()?);
+            match (num, den) {
+                (num, den) if den != 0.0 => round(num / den, 10),
+                (num, _) if num != 0.0 => f64::INFINITY,
+                _ => f64::NAN,
+            }
+        }
+        None => value.parse().ok()?,
+    };
+    Some(number)
+}
+
+fn round(value: f64, digits: i32) -> f64 {
+    if value == 0.0 || !value.is_finite() {
+        return value;
+    }
+    let scale = 10_f64.powi(digits - value.abs().log10().ceil() as i32);
+    (value * scale).round() / scale
+}
+
+fn format_number(value: f64) -> String {
+    match value {
+        value if value.is_nan() => String::from("undef"),
+        value if value.is_infinite() => String::from("inf"),
+        value => value.to_string(),
+    }
+}
+
+// Like `%.Nf`, with a trailing `.0` removed
+fn format_decimals(value: f64, decimals: usize) -> String {
+    let text = format!("{:.*}", decimals, value);
+    match text.strip_suffix(".0") {
+        Some(text) => text.to_string(),
+        None => text,
+    }
+}
+
+fn get_exposure_time(seconds: f64) -> String {
+    match seconds {
+        seconds if seconds > 0.0 && seconds < 0.25001 => {
+            format!("1/{}", (0.5 + 1.0 / seconds) as u64)
+        }
+        seconds => format_decimals(seconds, 1),
+    }
+}
+
+fn get_fnumber(value: f64) -> String {
+    match value {
+        value if value < 1.0 => format!("{:.2}", value),
+        value => format!("{:.1}", value),
+    }
+}
+
+// Exposure compensations are printed as fractions of a stop, such as `+1/3` or `-2`
+fn get_fraction(value: f64) -> String {
+    let value = value * 1.00001;
+    if value == 0.0 {
+        return String::from("0");
+    }
+    for den in 1..=3 {
+        let num = (value * den as f64).trunc();
+        if num / (value * den as f64) > 0.999 {
+            return match den {
+                1 => format!("{:+}", num),
+                den => format!("{:+}/{}", num, den),
+            };
+        }
+    }
+    format!("{:+}", round(value, 3))
+}
+
+fn get_dms(degrees: f64, minutes: f64, seconds: f64) -> String {
+    let value = degrees + minutes / 60.0 + seconds / 3600.0;
+    let mut degrees = value.trunc();
+    let mut minutes = ((value - degrees) * 60.0).trunc();
+    let mut seconds = ((value - degrees) * 60.0 - minutes) * 60.0;
+    // The seconds are rounded to 2 decimals, which can carry over
+    if seconds >= 59.995 {
+        seconds = 0.0;
+        minutes += 1.0;
+    }
+    if minutes >= 60.0 {
+        minutes -= 60.0;
+        degrees += 1.0;
+    }
+    format!("{} deg {}' {:.2}\"", degrees, minutes, seconds)
+}
+
+fn get_time(hours: f64, minutes: f64, seconds: f64) -> String {
+    let total = (hours * 60.0 + minutes) * 60.0 + seconds;
+    let hours = (total / 3600.0).trunc();
+    let minutes = ((total - hours * 3600.0) / 60.0).trunc();
+    let seconds = total - hours * 3600.0 - minutes * 60.0;
+    let fraction = format!("{:.9}", seconds.fract());
+    let fraction = fraction.trim_end_matches('0').trim_end_matches('.');
+    format!(
+        "{:02}:{:02}:{:02}{}",
+        hours,
+        minutes,
+        seconds.trunc(),
+        fraction.strip_prefix('0').unwrap_or(fraction)
+    )
+}
+
+// Focal lengths and apertures, as `24-105mm f/4`
+fn get_lens_info(values: &[f64]) -> Option<String> {
+    let [min_focal, max_focal, min_fnumber, max_fnumber] = values else {
+        return None;
+    };
+    let mut info = match min_focal == max_focal {
+        true => format!("{}mm", format_number(*min_focal)),
+        false => format!(
+            "{}-{}mm",
+            format_number(*min_focal),
+            format_number(*max_focal)
+        ),
+    };
+    match (min_fnumber.is_finite(), min_fnumber == max_fnumber) {
+        (false, _) => info.push_str(" f/?"),
+        (true, true) => info.push_str(&format!(" f/{}", format_number(*min_fnumber))),
+        (true, false) => info.push_str(&format!(
+            " f/{}-{}",
+            format_number(*min_fnumber),
+            format_number(*max_fnumber)
+        )),
+    }
+    Some(info)
+}
+
+// The value of the field, converted to the text exiftool prints for it
+pub fn get_print_value(entry: &IFDEntry, is_little_endian: bool) -> String {
+    let tag = Tag(entry.tag as usize);
+    let text = entry.get_value_as_text(is_little_endian);
+    match ExifTypes::from_code(entry.data_type) {
+        ExifTypes::Ascii => {
+            return match LETTER_NAMES.iter().find(|(other, _)| *other == tag) {
+                Some((_, names)) => match names.iter().find(|(letter, _)| *letter == text) {
+                    Some((_, name)) => name.to_string(),
+                    None => format!("Unknown ({})", text),
+                },
+                None => text,
+            };
+        }
+        ExifTypes::Undefined => match tag {
+            Tags::ExifVersion
+            | Tags::FlashpixVersion
+            | Tags::UserComment
+            | Tags::GPSProcessingMethod
+            | Tags::GPSAreaInformation => {
+                // Text with a character code, which only UserComment has dropped
+                let text = match tag {
+                    Tags::GPSProcessingMethod | Tags::GPSAreaInformation => text
+                        .get(8..)
+                        .filter(|_| text.starts_with("ASCII"))
+                        .unwrap_or(&text),
+                    _ => &text,
+                };
+                return text.trim_end_matches(['\0', ' ']).to_string();
+            }
+            Tags::ComponentsConfiguration => {
+                let names = ["-", "Y", "Cb", "Cr", "R", "G", "B"];
+                return entry
+                    .data
+                    .iter()
+                    .map(|component| names.get(*component as usize).unwrap_or(&"Err"))
+                    .copied()
+                    .collect::<Vec<&str>>()
+                    .join(", ");
+            }
+            Tags::FileSource | Tags::SceneType if entry.data.len() == 1 => (),
+            _ => {
+                return format!(
+                    "(Binary data {} bytes, use -b option to extract)",
+                    entry.data.len()
+                )
+            }
+        },
+        _ => (),
+    }
+
+    let numbers: Vec<f64> = match ExifTypes::from_code(entry.data_type) {
+        ExifTypes::Undefined => entry.data.iter().map(|byte| *byte as f64).collect(),
+        _ => text.split(' ').filter_map(parse_number).collect(),
+    };
+    if let Some((_, names)) = NUMBER_NAMES.iter().find(|(other, _)| *other == tag) {
+        if let [number] = numbers.as_slice() {
+            return match names.iter().find(|(value, _)| *value as f64 == *number) {
+                Some((_, name)) => name.to_string(),
+                None => format!("Unknown ({})", number),
+            };
+        }
+    }
+    if let Some((_, unit)) = UNITS.iter().find(|(other, _)| *other == tag) {
+        if let [number] = numbers.as_slice() {
+            return match tag {
+                Tags::FocalLength => format!("{:.1}{}", number, unit),
+                _ if !number.is_finite() => format_number(*number),
+                _ => format!("{}{}", format_number(*number), unit),
+            };
+        }
+    }
+
+    match (tag, numbers.as_slice()) {
+        (Tags::ExposureTime, [seconds]) => get_exposure_time(*seconds),
+        (Tags::ShutterSpeedValue, [value]) => get_exposure_time(match value.abs() < 100.0 {
+            true => 2_f64.powf(-value),
+            false => 0.0,
+        }),
+        (Tags::FNumber, [value]) => get_fnumber(*value),
+        (Tags::ApertureValue | Tags::MaxApertureValue, [value]) => {
+            format!("{:.1}", 2_f64.powf(value / 2.0))
+        }
+        (Tags::ExposureBiasValue, [value]) => get_fraction(*value),
+        (Tags::LensSpecification, values) => {
+            get_lens_info(values).unwrap_or_else(|| get_numbers_as_string(values))
+        }
+        (Tags::GPSVersionID, values) => values
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<String>>()
+            .join("."),
+        (tag, [degrees, minutes, seconds]) if COORDINATE_TAGS.contains(&tag) => {
+            get_dms(*degrees, *minutes, *seconds)
+        }
+        (Tags::GPSTimeStamp, [hours, minutes, seconds]) => get_time(*hours, *minutes, *seconds),
+        (_, values) => get_numbers_as_string(values),
+    }
+}
+
+fn get_numbers_as_string(values: &[f64]) -> String {
+    values
+        .iter()
+        .map(|value| format_number(*value))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Converts a value written as exiftool prints it back to the raw text form of
+// `ExifImage::set_tag_from_string`. Values that aren't in a printed form are kept as they are.
+// The coordinates are left to `gps::set_coordinate_from_string`, which reads the printed form.
+pub fn parse_print_value(tag: Tag, value: &str) -> String {
+    let value = value.trim();
+    if let Some((_, names)) = NUMBER_NAMES.iter().find(|(other, _)| *other == tag) {
+        if let Some((number, _)) = names
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(value))
+        {
+            return number.to_string();
+        }
+    }
+    if let Some((_, names)) = LETTER_NAMES.iter().find(|(other, _)| *other == tag) {
+        if let Some((letter, _)) = names
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(value))
+        {
+            return letter.to_string();
+        }
+    }
+    if let Some((_, unit)) = UNITS.iter().find(|(other, _)| *other == tag) {
+        if let Some(number) = value.strip_suffix(unit.trim()) {
+            return number.trim().to_string();
+        }
+    }
+
+    let numbers: Vec<f64> = value
+        .split(|c: char| !c.is_ascii_digit() && c != '.')
+        .filter_map(|number| number.parse().ok())
+        .collect();
+    match (tag, numbers.as_slice()) {
+        (Tags::GPSTimeStamp, [hours, minutes, seconds]) if value.contains(':') => {
+            format!("{} {} {}", hours, minutes, seconds)
+        }
+        _ => value.to_string(),
+    }
+}
+
+// All the tags, named and printed as exiftool does. The EXIF tags use the print conversions of
+// exiftool, while the other sections keep their own display. A tag that is in several IFDs is only
+// listed the first time, as the 0th IFD comes before the 1st.
+pub fn get_all_tags(img: &ExifImage, is_numeric: bool) -> Vec<(String, String)> {
+    let mut tags: Vec<(String, String)> = Vec::new();
+    for (ifd_name, ifd) in img.get_ifds() {
+        for entry in ifd.get_entries(img.get_slice()) {
+            let tag = Tag(entry.tag as usize);
+            if POINTER_TAGS.contains(&tag) {
+                continue;
+            }
+            let name = match (ifd_name.as_str(), tag.get_interop_name()) {
+                ("Interop", Some(name)) => format!("EXIF:{}", name),
+                _ => format!("{:#}", tag),
+            };
+            if tags.iter().any(|(other, _)| *other == name) {
+                continue;
+            }
+            let value = match is_numeric || ifd_name == "Interop" {
+                true => entry.get_value_as_text(img.is_little_endian()),
+                false => get_print_value(&entry, img.is_little_endian()),
+            };
+            tags.push((name, value));
+        }
+    }
+
+    for (section, section_tags) in img.get_all_sections() {
+        let group = match SECTION_GROUPS.iter().find(|(name, _)| *name == section) {
+            // The comments of PNG files are text chunks
+            Some(_) if section == "Comments" && img.get_image_format() == ImageFormat::Png => "PNG",
+            Some((_, group)) => group,
+            None => continue,
+        };
+        for (name, value) in section_tags {
+            // XMP properties lose their namespace prefix, as in XMP:Creator for dc:creator
+            let name = match section.as_str() {
+                "XMP" => {
+                    let name = name.rsplit(':').next().unwrap_or_default();
+                    let mut chars = name.chars();
+                    chars
+                        .next()
+                        .map(|first| first.to_uppercase().chain(chars).collect())
+                        .unwrap_or_default()
+                }
+                _ => name.replace(' ', ""),
+            };
+            tags.push((format!("{}:{}", group, name), value));
+        }
+    }
+    tags
+}
@@ -1,7 +1,95 @@
-#[derive(PartialEq)]
+use core::fmt;
+
+use crate::raw::{get_make, is_cr2, is_orf, is_raf, is_rw2};
+use crate::webp::is_webp;
+
+#[derive(PartialEq, Clone, Copy)]
 pub enum ImageFormat {
     Jpeg,
     Png,
+    Webp,
+    Tiff,
+    // Camera raw formats
+    Cr2,
+    Nef,
+    Arw,
+    Orf,
+    Rw2,
+    Raf,
+}
+
+impl ImageFormat {
+    // Formats whose file starts with a TIFF header, possibly with a vendor specific magic number
+    pub fn is_tiff_based(&self) -> bool {
+        matches!(
+            self,
+            ImageFormat::Tiff
+                | ImageFormat::Cr2
+                | ImageFormat::Nef
+                | ImageFormat::Arw
+                | ImageFormat::Orf
+                | ImageFormat::Rw2
+        )
+    }
+
+    // Case is ignored, so that `jpeg` works as well as `JPEG`
+    pub fn from_name(name: &str) -> Option<ImageFormat> {
+        let name = match name.to_ascii_uppercase().as_str() {
+            "JPG" => "JPEG",
+            "TIF" => "TIFF",
+            _ => name,
+        };
+        [
+            ImageFormat::Jpeg,
+            ImageFormat::Png,
+            ImageFormat::Webp,
+            ImageFormat::Tiff,
+            ImageFormat::Cr2,
+            ImageFormat::Nef,
+            ImageFormat::Arw,
+            ImageFormat::Orf,
+            ImageFormat::Rw2,
+            ImageFormat::Raf,
+        ]
+        .into_iter()
+        .find(|format| format.to_string().eq_ignore_ascii_case(name))
+    }
+
+    // ORF and RW2 use their own magic numbers and RAF is not TIFF-based at all, so only the
+    // formats that stick to the TIFF spec can be edited
+    pub fn is_editable(&self) -> bool {
+        matches!(
+            self,
+            ImageFormat::Jpeg
+                | ImageFormat::Png
+                | ImageFormat::Webp
+                | ImageFormat::Tiff
+                | ImageFormat::Cr2
+                | ImageFormat::Nef
+                | ImageFormat::Arw
+        )
+    }
+}
+
+impl fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ImageFormat::Jpeg => "JPEG",
+                ImageFormat::Png => "PNG",
+                ImageFormat::Webp => "WebP",
+                ImageFormat::Tiff => "TIFF",
+                ImageFormat::Cr2 => "CR2",
+                ImageFormat::Nef => "NEF",
+                ImageFormat::Arw => "ARW",
+                ImageFormat::Orf => "ORF",
+                ImageFormat::Rw2 => "RW2",
+                ImageFormat::Raf => "RAF",
+            }
+        )
+    }
 }
 
 // See https://en.wikipedia.org/wiki/List_of_file_signatures
@@ -10,32 +98,40 @@ pub fn get_image_format_for(slice: &[u8]) -> Option<ImageFormat> {
         Some(ImageFormat::Jpeg)
     } else if is_png(slice) {
         Some(ImageFormat::Png)
+    } else if is_webp(slice) {
+        Some(ImageFormat::Webp)
+    } else if is_raf(slice) {
+        Some(ImageFormat::Raf)
+    } else if is_orf(slice) {
+        Some(ImageFormat::Orf)
+    } else if is_rw2(slice) {
+        Some(ImageFormat::Rw2)
+    } else if is_tiff(slice) {
+        if is_cr2(slice) {
+            return Some(ImageFormat::Cr2);
+        }
+
+        // NEF and ARW files are plain TIFF files, only the camera maker tells them apart
+        Some(match get_make(slice) {
+            Some(make) if make.starts_with("NIKON") => ImageFormat::Nef,
+            Some(make) if make.starts_with("SONY") => ImageFormat::Arw,
+            _ => ImageFormat::Tiff,
+        })
     } else {
         None
     }
 }
 
+// Any SOI marker followed by another marker, the segments are checked when reading the file
 fn is_jpeg(slice: &[u8]) -> bool {
-    slice.starts_with(vec![0xFF, 0xD8, 0xFF, 0xDB].as_ref())
-        || slice.starts_with(
-            vec![
-                0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, 0x4A, 0x46, 0x49, 0x46, 0x00, 0x01,
-            ]
-            .as_ref(),
-        )
-        || slice.starts_with(vec![0xFF, 0xD8, 0xFF, 0xEE].as_ref())
-        || slice.starts_with(
-            vec![
-                0xFF, 0xD8, 0xFF, 0xE1, 0x00, 0x3F, 0x45, 0x78, 0x69, 0x66, 0x00, 0x00,
-            ]
-            .as_ref(),
-        )
-        || (slice.len() >= 13
-            && slice.starts_with(vec![0xFF, 0xD8, 0xFF, 0xE1].as_ref())
-            && slice[6..13].starts_with(vec![0x45, 0x78, 0x69, 0x66, 0x00, 0x00].as_ref()))
-        || slice.starts_with(vec![0xFF, 0xD8, 0xFF, 0xE0].as_ref())
+    slice.starts_with(vec![0xFF, 0xD8, 0xFF].as_ref())
 }
 
 fn is_png(slice: &[u8]) -> bool {
     slice.starts_with(vec![0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A].as_ref())
 }
+
+fn is_tiff(slice: &[u8]) -> bool {
+    slice.starts_with(vec![0x49, 0x49, 0x2A, 0x00].as_ref())
+        || slice.starts_with(vec![0x4D, 0x4D, 0x00, 0x2A].as_ref())
+}
@@ -0,0 +1,431 @@
+use crate::datetime::{parse_offset, DateTime};
+use crate::exif::{ExifImage, IFDTypes};
+use crate::tags::{Tag, Tags};
+
+// The decimals of the seconds written in GPSLatitude and GPSLongitude by default, about 3 mm
+pub const DEFAULT_SECONDS_DECIMALS: usize = 4;
+// More would overflow the numerator of the seconds
+pub const MAX_SECONDS_DECIMALS: usize = 7;
+
+// A tag holding degrees, minutes and seconds, with the tag of its hemisphere
+struct Coordinate {
+    tag: Tag,
+    ref_tag: Tag,
+    max: f64,
+    refs: [&'static str; 2], // Positive, then negative
+}
+
+const COORDINATES: [Coordinate; 4] = [
+    Coordinate {
+        tag: Tags::GPSLatitude,
+        ref_tag: Tags::GPSLatitudeRef,
+        max: 90.0,
+        refs: ["N", "S"],
+    },
+    Coordinate {
+        tag: Tags::GPSLongitude,
+        ref_tag: Tags::GPSLongitudeRef,
+        max: 180.0,
+        refs: ["E", "W"],
+    },
+    Coordinate {
+        tag: Tags::GPSDestLatitude,
+        ref_tag: Tags::GPSDestLatitudeRef,
+        max: 90.0,
+        refs: ["N", "S"],
+    },
+    Coordinate {
+        tag: Tags::GPSDestLongitude,
+        ref_tag: Tags::GPSDestLongitudeRef,
+        max: 180.0,
+        refs: ["E", "W"],
+    },
+];
+
+// A position in decimal degrees, positive to the north and east, with its altitude in meters
+pub struct Position {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: Option<f64>,
+}
+
+pub fn is_coordinate_tag(tag: Tag) -> bool {
+    COORDINATES.iter().any(|coordinate| coordinate.tag == tag)
+}
+
+fn get_coordinate_info(tag: Tag) -> Result<&'static Coordinate, String> {
+    COORDINATES
+        .iter()
+        .find(|coordinate| coordinate.tag == tag)
+        .ok_or_else(|| format!("{} isn't a coordinate", tag))
+}
+
+// The position of the GPS IFD. The coordinates without their Ref tag are taken as north and east.
+pub fn get_position(img: &ExifImage) -> Option<Position> {
+    let altitude = img
+        .get_rationals(IFDTypes::GPS, Tags::GPSAltitude)
+        .and_then(|altitude| match altitude.as_slice() {
+            [(num, den)] if *den != 0 => Some(*num as f64 / *den as f64),
+            _ => None,
+        });
+    let is_below_sea_level = img
+        .get_uints(IFDTypes::GPS, Tags::GPSAltitudeRef)
+        .is_some_and(|altitude_ref| altitude_ref.first() == Some(&1));
+
+    Some(Position {
+        latitude: get_coordinate(img, Tags::GPSLatitude)?,
+        longitude: get_coordinate(img, Tags::GPSLongitude)?,
+        altitude: altitude.map(|altitude| match is_below_sea_level {
+            true => -altitude,
+            false => altitude,
+        }),
+    })
+}
+
+// The signed decimal degrees of a coordinate tag, with the sign of its Ref tag
+pub fn get_coordinate(img: &ExifImage, tag: Tag) -> Option<f64> {
+    let coordinate = get_coordinate_info(tag).ok()?;
+    let value = match img.get_rationals(IFDTypes::GPS, tag)?.as_slice() {
+        [degrees, minutes, seconds]
+            if [degrees, minutes, seconds].iter().all(|(_, den)| *den != 0) =>
+        {
+            let [degrees, minutes, seconds] =
+                [degrees, minutes, seconds].map(|(num, den)| *num as f64 / *den as f64);
+            degrees + minutes / 60.0 + seconds / 3600.0
+        }
+        _ => return None,
+    };
+    let is_negative = img
+        .get_ascii(IFDTypes::GPS, coordinate.ref_tag)
+        .is_some_and(|value| {
+            value
+                .trim_end_matches(['\0', ' '])
+                .eq_ignore_ascii_case(coordinate.refs[1])
+        });
+    Some(if is_negative { -value } else { value })
+}
+
+// The coordinate in signed decimal degrees and in degrees, minutes and seconds with its
+// hemisphere, as in `48.858400 (48° 51' 30.24" N)`
+pub fn get_coordinate_as_string(img: &ExifImage, tag: Tag) -> Option<String> {
+    let coordinate = get_coordinate_info(tag).ok()?;
+    let value = get_coordinate(img, tag)?;
+    let (degrees, minutes, seconds) = get_dms(value, 2);
+    Some(format!(
+        "{:.6} ({}° {}' {:.2}\" {})",
+        value,
+        degrees,
+        minutes,
+        seconds,
+        coordinate.refs[(value < 0.0) as usize]
+    ))
+}
+
+// The degrees, minutes and seconds of the value without its sign, the seconds being rounded to
+// `decimals` decimals
+fn get_dms(value: f64, decimals: usize) -> (f64, f64, f64) {
+    let value = value.abs();
+    let mut degrees = value.trunc();
+    let mut minutes = ((value - degrees) * 60.0).trunc();
+    let scale = 10_f64.powi(decimals as i32);
+    let mut seconds = (((value - degrees) * 3600.0 - minutes * 60.0) * scale).round() / scale;
+    // The rounding of the seconds can carry over
+    if seconds >= 60.0 {
+        seconds -= 60.0;
+        minutes += 1.0;
+    }
+    if minutes >= 60.0 {
+        minutes -= 60.0;
+        degrees += 1.0;
+    }
+    (degrees, minutes, seconds)
+}
+
+// Degrees, minutes and seconds as written in GPSLatitude and GPSLongitude, without the sign:
+// 48.8584 gives `48 51 30.2400`
+pub fn get_dms_text(value: f64, decimals: usize) -> String {
+    let (degrees, minutes, seconds) = get_dms(value, decimals);
+    format!("{} {} {:.*}", degrees, minutes, decimals, seconds)
+}
+
+// Decimal degrees (48.8584 or -2.2945), degrees and decimal minutes (48 51.504), or degrees,
+// minutes and seconds (48° 51' 30.24" or 48/1 51/1 3024/100), followed or preceded by the
+// hemisphere. Returns the degrees without their sign, with whether they are negative when the
+// text gives a sign or a hemisphere, or when they are decimal degrees, which are signed.
+fn parse_coordinate(text: &str, coordinate: &Coordinate) -> Result<(f64, Option<bool>), String> {
+    let invalid = || {
+        format!(
+            "Invalid {} {}, expected something like 48.8584, -2.2945 or 48° 51' 30.24\" {}",
+            coordinate.tag, text, coordinate.refs[0]
+        )
+    };
+    let mut rest = text.trim();
+    let mut is_negative = None;
+    for (i, hemisphere) in coordinate.refs.iter().enumerate() {
+        let is_hemisphere = |c: char| hemisphere.eq_ignore_ascii_case(c.encode_utf8(&mut [0; 4]));
+        let stripped = match (rest.chars().next(), rest.chars().next_back()) {
+            (Some(first), _) if is_hemisphere(first) => &rest[first.len_utf8()..],
+            (_, Some(last)) if is_hemisphere(last) => &rest[..rest.len() - last.len_utf8()],
+            _ => continue,
+        };
+        rest = stripped.trim();
+        is_negative = Some(i == 1);
+        break;
+    }
+    if let Some(unsigned) = rest.strip_prefix(['-', '+']) {
+        if is_negative.is_some() {
+            return Err(format!(
+                "{} has both a sign and a hemisphere",
+                coordinate.tag
+            ));
+        }
+        is_negative = Some(rest.starts_with('-'));
+        rest = unsigned;
+    }
+
+    let numbers = rest
+        .replace("deg", " ")
+        .replace(['°', 'º', '\'', '′', '"', '″'], " ")
+        .split_whitespace()
+        .map(|number| match number.split_once('/') {
+            Some((num, den)) => {
+                let (num, den) = (num.parse::<f64>().ok()?, den.parse::<f64>().ok()?);
+                (den != 0.0).then(|| num / den)
+            }
+            None => number.parse::<f64>().ok(),
+        })
+        .collect::<Option<Vec<f64>>>()
+        .filter(|numbers| {
+            numbers
+                .iter()
+                .all(|number| number.is_finite() && *number >= 0.0)
+        })
+        .ok_or_else(invalid)?;
+    let degrees = match numbers.as_slice() {
+        [degrees] => {
+            is_negative.get_or_insert(false);
+            *degrees
+        }
+        [degrees, minutes] if *minutes < 60.0 => degrees + minutes / 60.0,
+        [degrees, minutes, seconds] if *minutes < 60.0 && *seconds < 60.0 => {
+            degrees + minutes / 60.0 + seconds / 3600.0
+        }
+        _ => return Err(invalid()),
+    };
+    if degrees > coordinate.max {
+        return Err(format!(
+            "{} must be between -{} and {}",
+            coordinate.tag, coordinate.max, coordinate.max
+        ));
+    }
+    Ok((degrees, is_negative))
+}
+
+// The tags of the coordinate written with the seconds rounded to `decimals` decimals. The
+// hemisphere is left as it is when the value doesn't give one, unless the image has none.
+fn get_coordinate_values(
+    img: &ExifImage,
+    coordinate: &Coordinate,
+    degrees: f64,
+    is_negative: Option<bool>,
+    decimals: usize,
+) -> Vec<(Tag, String)> {
+    let mut values = vec![(coordinate.tag, get_dms_text(degrees, decimals))];
+    let has_ref = img.get_interop(IFDTypes::GPS, coordinate.ref_tag).is_some();
+    match is_negative {
+        Some(is_negative) => values.push((
+            coordinate.ref_tag,
+            coordinate.refs[is_negative as usize].to_string(),
+        )),
+        None if !has_ref => values.push((coordinate.ref_tag, coordinate.refs[0].to_string())),
+        None => (),
+    }
+    values
+}
+
+// Writes the values in the GPS IFD, which is created if the image has none
+fn set_gps_values(img: &mut ExifImage, mut values: Vec<(Tag, String)>) -> Result<(), String> {
+    if img.get_interop(IFDTypes::GPS, Tags::GPSVersionID).is_none() {
+        values.push((Tags::GPSVersionID, String::from("2 3 0 0")));
+    }
+    let mut entries = Vec::new();
+    for (tag, value) in values {
+        entries.push(img.get_entry_from_string(tag, &value)?.1);
+    }
+    img.set_tags(IFDTypes::GPS, entries)
+}
+
+// Sets a coordinate tag and its hemisphere from any of the forms of `parse_coordinate`
+pub fn set_coordinate_from_string(
+    img: &mut ExifImage,
+    tag: Tag,
+    text: &str,
+    decimals: usize,
+) -> Result<(), String> {
+    let coordinate = get_coordinate_info(tag)?;
+    let (degrees, is_negative) = parse_coordinate(text, coordinate)?;
+    let values = get_coordinate_values(img, coordinate, degrees, is_negative, decimals);
+    set_gps_values(img, values)
+}
+
+// Sets GPSLatitude and GPSLongitude from a position such as `48.8584, 2.2945` or
+// `48° 51' 30.24" N, 2° 17' 40.2" E`
+pub fn set_position_from_string(
+    img: &mut ExifImage,
+    text: &str,
+    decimals: usize,
+) -> Result<(), String> {
+    let (latitude, longitude) = text.split_once(',').ok_or_else(|| {
+        format!(
+            "Invalid position {}, expected a latitude and a longitude such as 48.8584, 2.2945",
+            text
+        )
+    })?;
+    let mut values = Vec::new();
+    for (coordinate, text) in COORDINATES.iter().zip([latitude, longitude]) {
+        let (degrees, is_negative) = parse_coordinate(text, coordinate)?;
+        values.extend(get_coordinate_values(
+            img,
+            coordinate,
+            degrees,
+            is_negative,
+            decimals,
+        ));
+    }
+    set_gps_values(img, values)
+}
+
+// Writes the position, with the time (in seconds since 1970-01-01 UTC) it was measured at. A
+// previous altitude is removed when the new position has none, as it belonged to another place.
+pub fn set_position(
+    img: &mut ExifImage,
+    position: &Position,
+    timestamp: Option<i64>,
+    decimals: usize,
+) -> Result<(), String> {
+    let mut values = Vec::new();
+    for (coordinate, value) in COORDINATES
+        .iter()
+        .zip([position.latitude, position.longitude])
+    {
+        values.extend(get_coordinate_values(
+            img,
+            coordinate,
+            value.abs(),
+            Some(value < 0.0),
+            decimals,
+        ));
+    }
+    if let Some(altitude) = position.altitude {
+        values.push((
+            Tags::GPSAltitudeRef,
+            String::from(if altitude < 0.0 { "1" } else { "0" }),
+        ));
+        values.push((Tags::GPSAltitude, format!("{:.2}", altitude.abs())));
+    }
+    if let Some(timestamp) = timestamp {
+        let date = DateTime::from_timestamp(timestamp);
+        values.push((
+            Tags::GPSTimeStamp,
+            format!("{} {} {}", date.hour, date.minute, date.second),
+        ));
+        values.push((Tags::GPSDateStamp, date.format("%Y:%m:%d")?));
+    }
+
+    set_gps_values(img, values)?;
+    if position.altitude.is_none() && img.get_interop(IFDTypes::GPS, Tags::GPSAltitude).is_some() {
+        img.remove_tags(IFDTypes::GPS, &[Tags::GPSAltitudeRef, Tags::GPSAltitude])?;
+    }
+    Ok(())
+}
+
+// The time in seconds since 1970-01-01 UTC that the position was measured at, from the GPS date
+// and time, or else from DateTimeOriginal and its time zone
+pub fn get_position_time(img: &ExifImage) -> Option<i64> {
+    let gps_time = img
+        .get_rationals(IFDTypes::GPS, Tags::GPSTimeStamp)
+        .zip(img.get_ascii(IFDTypes::GPS, Tags::GPSDateStamp))
+        .and_then(|(time, date)| {
+            let [hour, minute, second] = match time.as_slice() {
+                [hour, minute, second] if time.iter().all(|(_, den)| *den != 0) => {
+                    [hour, minute, second].map(|(num, den)| num / den)
+                }
+                _ => return None,
+            };
+            let date = date.trim_end_matches(['\0', ' ']);
+            DateTime::from_exif(&format!("{} {:02}:{:02}:{:02}", date, hour, minute, second))
+        });
+    if let Some(date) = gps_time {
+        return Some(date.get_timestamp());
+    }
+
+    let date = DateTime::from_exif(&img.get_value_as_string(Tags::DateTimeOriginal)?)?;
+    let offset = img.get_value_as_string(Tags::OffsetTimeOriginal)?;
+    Some(date.get_timestamp() - parse_offset(offset.trim_end_matches(['\0', ' ']))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str, tag: Tag) -> Result<(f64, Option<bool>), String> {
+        parse_coordinate(text, get_coordinate_info(tag).unwrap())
+    }
+
+    fn assert_coordinate(text: &str, tag: Tag, degrees: f64, is_negative: Option<bool>) {
+        let (value, sign) = parse(text, tag).unwrap();
+        assert!((value - degrees).abs() < 1e-9, "{}: {}", text, value);
+        assert_eq!(sign, is_negative, "{}", text);
+    }
+
+    #[test]
+    fn decimal_degrees() {
+        assert_coordinate("48.8584", Tags::GPSLatitude, 48.8584, Some(false));
+        assert_coordinate("-33.5", Tags::GPSLatitude, 33.5, Some(true));
+        assert_coordinate("+2.2945", Tags::GPSLongitude, 2.2945, Some(false));
+        assert_coordinate("179.25 W", Tags::GPSLongitude, 179.25, Some(true));
+    }
+
+    #[test]
+    fn degrees_minutes_seconds() {
+        let degrees = 48.0 + 51.0 / 60.0 + 30.24 / 3600.0;
+        assert_coordinate("48 51 30.24", Tags::GPSLatitude, degrees, None);
+        assert_coordinate("48/1 51/1 3024/100", Tags::GPSLatitude, degrees, None);
+        assert_coordinate("48° 51' 30.24\" N", Tags::GPSLatitude, degrees, Some(false));
+        assert_coordinate("N 48° 51′ 30.24″", Tags::GPSLatitude, degrees, Some(false));
+        assert_coordinate(
+            "48 deg 51' 30.24\" s",
+            Tags::GPSLatitude,
+            degrees,
+            Some(true),
+        );
+        assert_coordinate("-48 51 30.24", Tags::GPSLatitude, degrees, Some(true));
+        assert_coordinate("48 51.504", Tags::GPSLatitude, 48.8584, None);
+    }
+
+    #[test]
+    fn invalid_coordinates() {
+        for text in [
+            "",
+            "N",
+            "-48 N",
+            "91",
+            "48 60",
+            "48 51 60",
+            "48 1/0",
+            "48.8, 2.3",
+            "48 E",
+            "1 2 3 4",
+        ] {
+            assert!(parse(text, Tags::GPSLatitude).is_err(), "{}", text);
+        }
+        assert!(parse("181 E", Tags::GPSLongitude).is_err());
+    }
+
+    #[test]
+    fn dms() {
+        assert_eq!(get_dms_text(48.8584, 4), "48 51 30.2400");
+        // The rounding of the seconds carries over to the minutes and the degrees
+        assert_eq!(get_dms_text(48.99999999, 2), "49 0 0.00");
+    }
+}
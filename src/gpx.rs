@@ -0,0 +1,290 @@
+use std::path::Path;
+
+use crate::datetime::{parse_offset, DateTime};
+use crate::gps::Position;
+use crate::xml::{parse_xml, XMLElement, XMLNode};
+
+// A point of a GPX track, with its time in seconds since 1970-01-01 UTC
+#[derive(Clone, Copy)]
+pub struct TrackPoint {
+    pub timestamp: i64,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: Option<f64>,
+}
+
+// A geotagged image, as it is exported
+pub struct Waypoint {
+    pub path: String,
+    pub position: Position,
+    pub timestamp: Option<i64>,
+    pub thumbnail: Option<Vec<u8>>, // A JPEG
+}
+
+impl Waypoint {
+    pub fn get_file_name(&self) -> String {
+        Path::new(&self.path)
+            .file_name()
+            .map_or(self.path.clone(), |name| name.to_string_lossy().to_string())
+    }
+}
+
+// The points of all the tracks and segments of the document, in chronological order. The points
+// without a time can't be matched with images, so they are left out.
+pub fn parse_gpx(text: &str) -> Result<Vec<TrackPoint>, String> {
+    let root = parse_xml(text)?;
+    if root.get_local_name() != "gpx" {
+        return Err(String::from("Not a GPX document"));
+    }
+    let mut points = Vec::new();
+    collect_points(&root, &mut points)?;
+    if points.is_empty() {
+        return Err(String::from("The track has no point with a time"));
+    }
+    points.sort_by_key(|point| point.timestamp);
+    Ok(points)
+}
+
+fn collect_points(element: &XMLElement, points: &mut Vec<TrackPoint>) -> Result<(), String> {
+    for child in element.get_child_elements() {
+        if child.get_local_name() != "trkpt" {
+            collect_points(child, points)?;
+            continue;
+        }
+        let get_coordinate = |name: &str, max: f64| {
+            child
+                .get_attribute(name)
+                .and_then(|value| value.trim().parse::<f64>().ok())
+                .filter(|value| value.abs() <= max)
+                .ok_or_else(|| format!("A track point has no valid {}", name))
+        };
+        let get_text = |name: &str| {
+            child
+                .get_child_elements()
+                .find(|element| element.get_local_name() == name)
+                .map(|element| element.get_text().trim().to_string())
+        };
+
+        let timestamp = match get_text("time") {
+            Some(time) => parse_time(&time).ok_or_else(|| format!("Invalid time {}", time))?,
+            None => continue,
+        };
+        points.push(TrackPoint {
+            timestamp,
+            latitude: get_coordinate("lat", 90.0)?,
+            longitude: get_coordinate("lon", 180.0)?,
+            altitude: get_text("ele").and_then(|ele| ele.parse().ok()),
+        });
+    }
+    Ok(())
+}
+
+// ISO 8601 times, such as 2024-05-03T12:30:12Z or 2024-05-03T14:30:12.250+02:00. The times
+// without a time zone are taken as UTC, which GPX requires them to be.
+fn parse_time(text: &str) -> Option<i64> {
+    let (date, time) = text.split_once('T')?;
+    let (time, zone) = time.split_at(time.find(['Z', '+', '-']).unwrap_or(time.len()));
+    let offset = match zone {
+        "" => 0,
+        zone => parse_offset(zone)?,
+    };
+    let date = DateTime::from_exif(&format!("{} {}", date.replace('-', ":"), time))?;
+    Some(date.get_timestamp() - offset)
+}
+
+// The position at the time, interpolated between the points around it. Outside of the track, the
+// position of its first or last point is used. Fails when the nearest points are more than
+// `max_gap` seconds away, as the camera could have been anywhere in between.
+pub fn get_position_at(
+    points: &[TrackPoint],
+    timestamp: i64,
+    max_gap: i64,
+) -> Result<TrackPoint, String> {
+    let i = points.partition_point(|point| point.timestamp < timestamp);
+    let (before, after) = (i.checked_sub(1).map(|i| &points[i]), points.get(i));
+    let (before, after) = match (before, after) {
+        (_, Some(after)) if after.timestamp == timestamp => return Ok(*after),
+        (Some(before), Some(after)) => (before, after),
+        (Some(point), None) | (None, Some(point)) => {
+            let distance = (timestamp - point.timestamp).abs();
+            return match distance > max_gap {
+                true => Err(format!("The track is {}s away from this time", distance)),
+                false => Ok(TrackPoint {
+                    timestamp,
+                    ..*point
+                }),
+            };
+        }
+        (None, None) => return Err(String::from("The track is empty")),
+    };
+
+    let gap = after.timestamp - before.timestamp;
+    if gap > max_gap {
+        return Err(format!("The track has a gap of {}s around this time", gap));
+    }
+    let ratio = (timestamp - before.timestamp) as f64 / gap as f64;
+    // The shortest way between the longitudes can cross the antimeridian
+    let mut longitude_delta = after.longitude - before.longitude;
+    if longitude_delta > 180.0 {
+        longitude_delta -= 360.0;
+    } else if longitude_delta < -180.0 {
+        longitude_delta += 360.0;
+    }
+    let mut longitude = before.longitude + longitude_delta * ratio;
+    if longitude > 180.0 {
+        longitude -= 360.0;
+    } else if longitude < -180.0 {
+        longitude += 360.0;
+    }
+
+    Ok(TrackPoint {
+        timestamp,
+        latitude: before.latitude + (after.latitude - before.latitude) * ratio,
+        longitude,
+        altitude: match (before.altitude, after.altitude) {
+            (Some(before), Some(after)) => Some(before + (after - before) * ratio),
+            (before, after) => before.or(after),
+        },
+    })
+}
+
+// A track through the images in the order they are given, named after their files
+pub fn write_gpx(waypoints: &[Waypoint]) -> String {
+    let mut segment = XMLElement::new("trkseg");
+    for waypoint in waypoints {
+        let mut point = XMLElement::new("trkpt");
+        point.attributes = vec![
+            (
+                String::from("lat"),
+                format!("{:.7}", waypoint.position.latitude),
+            ),
+            (
+                String::from("lon"),
+                format!("{:.7}", waypoint.position.longitude),
+            ),
+        ];
+        // In the order of the GPX schema
+        let mut elements = Vec::new();
+        if let Some(altitude) = waypoint.position.altitude {
+            elements.push(XMLElement::with_text("ele", &format!("{:.2}", altitude)));
+        }
+        if let Some(timestamp) = waypoint.timestamp {
+            elements.push(XMLElement::with_text("time", &format_time(timestamp)));
+        }
+        elements.push(XMLElement::with_text("name", &waypoint.get_file_name()));
+        elements.push(XMLElement::with_text("desc", &waypoint.path));
+        point.children = elements.into_iter().map(XMLNode::Element).collect();
+        segment.children.push(XMLNode::Element(point));
+    }
+
+    let mut track = XMLElement::new("trk");
+    track.children.push(XMLNode::Element(segment));
+    let mut gpx = XMLElement::new("gpx");
+    gpx.attributes = vec![
+        (String::from("version"), String::from("1.1")),
+        (String::from("creator"), String::from("ExifEdiTUI")),
+        (
+            String::from("xmlns"),
+            String::from("http://www.topografix.com/GPX/1/1"),
+        ),
+    ];
+    gpx.children.push(XMLNode::Element(track));
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{}",
+        gpx.to_xml()
+    )
+}
+
+// As in 2024-05-03T12:30:12Z
+pub fn format_time(timestamp: i64) -> String {
+    let date = DateTime::from_timestamp(timestamp);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        date.year, date.month, date.day, date.hour, date.minute, date.second
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(timestamp: i64, latitude: f64, longitude: f64, altitude: Option<f64>) -> TrackPoint {
+        TrackPoint {
+            timestamp,
+            latitude,
+            longitude,
+            altitude,
+        }
+    }
+
+    fn assert_close(value: f64, expected: f64) {
+        assert!((value - expected).abs() < 1e-9, "{} != {}", value, expected);
+    }
+
+    #[test]
+    fn interpolation() {
+        let points = [
+            point(1000, 48.0, 2.0, Some(100.0)),
+            point(1100, 49.0, 3.0, Some(200.0)),
+            point(1200, 50.0, 4.0, None),
+        ];
+        let position = get_position_at(&points, 1025, 300).unwrap();
+        assert_close(position.latitude, 48.25);
+        assert_close(position.longitude, 2.25);
+        assert_close(position.altitude.unwrap(), 125.0);
+        assert_eq!(position.timestamp, 1025);
+
+        let position = get_position_at(&points, 1100, 300).unwrap();
+        assert_close(position.latitude, 49.0);
+        // Only one of the points has an altitude
+        let position = get_position_at(&points, 1150, 300).unwrap();
+        assert_close(position.altitude.unwrap(), 200.0);
+    }
+
+    #[test]
+    fn outside_of_the_track() {
+        let points = [point(1000, 48.0, 2.0, None), point(1100, 49.0, 3.0, None)];
+        let position = get_position_at(&points, 900, 100).unwrap();
+        assert_close(position.latitude, 48.0);
+        assert_eq!(position.timestamp, 900);
+        let position = get_position_at(&points, 1150, 100).unwrap();
+        assert_close(position.latitude, 49.0);
+        assert!(get_position_at(&points, 899, 100).is_err());
+        assert!(get_position_at(&points, 1201, 100).is_err());
+        assert!(get_position_at(&[], 1000, 100).is_err());
+    }
+
+    #[test]
+    fn gaps() {
+        let points = [point(1000, 48.0, 2.0, None), point(2000, 49.0, 3.0, None)];
+        assert!(get_position_at(&points, 1500, 999).is_err());
+        assert!(get_position_at(&points, 1500, 1000).is_ok());
+        // The points themselves are still found
+        assert!(get_position_at(&points, 2000, 10).is_ok());
+    }
+
+    #[test]
+    fn antimeridian() {
+        let points = [point(0, 0.0, 179.0, None), point(100, 0.0, -179.0, None)];
+        assert_close(get_position_at(&points, 25, 100).unwrap().longitude, 179.5);
+        assert_close(get_position_at(&points, 75, 100).unwrap().longitude, -179.5);
+        let position = get_position_at(&points, 50, 100).unwrap();
+        assert_close(position.longitude.abs(), 180.0);
+
+        let points = [point(0, 0.0, -179.0, None), point(100, 0.0, 179.0, None)];
+        assert_close(get_position_at(&points, 25, 100).unwrap().longitude, -179.5);
+        assert_close(get_position_at(&points, 75, 100).unwrap().longitude, 179.5);
+    }
+
+    #[test]
+    fn times() {
+        assert_eq!(parse_time("2024-05-03T12:30:12Z"), Some(1714739412));
+        assert_eq!(
+            parse_time("2024-05-03T14:30:12.250+02:00"),
+            Some(1714739412)
+        );
+        assert_eq!(parse_time("2024-05-03T12:30:12"), Some(1714739412));
+        assert_eq!(parse_time("2024-05-03T07:00:12-05:30"), Some(1714739412));
+        assert_eq!(parse_time("2024-05-03 12:30:12Z"), None);
+    }
+}
@@ -0,0 +1,328 @@
+use crate::jpeg::{get_segments, APP2};
+use crate::png;
+
+// See the ICC specification (ICC.1:2022), section 7.2 for the header and 7.3 for the tag table
+pub const ICC_IDENTIFIER: &[u8] = b"ICC_PROFILE\0";
+const HEADER_SIZE: usize = 128;
+const PROFILE_SIGNATURE: &[u8; 4] = b"acsp";
+
+// In JPEG files, the profile is split in APP2 segments that start with the identifier, the
+// sequence number of the chunk (from 1) and the number of chunks
+pub fn read_jpeg_icc(slice: &[u8]) -> Result<Option<Vec<u8>>, String> {
+    let mut chunks: Vec<(u8, u8, &[u8])> = Vec::new();
+    for segment in get_segments(slice).map_while(Result::ok) {
+        let data = segment.get_data(slice);
+        if segment.marker != APP2 || !data.starts_with(ICC_IDENTIFIER) {
+            continue;
+        }
+        match data.get(ICC_IDENTIFIER.len()..ICC_IDENTIFIER.len() + 2) {
+            Some(numbers) => {
+                chunks.push((numbers[0], numbers[1], &data[ICC_IDENTIFIER.len() + 2..]))
+            }
+            None => return Err(String::from("Truncated ICC profile chunk")),
+        }
+    }
+    if chunks.is_empty() {
+        return Ok(None);
+    }
+
+    chunks.sort_by_key(|(sequence_number, _, _)| *sequence_number);
+    let count = chunks[0].1;
+    let is_complete = chunks.len() == count as usize
+        && chunks
+            .iter()
+            .enumerate()
+            .all(|(i, (sequence_number, other_count, _))| {
+                *sequence_number as usize == i + 1 && *other_count == count
+            });
+    if !is_complete {
+        return Err(format!(
+            "The ICC profile is split in {} chunks but {} were found",
+            count,
+            chunks.len()
+        ));
+    }
+
+    Ok(Some(
+        chunks
+            .iter()
+            .flat_map(|(_, _, data)| data.to_vec())
+            .collect(),
+    ))
+}
+
+// The iCCP chunk holds the name of the profile, the compression method (always zlib) and the
+// compressed profile
+pub fn read_png_icc(slice: &[u8]) -> Result<Option<Vec<u8>>, String> {
+    let data = match png::get_chunks(slice)
+        .map_while(Result::ok)
+        .find(|chunk| &chunk.chunk_type == png::ICCP)
+    {
+        Some(chunk) => chunk.get_data(slice),
+        None => return Ok(None),
+    };
+
+    let compressed = match data.iter().position(|b| *b == 0) {
+        Some(name_end) if data.get(name_end + 1) == Some(&0) => &data[name_end + 2..],
+        _ => return Err(String::from("Invalid iCCP chunk")),
+    };
+    png::inflate(compressed).map(Some)
+}
+
+fn get_signature(data: &[u8], offset: usize) -> String {
+    String::from_utf8_lossy(&data[offset..offset + 4])
+        .trim_end_matches(['\0', ' '])
+        .to_string()
+}
+
+fn get_u32(data: &[u8], offset: usize) -> Option<usize> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()) as usize)
+}
+
+pub struct ICCProfile {
+    data: Vec<u8>,
+    description: Option<String>,
+}
+
+impl ICCProfile {
+    pub fn from(data: Vec<u8>) -> Result<Self, String> {
+        if data.len() < HEADER_SIZE + 4 || &data[36..40] != PROFILE_SIGNATURE {
+            return Err(String::from("Invalid ICC profile header"));
+        }
+
+        let description = get_description(&data);
+        Ok(Self { data, description })
+    }
+
+    pub fn get_data(&self) -> &[u8] {
+        self.data.as_ref()
+    }
+
+    pub fn get_description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn is_display_p3(&self) -> bool {
+        self.description
+            .as_ref()
+            .is_some_and(|description| description.contains("Display P3"))
+    }
+
+    pub fn get_version(&self) -> String {
+        format!(
+            "{}.{}.{}",
+            self.data[8],
+            self.data[9] >> 4,
+            self.data[9] & 0x0F
+        )
+    }
+
+    pub fn get_class(&self) -> String {
+        match &self.data[12..16] {
+            b"scnr" => String::from("Input Device Profile"),
+            b"mntr" => String::from("Display Device Profile"),
+            b"prtr" => String::from("Output Device Profile"),
+            b"link" => String::from("DeviceLink Profile"),
+            b"spac" => String::from("ColorSpace Conversion Profile"),
+            b"abst" => String::from("Abstract Profile"),
+            b"nmcl" => String::from("NamedColor Profile"),
+            _ => get_signature(&self.data, 12),
+        }
+    }
+
+    pub fn get_color_space(&self) -> String {
+        get_signature(&self.data, 16)
+    }
+
+    pub fn get_connection_space(&self) -> String {
+        get_signature(&self.data, 20)
+    }
+
+    pub fn get_rendering_intent(&self) -> String {
+        match get_u32(&self.data, 64).unwrap_or(0) & 0xFFFF {
+            0 => String::from("Perceptual"),
+            1 => String::from("Media-Relative Colorimetric"),
+            2 => String::from("Saturation"),
+            3 => String::from("ICC-Absolute Colorimetric"),
+            intent => format!("Unknown ({})", intent),
+        }
+    }
+
+    fn get_date(&self) -> String {
+        let values: Vec<u16> = self.data[24..36]
+            .chunks(2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+            .collect();
+        format!(
+            "{:04}:{:02}:{:02} {:02}:{:02}:{:02}",
+            values[0], values[1], values[2], values[3], values[4], values[5]
+        )
+    }
+
+    pub fn get_all_tags(&self) -> Vec<(String, String)> {
+        let mut tags = vec![
+            (String::from("ProfileCMMType"), get_signature(&self.data, 4)),
+            (String::from("ProfileVersion"), self.get_version()),
+            (String::from("ProfileClass"), self.get_class()),
+            (String::from("ColorSpaceData"), self.get_color_space()),
+            (
+                String::from("ProfileConnectionSpace"),
+                self.get_connection_space(),
+            ),
+            (String::from("ProfileDateTime"), self.get_date()),
+            (String::from("RenderingIntent"), self.get_rendering_intent()),
+            (
+                String::from("ProfileCreator"),
+                get_signature(&self.data, 80),
+            ),
+        ];
+        if let Some(description) = &self.description {
+            tags.push((String::from("ProfileDescription"), description.clone()));
+        }
+        tags.push((
+            String::from("ProfileSize"),
+            format!("{} bytes", self.data.len()),
+        ));
+
+        tags
+    }
+}
+
+// Version 2 profiles use the textDescriptionType for the 'desc' tag, and version 4 profiles the
+// multiLocalizedUnicodeType, from which the English text is preferred
+fn get_description(data: &[u8]) -> Option<String> {
+    // Bounded by the size of the data, so that a corrupted count can't make us loop for long
+    let tag_count = get_u32(data, HEADER_SIZE)?.min(data.len() / 12);
+    let (offset, size) = (0..tag_count).find_map(|i| {
+        let entry = HEADER_SIZE + 4 + i * 12;
+        match data.get(entry..entry + 4)? {
+            b"desc" => Some((get_u32(data, entry + 4)?, get_u32(data, entry + 8)?)),
+            _ => None,
+        }
+    })?;
+    let tag = data.get(offset..offset + size)?;
+
+    match tag.get(0..4)? {
+        b"desc" => {
+            let length = get_u32(tag, 8)?;
+            let text = tag.get(12..12 + length)?;
+            Some(
+                String::from_utf8_lossy(text)
+                    .trim_end_matches('\0')
+                    .to_string(),
+            )
+        }
+        b"mluc" => {
+            let record_count = get_u32(tag, 8)?.min(tag.len() / 12);
+            let record_size = get_u32(tag, 12)?;
+            let records: Vec<&[u8]> = (0..record_count)
+                .filter_map(|i| tag.get(16 + i * record_size..16 + i * record_size + 12))
+                .collect();
+            let record = records
+                .iter()
+                .find(|record| record.starts_with(b"en"))
+                .or(records.first())?;
+            let length = get_u32(record, 4)?;
+            let text_offset = get_u32(record, 8)?;
+            let text: Vec<u16> = tag
+                .get(text_offset..text_offset + length)?
+                .chunks_exact(2)
+                .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+                .collect();
+            Some(
+                String::from_utf16_lossy(&text)
+                    .trim_end_matches('\0')
+                    .to_string(),
+            )
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jpeg::{build_segment, EOI, SOI};
+
+    // A version 4 display profile whose 'desc' tag holds a French and an English record
+    fn build_profile() -> Vec<u8> {
+        let mut description = b"mluc\0\0\0\0".to_vec();
+        description.extend_from_slice(&2u32.to_be_bytes());
+        description.extend_from_slice(&12u32.to_be_bytes());
+        for (language, offset) in [(b"frFR", 40u32), (b"enUS", 48)] {
+            description.extend_from_slice(language);
+            description.extend_from_slice(&8u32.to_be_bytes());
+            description.extend_from_slice(&offset.to_be_bytes());
+        }
+        for text in ["Écra", "Disp"] {
+            description.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+        }
+
+        let mut data = vec![0; HEADER_SIZE];
+        data[4..8].copy_from_slice(b"appl");
+        data[8..10].copy_from_slice(&[4, 0x20]);
+        data[12..24].copy_from_slice(b"mntrRGB XYZ ");
+        data[24..36].copy_from_slice(&[0x07, 0xE8, 0, 1, 0, 2, 0, 3, 0, 4, 0, 5]);
+        data[36..40].copy_from_slice(PROFILE_SIGNATURE);
+        data[67] = 1;
+        data.extend_from_slice(&1u32.to_be_bytes());
+        data.extend_from_slice(b"desc");
+        data.extend_from_slice(&(HEADER_SIZE as u32 + 16).to_be_bytes());
+        data.extend_from_slice(&(description.len() as u32).to_be_bytes());
+        data.extend_from_slice(&description);
+        let size = data.len() as u32;
+        data[0..4].copy_from_slice(&size.to_be_bytes());
+        data
+    }
+
+    fn build_jpeg(profile: &[u8], chunk_size: usize, order: &[usize]) -> Vec<u8> {
+        let chunks: Vec<&[u8]> = profile.chunks(chunk_size).collect();
+        let mut jpeg = vec![0xFF, SOI];
+        for i in order {
+            let mut data = ICC_IDENTIFIER.to_vec();
+            data.extend_from_slice(&[*i as u8 + 1, chunks.len() as u8]);
+            data.extend_from_slice(chunks[*i]);
+            jpeg.extend(build_segment(APP2, &data).unwrap());
+        }
+        jpeg.extend_from_slice(&[0xFF, EOI]);
+        jpeg
+    }
+
+    #[test]
+    fn profile_header_and_description() {
+        let profile = ICCProfile::from(build_profile()).unwrap();
+        assert_eq!(profile.get_description(), Some("Disp"));
+        assert!(!profile.is_display_p3());
+        assert_eq!(profile.get_version(), "4.2.0");
+        assert_eq!(profile.get_class(), "Display Device Profile");
+        assert_eq!(profile.get_color_space(), "RGB");
+        assert_eq!(profile.get_connection_space(), "XYZ");
+        assert_eq!(
+            profile.get_rendering_intent(),
+            "Media-Relative Colorimetric"
+        );
+        assert!(profile.get_all_tags().contains(&(
+            String::from("ProfileDateTime"),
+            String::from("2024:01:02 03:04:05")
+        )));
+
+        assert!(ICCProfile::from(vec![0; HEADER_SIZE + 4]).is_err());
+    }
+
+    #[test]
+    fn jpeg_chunks() {
+        let profile = build_profile();
+        assert_eq!(
+            read_jpeg_icc(&build_jpeg(&profile, 70, &[0, 1, 2])),
+            Ok(Some(profile.clone()))
+        );
+        // The chunks are sorted by their sequence number
+        assert_eq!(
+            read_jpeg_icc(&build_jpeg(&profile, 70, &[2, 0, 1])),
+            Ok(Some(profile.clone()))
+        );
+        assert!(read_jpeg_icc(&build_jpeg(&profile, 70, &[0, 2])).is_err());
+        assert_eq!(read_jpeg_icc(&[0xFF, SOI, 0xFF, EOI]), Ok(None));
+    }
+}
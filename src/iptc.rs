@@ -0,0 +1,482 @@
+// See the IPTC-NAA Information Interchange Model, version 4.2
+const TAG_MARKER: u8 = 0x1C;
+// ISO 2022 escape sequence announcing UTF-8, as the value of 1:90
+pub const UTF8_ESCAPE_SEQUENCE: &[u8] = b"\x1B%G";
+// Version 4 of the application record
+const APPLICATION_RECORD_VERSION: [u8; 2] = [0x00, 0x04];
+
+const ENVELOPE_RECORD: u8 = 1;
+const APPLICATION_RECORD: u8 = 2;
+const CODED_CHARACTER_SET: u8 = 90;
+const RECORD_VERSION: u8 = 0;
+
+// Binary datasets hold numbers or escape sequences rather than text
+struct DatasetInfo {
+    record: u8,
+    number: u8,
+    name: &'static str,
+    max_length: usize, // In bytes
+    is_repeatable: bool,
+    is_binary: bool,
+}
+
+const fn dataset(
+    record: u8,
+    number: u8,
+    name: &'static str,
+    max_length: usize,
+    is_repeatable: bool,
+    is_binary: bool,
+) -> DatasetInfo {
+    DatasetInfo {
+        record,
+        number,
+        name,
+        max_length,
+        is_repeatable,
+        is_binary,
+    }
+}
+
+// The names are the ones used by exiftool
+const DATASETS: [DatasetInfo; 64] = [
+    dataset(1, 0, "EnvelopeRecordVersion", 2, false, true),
+    dataset(1, 5, "Destination", 1024, true, false),
+    dataset(1, 20, "FileFormat", 2, false, true),
+    dataset(1, 22, "FileVersion", 2, false, true),
+    dataset(1, 30, "ServiceIdentifier", 10, false, false),
+    dataset(1, 40, "EnvelopeNumber", 8, false, false),
+    dataset(1, 50, "ProductID", 32, true, false),
+    dataset(1, 60, "EnvelopePriority", 1, false, false),
+    dataset(1, 70, "DateSent", 8, false, false),
+    dataset(1, 80, "TimeSent", 11, false, false),
+    dataset(1, 90, "CodedCharacterSet", 32, false, true),
+    dataset(1, 100, "UniqueObjectName", 80, false, false),
+    dataset(1, 120, "ARMIdentifier", 2, false, true),
+    dataset(1, 122, "ARMVersion", 2, false, true),
+    dataset(2, 0, "ApplicationRecordVersion", 2, false, true),
+    dataset(2, 3, "ObjectTypeReference", 67, false, false),
+    dataset(2, 4, "ObjectAttributeReference", 68, true, false),
+    dataset(2, 5, "ObjectName", 64, false, false),
+    dataset(2, 7, "EditStatus", 64, false, false),
+    dataset(2, 8, "EditorialUpdate", 2, false, false),
+    dataset(2, 10, "Urgency", 1, false, false),
+    dataset(2, 12, "SubjectReference", 236, true, false),
+    dataset(2, 15, "Category", 3, false, false),
+    dataset(2, 20, "SupplementalCategories", 32, true, false),
+    dataset(2, 22, "FixtureIdentifier", 32, false, false),
+    dataset(2, 25, "Keywords", 64, true, false),
+    dataset(2, 26, "ContentLocationCode", 3, true, false),
+    dataset(2, 27, "ContentLocationName", 64, true, false),
+    dataset(2, 30, "ReleaseDate", 8, false, false),
+    dataset(2, 35, "ReleaseTime", 11, false, false),
+    dataset(2, 37, "ExpirationDate", 8, false, false),
+    dataset(2, 38, "ExpirationTime", 11, false, false),
+    dataset(2, 40, "SpecialInstructions", 256, false, false),
+    dataset(2, 42, "ActionAdvised", 2, false, false),
+    dataset(2, 45, "ReferenceService", 10, true, false),
+    dataset(2, 47, "ReferenceDate", 8, true, false),
+    dataset(2, 50, "ReferenceNumber", 8, true, false),
+    dataset(2, 55, "DateCreated", 8, false, false),
+    dataset(2, 60, "TimeCreated", 11, false, false),
+    dataset(2, 62, "DigitalCreationDate", 8, false, false),
+    dataset(2, 63, "DigitalCreationTime", 11, false, false),
+    dataset(2, 65, "OriginatingProgram", 32, false, false),
+    dataset(2, 70, "ProgramVersion", 10, false, false),
+    dataset(2, 75, "ObjectCycle", 1, false, false),
+    dataset(2, 80, "By-line", 32, true, false),
+    dataset(2, 85, "By-lineTitle", 32, true, false),
+    dataset(2, 90, "City", 32, false, false),
+    dataset(2, 92, "Sub-location", 32, false, false),
+    dataset(2, 95, "Province-State", 32, false, false),
+    dataset(2, 100, "Country-PrimaryLocationCode", 3, false, false),
+    dataset(2, 101, "Country-PrimaryLocationName", 64, false, false),
+    dataset(2, 103, "OriginalTransmissionReference", 32, false, false),
+    dataset(2, 105, "Headline", 256, false, false),
+    dataset(2, 110, "Credit", 32, false, false),
+    dataset(2, 115, "Source", 32, false, false),
+    dataset(2, 116, "CopyrightNotice", 128, false, false),
+    dataset(2, 118, "Contact", 128, true, false),
+    dataset(2, 120, "Caption-Abstract", 2000, false, false),
+    dataset(2, 122, "Writer-Editor", 32, true, false),
+    dataset(2, 130, "ImageType", 2, false, false),
+    dataset(2, 131, "ImageOrientation", 1, false, false),
+    dataset(2, 135, "LanguageIdentifier", 3, false, false),
+    dataset(2, 184, "JobID", 64, false, false),
+    dataset(2, 221, "Prefs", 64, false, false),
+];
+
+// Names are compared without case nor dashes, so that "byline" finds By-line
+fn get_dataset_info(name: &str) -> Option<&'static DatasetInfo> {
+    let normalize = |name: &str| name.replace('-', "").to_lowercase();
+    DATASETS
+        .iter()
+        .find(|info| normalize(info.name) == normalize(name))
+}
+
+// Unknown datasets are not repeatable
+pub fn is_repeatable(name: &str) -> bool {
+    get_dataset_info(name).is_some_and(|info| info.is_repeatable)
+}
+
+fn get_dataset_info_for_number(record: u8, number: u8) -> Option<&'static DatasetInfo> {
+    DATASETS
+        .iter()
+        .find(|info| info.record == record && info.number == number)
+}
+
+#[derive(Clone)]
+pub struct IIMDataset {
+    pub record: u8,
+    pub number: u8,
+    pub data: Vec<u8>,
+}
+
+impl IIMDataset {
+    pub fn get_name(&self) -> String {
+        match get_dataset_info_for_number(self.record, self.number) {
+            Some(info) => info.name.to_string(),
+            None => format!("IPTC{}:{}", self.record, self.number),
+        }
+    }
+
+    fn is_binary(&self) -> bool {
+        get_dataset_info_for_number(self.record, self.number).is_some_and(|info| info.is_binary)
+    }
+
+    fn write(&self, data: &mut Vec<u8>) {
+        data.extend_from_slice(&[TAG_MARKER, self.record, self.number]);
+        if self.data.len() < 0x8000 {
+            data.extend_from_slice(&(self.data.len() as u16).to_be_bytes());
+        } else {
+            // Extended dataset, the length is followed by the size of the data on 4 bytes
+            data.extend_from_slice(&0x8004u16.to_be_bytes());
+            data.extend_from_slice(&(self.data.len() as u32).to_be_bytes());
+        }
+        data.extend_from_slice(&self.data);
+    }
+}
+
+// The IIM datasets stored in the IPTC resource of the Photoshop image resources
+#[derive(Clone)]
+pub struct IPTCData {
+    datasets: Vec<IIMDataset>,
+}
+
+impl IPTCData {
+    pub fn from(data: &[u8]) -> Result<Self, String> {
+        let mut datasets = Vec::new();
+        let mut pos = 0;
+
+        while pos < data.len() {
+            if data[pos] != TAG_MARKER {
+                // The resource is often padded with zeros
+                if data[pos..].iter().all(|b| *b == 0) {
+                    break;
+                }
+                return Err(format!("Invalid IIM tag marker at offset {}", pos));
+            }
+
+            let header = match data.get(pos..pos + 5) {
+                Some(header) => header,
+                None => return Err(format!("Truncated IIM dataset at offset {}", pos)),
+            };
+            let mut length = u16::from_be_bytes(header[3..5].try_into().unwrap()) as usize;
+            let mut data_offset = pos + 5;
+            // Extended datasets, the lower bits give the size of the length field
+            if length & 0x8000 != 0 {
+                let size = length & 0x7FFF;
+                // A length over 4 bytes could overflow, and wouldn't fit in a resource anyway
+                let bytes = match data.get(data_offset..data_offset + size) {
+                    Some(bytes) if size <= 4 => bytes,
+                    _ => return Err(format!("Invalid extended IIM dataset at offset {}", pos)),
+                };
+                length = bytes
+                    .iter()
+                    .fold(0, |length, b| (length << 8) | *b as usize);
+                data_offset += size;
+            }
+            let end = match data_offset.checked_add(length) {
+                Some(end) if end <= data.len() => end,
+                _ => {
+                    return Err(format!(
+                        "The IIM dataset at offset {} goes past the end of the data",
+                        pos
+                    ))
+                }
+            };
+
+            datasets.push(IIMDataset {
+                record: header[1],
+                number: header[2],
+                data: data[data_offset..end].to_vec(),
+            });
+            pos = end;
+        }
+
+        Ok(Self { datasets })
+    }
+
+    pub fn new() -> Self {
+        Self {
+            datasets: Vec::new(),
+        }
+    }
+
+    // Record versions and character sets alone don't hold any information
+    pub fn is_empty(&self) -> bool {
+        self.datasets.iter().all(|dataset| dataset.is_binary())
+    }
+
+    // Text is in ISO 8859-1 unless the coded character set (1:90) says it's UTF-8
+    pub fn is_utf8(&self) -> bool {
+        self.datasets.iter().any(|dataset| {
+            dataset.record == ENVELOPE_RECORD
+                && dataset.number == CODED_CHARACTER_SET
+                && dataset.data == UTF8_ESCAPE_SEQUENCE
+        })
+    }
+
+    fn decode(&self, data: &[u8]) -> String {
+        if self.is_utf8() {
+            String::from_utf8_lossy(data).to_string()
+        } else {
+            data.iter().map(|b| *b as char).collect()
+        }
+    }
+
+    pub fn get_value_as_string(&self, dataset: &IIMDataset) -> String {
+        if !dataset.is_binary() {
+            return self.decode(&dataset.data);
+        }
+
+        match dataset.data.as_slice() {
+            UTF8_ESCAPE_SEQUENCE if dataset.number == CODED_CHARACTER_SET => String::from("UTF-8"),
+            [high, low] => u16::from_be_bytes([*high, *low]).to_string(),
+            data => data
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect::<Vec<String>>()
+                .join(" "),
+        }
+    }
+
+    pub fn get_values(&self, name: &str) -> Result<Vec<String>, String> {
+        let info = get_dataset_info(name).ok_or(format!("Unknown IPTC dataset {}", name))?;
+        Ok(self
+            .datasets
+            .iter()
+            .filter(|dataset| dataset.record == info.record && dataset.number == info.number)
+            .map(|dataset| self.get_value_as_string(dataset))
+            .collect())
+    }
+
+    // Repeated datasets (such as the keywords) are joined on a single line
+    pub fn get_all_tags(&self) -> Vec<(String, String)> {
+        let mut tags: Vec<((u8, u8), String, Vec<String>)> = Vec::new();
+        for dataset in self.datasets.iter() {
+            let value = self.get_value_as_string(dataset);
+            match tags
+                .iter_mut()
+                .find(|(key, _, _)| *key == (dataset.record, dataset.number))
+            {
+                Some((_, _, values)) => values.push(value),
+                None => tags.push((
+                    (dataset.record, dataset.number),
+                    dataset.get_name(),
+                    vec![value],
+                )),
+            }
+        }
+
+        tags.into_iter()
+            .map(|(_, name, values)| (name, values.join(", ")))
+            .collect()
+    }
+
+    // Replaces the datasets of the given record and number, keeping the records in order
+    fn replace_datasets(&mut self, record: u8, number: u8, data: Vec<Vec<u8>>) {
+        let is_same = |dataset: &IIMDataset| dataset.record == record && dataset.number == number;
+        let insert_at = self.datasets.iter().position(is_same).unwrap_or_else(|| {
+            self.datasets
+                .iter()
+                .position(|dataset| (dataset.record, dataset.number) > (record, number))
+                .unwrap_or(self.datasets.len())
+        });
+        self.datasets.retain(|dataset| !is_same(dataset));
+
+        let insert_at = insert_at.min(self.datasets.len());
+        for (i, data) in data.into_iter().enumerate() {
+            self.datasets.insert(
+                insert_at + i,
+                IIMDataset {
+                    record,
+                    number,
+                    data,
+                },
+            );
+        }
+    }
+
+    // Re-encodes the text datasets from ISO 8859-1 to UTF-8 and records it in 1:90
+    fn convert_to_utf8(&mut self) {
+        if self.is_utf8() {
+            return;
+        }
+
+        for dataset in self.datasets.iter_mut() {
+            if !dataset.is_binary() {
+                dataset.data = dataset
+                    .data
+                    .iter()
+                    .map(|b| *b as char)
+                    .collect::<String>()
+                    .into_bytes();
+            }
+        }
+        self.replace_datasets(
+            ENVELOPE_RECORD,
+            CODED_CHARACTER_SET,
+            vec![UTF8_ESCAPE_SEQUENCE.to_vec()],
+        );
+    }
+
+    // Sets the values of a dataset, or removes it if there are none. Non-ASCII text switches the
+    // whole record to UTF-8.
+    pub fn set_values(&mut self, name: &str, values: &[String]) -> Result<(), String> {
+        let info = get_dataset_info(name).ok_or(format!("Unknown IPTC dataset {}", name))?;
+        if info.is_binary {
+            return Err(format!("The IPTC dataset {} can't be edited", info.name));
+        }
+        if values.len() > 1 && !info.is_repeatable {
+            return Err(format!("The IPTC dataset {} can't be repeated", info.name));
+        }
+        if values.iter().any(|value| value.len() > info.max_length) {
+            return Err(format!(
+                "The IPTC dataset {} is limited to {} bytes",
+                info.name, info.max_length
+            ));
+        }
+
+        if values.iter().any(|value| !value.is_ascii()) {
+            self.convert_to_utf8();
+        }
+        let data = values
+            .iter()
+            .map(|value| value.as_bytes().to_vec())
+            .collect();
+        self.replace_datasets(info.record, info.number, data);
+
+        // The record version is mandatory in any application record
+        let has_dataset = |number: Option<u8>| {
+            self.datasets.iter().any(|dataset| {
+                dataset.record == APPLICATION_RECORD
+                    && number.is_none_or(|number| dataset.number == number)
+            })
+        };
+        if has_dataset(None) && !has_dataset(Some(RECORD_VERSION)) {
+            self.replace_datasets(
+                APPLICATION_RECORD,
+                RECORD_VERSION,
+                vec![APPLICATION_RECORD_VERSION.to_vec()],
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for dataset in self.datasets.iter() {
+            dataset.write(&mut data);
+        }
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_dataset(record: u8, number: u8, data: &[u8]) -> Vec<u8> {
+        let mut dataset = vec![TAG_MARKER, record, number];
+        dataset.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        dataset.extend_from_slice(data);
+        dataset
+    }
+
+    #[test]
+    fn parse_and_build() {
+        let data = [
+            build_dataset(1, 90, UTF8_ESCAPE_SEQUENCE),
+            build_dataset(2, 0, &APPLICATION_RECORD_VERSION),
+            build_dataset(2, 25, b"news"),
+            build_dataset(2, 25, b"sport"),
+            build_dataset(2, 90, "Zürich".as_bytes()),
+        ]
+        .concat();
+        // The resource is padded with zeros
+        let iptc = IPTCData::from(&[data.clone(), vec![0; 3]].concat()).unwrap();
+        assert!(iptc.is_utf8());
+        assert_eq!(iptc.get_values("keywords").unwrap(), ["news", "sport"]);
+        assert_eq!(iptc.get_values("City").unwrap(), ["Zürich"]);
+        assert_eq!(
+            iptc.get_all_tags(),
+            [
+                (String::from("CodedCharacterSet"), String::from("UTF-8")),
+                (String::from("ApplicationRecordVersion"), String::from("4")),
+                (String::from("Keywords"), String::from("news, sport")),
+                (String::from("City"), String::from("Zürich")),
+            ]
+        );
+        assert_eq!(iptc.to_bytes(), data);
+    }
+
+    #[test]
+    fn latin1_and_utf8() {
+        let mut iptc = IPTCData::from(&build_dataset(2, 90, b"Z\xFCrich")).unwrap();
+        assert!(!iptc.is_utf8());
+        assert_eq!(iptc.get_values("City").unwrap(), ["Zürich"]);
+
+        // Non-ASCII text switches the record to UTF-8
+        iptc.set_values("By-line", &[String::from("Jérôme")])
+            .unwrap();
+        let iptc = IPTCData::from(&iptc.to_bytes()).unwrap();
+        assert!(iptc.is_utf8());
+        assert_eq!(iptc.get_values("City").unwrap(), ["Zürich"]);
+        assert_eq!(iptc.get_values("byline").unwrap(), ["Jérôme"]);
+        assert_eq!(iptc.get_values("ApplicationRecordVersion").unwrap(), ["4"]);
+
+        let mut iptc = IPTCData::new();
+        assert!(iptc
+            .set_values("City", &[String::from("a"), String::from("b")])
+            .is_err());
+        assert!(iptc.set_values("Urgency", &[String::from("12")]).is_err());
+        assert!(iptc.set_values("Unknown", &[String::from("a")]).is_err());
+    }
+
+    #[test]
+    fn extended_datasets() {
+        let mut iptc = IPTCData::new();
+        iptc.datasets.push(IIMDataset {
+            record: 2,
+            number: 202,
+            data: vec![0x42; 0x8000],
+        });
+        let data = iptc.to_bytes();
+        assert_eq!(data[3..9], [0x80, 0x04, 0x00, 0x00, 0x80, 0x00]);
+        let iptc = IPTCData::from(&data).unwrap();
+        assert_eq!(iptc.datasets[0].data.len(), 0x8000);
+
+        // The length field is cut short
+        assert!(IPTCData::from(&[TAG_MARKER, 2, 202, 0x80, 0x04, 0x00, 0x01]).is_err());
+        // The length field is too long
+        let mut data = vec![TAG_MARKER, 2, 202, 0x80, 0x09];
+        data.extend_from_slice(&[0xFF; 9]);
+        assert!(IPTCData::from(&data).is_err());
+        // The length goes past the end of the data
+        let data = [TAG_MARKER, 2, 202, 0x80, 0x04, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+        let e = IPTCData::from(&data).err().unwrap();
+        assert!(e.contains("goes past the end of the data"));
+    }
+}
@@ -0,0 +1,309 @@
+use crate::jpeg::{build_segment, get_segments, replace_segments, JPEGSegment, APP0, SOS};
+
+// See the JFIF spec (ITU-T T.871), sections 10.1 and 10.2
+pub const JFIF_IDENTIFIER: &[u8] = b"JFIF\0";
+pub const JFXX_IDENTIFIER: &[u8] = b"JFXX\0";
+
+// Identifier, version, density unit, densities and thumbnail size
+const JFIF_HEADER_SIZE: usize = 14;
+
+// Extension codes of the JFXX segment
+const JPEG_THUMBNAIL: u8 = 0x10;
+const PALETTE_THUMBNAIL: u8 = 0x11;
+const RGB_THUMBNAIL: u8 = 0x13;
+const PALETTE_SIZE: usize = 768;
+
+#[derive(Clone)]
+pub struct JFIFSegment {
+    pub version: (u8, u8),
+    pub density_unit: u8, // 0 when the densities only give the aspect ratio, 1 for inches, 2 for cm
+    pub x_density: u16,
+    pub y_density: u16,
+    pub thumbnail: Option<(u8, u8, Vec<u8>)>, // Width, height and RGB pixels
+}
+
+impl JFIFSegment {
+    pub fn from(data: &[u8]) -> Result<Self, String> {
+        if !data.starts_with(JFIF_IDENTIFIER) || data.len() < JFIF_HEADER_SIZE {
+            return Err(String::from("Invalid JFIF segment"));
+        }
+
+        let (width, height) = (data[12], data[13]);
+        let thumbnail_size = 3 * width as usize * height as usize;
+        let thumbnail = match data.get(JFIF_HEADER_SIZE..JFIF_HEADER_SIZE + thumbnail_size) {
+            _ if thumbnail_size == 0 => None,
+            Some(pixels) => Some((width, height, pixels.to_vec())),
+            None => return Err(String::from("The JFIF thumbnail is truncated")),
+        };
+
+        Ok(Self {
+            version: (data[5], data[6]),
+            density_unit: data[7],
+            x_density: u16::from_be_bytes([data[8], data[9]]),
+            y_density: u16::from_be_bytes([data[10], data[11]]),
+            thumbnail,
+        })
+    }
+
+    // Version 1.02, 72 dpi, without thumbnail
+    pub fn new() -> Self {
+        Self {
+            version: (1, 2),
+            density_unit: 1,
+            x_density: 72,
+            y_density: 72,
+            thumbnail: None,
+        }
+    }
+
+    pub fn get_data(&self) -> Result<Vec<u8>, String> {
+        if self.version.0 != 1 {
+            return Err(format!(
+                "Unsupported JFIF version {}.{:02}",
+                self.version.0, self.version.1
+            ));
+        }
+        if self.density_unit > 2 {
+            return Err(format!("Invalid JFIF density unit {}", self.density_unit));
+        }
+        if self.x_density == 0 || self.y_density == 0 {
+            return Err(String::from("The JFIF densities can't be 0"));
+        }
+
+        let mut data = JFIF_IDENTIFIER.to_vec();
+        data.extend_from_slice(&[self.version.0, self.version.1, self.density_unit]);
+        data.extend_from_slice(&self.x_density.to_be_bytes());
+        data.extend_from_slice(&self.y_density.to_be_bytes());
+        match &self.thumbnail {
+            Some((width, height, pixels)) => {
+                if pixels.len() != 3 * *width as usize * *height as usize {
+                    return Err(String::from(
+                        "The JFIF thumbnail doesn't match its dimensions",
+                    ));
+                }
+                data.extend_from_slice(&[*width, *height]);
+                data.extend_from_slice(pixels);
+            }
+            None => data.extend_from_slice(&[0, 0]),
+        }
+
+        Ok(data)
+    }
+
+    // Density in dots per inch, if the unit is a physical one
+    pub fn get_dpi(&self, density: u16) -> Option<f64> {
+        match self.density_unit {
+            1 => Some(density as f64),
+            2 => Some(density as f64 * 2.54),
+            _ => None,
+        }
+    }
+
+    pub fn get_all_tags(&self) -> Vec<(String, String)> {
+        let mut tags = vec![
+            (
+                String::from("JFIFVersion"),
+                format!("{}.{:02}", self.version.0, self.version.1),
+            ),
+            (
+                String::from("ResolutionUnit"),
+                get_unit_as_string(self.density_unit),
+            ),
+            (String::from("XResolution"), self.x_density.to_string()),
+            (String::from("YResolution"), self.y_density.to_string()),
+        ];
+        if let Some((width, height, _)) = &self.thumbnail {
+            tags.push((
+                String::from("ThumbnailSize"),
+                format!("{}x{}", width, height),
+            ));
+        }
+
+        tags
+    }
+
+    // Sets a value from text in the form get_all_tags gives it, the unit can also be given as its
+    // code (0, 1 or 2)
+    pub fn set_value(&mut self, name: &str, text: &str) -> Result<(), String> {
+        let invalid = || format!("Invalid value for {}: {}", name, text);
+        let parse_u8 = |text: &str| text.trim().parse::<u8>().map_err(|_| invalid());
+        let parse_u16 = |text: &str| text.trim().parse::<u16>().map_err(|_| invalid());
+        let mut jfif = self.clone();
+        match name.to_lowercase().as_str() {
+            "jfifversion" => {
+                let (major, minor) = text.split_once('.').ok_or_else(invalid)?;
+                jfif.version = (parse_u8(major)?, parse_u8(minor)?);
+            }
+            "resolutionunit" => {
+                let text = text.trim().to_lowercase();
+                jfif.density_unit = match (0..=2).find(|unit| {
+                    text == unit.to_string()
+                        || get_unit_as_string(*unit).to_lowercase().starts_with(&text)
+                }) {
+                    Some(unit) if !text.is_empty() => unit,
+                    _ => return Err(invalid()),
+                };
+            }
+            "xresolution" => jfif.x_density = parse_u16(text)?,
+            "yresolution" => jfif.y_density = parse_u16(text)?,
+            _ => return Err(format!("The JFIF value {} can't be edited", name)),
+        }
+
+        // The segment must still be valid
+        jfif.get_data()?;
+        *self = jfif;
+        Ok(())
+    }
+}
+
+fn get_unit_as_string(density_unit: u8) -> String {
+    match density_unit {
+        0 => String::from("None (aspect ratio)"),
+        1 => String::from("inches"),
+        2 => String::from("centimeters"),
+        _ => String::from("reserved"),
+    }
+}
+
+// The JFIF extension segment, which only holds a thumbnail
+pub struct JFXXThumbnail {
+    pub extension_code: u8,
+    pub data: Vec<u8>,
+}
+
+impl JFXXThumbnail {
+    pub fn from(data: &[u8]) -> Option<Self> {
+        Some(Self {
+            extension_code: *data.get(JFXX_IDENTIFIER.len())?,
+            data: data.get(JFXX_IDENTIFIER.len() + 1..)?.to_vec(),
+        })
+    }
+
+    pub fn get_jpeg(&self) -> Option<&[u8]> {
+        match self.extension_code {
+            JPEG_THUMBNAIL => Some(self.data.as_ref()),
+            _ => None,
+        }
+    }
+
+    pub fn get_as_string(&self) -> String {
+        let size = match self.data.get(0..2) {
+            Some(size) => format!("{}x{}", size[0], size[1]),
+            None => String::from("?"),
+        };
+        match self.extension_code {
+            JPEG_THUMBNAIL => format!("JPEG ({} bytes)", self.data.len()),
+            PALETTE_THUMBNAIL => format!(
+                "{} with a palette ({} bytes)",
+                size,
+                self.data.len().saturating_sub(2 + PALETTE_SIZE)
+            ),
+            RGB_THUMBNAIL => format!("{} RGB", size),
+            code => format!("Unknown extension 0x{:02X}", code),
+        }
+    }
+}
+
+pub fn is_jfif_segment(segment: &JPEGSegment, slice: &[u8]) -> bool {
+    segment.marker == APP0 && segment.get_data(slice).starts_with(JFIF_IDENTIFIER)
+}
+
+// Reads the JFIF segment and the JFXX extension, which come right after SOI
+pub fn read_jpeg_jfif(
+    slice: &[u8],
+) -> Result<(Option<JFIFSegment>, Option<JFXXThumbnail>), String> {
+    let mut jfif = None;
+    let mut jfxx = None;
+    for segment in get_segments(slice).map_while(Result::ok) {
+        if segment.marker == SOS {
+            break;
+        }
+        if segment.marker != APP0 {
+            continue;
+        }
+
+        let data = segment.get_data(slice);
+        if data.starts_with(JFIF_IDENTIFIER) && jfif.is_none() {
+            jfif = Some(JFIFSegment::from(data)?);
+        } else if data.starts_with(JFXX_IDENTIFIER) && jfxx.is_none() {
+            jfxx = JFXXThumbnail::from(data);
+        }
+    }
+
+    Ok((jfif, jfxx))
+}
+
+// The JFIF segment must directly follow SOI, which is where it goes if there was none
+pub fn write_jpeg_jfif(slice: &[u8], jfif: &JFIFSegment) -> Result<Vec<u8>, String> {
+    let segment = build_segment(APP0, &jfif.get_data()?)?;
+    let has_jfif = get_segments(slice)
+        .map_while(Result::ok)
+        .any(|segment| is_jfif_segment(&segment, slice));
+    if has_jfif {
+        return replace_segments(slice, is_jfif_segment, &[segment]);
+    }
+
+    let mut contents = Vec::with_capacity(slice.len() + segment.len());
+    contents.extend_from_slice(&slice[0..2]);
+    contents.extend_from_slice(&segment);
+    contents.extend_from_slice(&slice[2..]);
+    Ok(contents)
+}
+
+pub struct DensityConflict {
+    pub tag: String,
+    pub exif_value: String,
+    pub jfif_value: String,
+}
+
+fn get_dpi_as_string(dpi: f64) -> String {
+    if dpi.fract() == 0.0 {
+        format!("{} dpi", dpi)
+    } else {
+        format!("{:.2} dpi", dpi)
+    }
+}
+
+// Compares the JFIF densities with the Exif resolution (whose unit defaults to inches), once both
+// are converted to dots per inch. Densities that only give the aspect ratio can't conflict.
+pub fn get_density_conflicts(
+    jfif: &JFIFSegment,
+    exif_unit: Option<u32>,
+    exif_resolutions: [Option<(u32, u32)>; 2],
+) -> Vec<DensityConflict> {
+    let mut conflicts = Vec::new();
+    if jfif.density_unit == 0 || exif_resolutions.iter().all(|res| res.is_none()) {
+        return conflicts;
+    }
+
+    let exif_unit = exif_unit.unwrap_or(2);
+    let jfif_unit = jfif.density_unit as u32 + 1;
+    if exif_unit != jfif_unit {
+        conflicts.push(DensityConflict {
+            tag: String::from("ResolutionUnit"),
+            exif_value: get_unit_as_string((exif_unit as u8).saturating_sub(1)),
+            jfif_value: get_unit_as_string(jfif.density_unit),
+        });
+    }
+
+    let tags = ["XResolution", "YResolution"];
+    let densities = [jfif.x_density, jfif.y_density];
+    for ((tag, resolution), density) in tags.iter().zip(exif_resolutions).zip(densities) {
+        let exif_dpi = match resolution {
+            Some((num, den)) if den != 0 => {
+                num as f64 / den as f64 * if exif_unit == 3 { 2.54 } else { 1.0 }
+            }
+            _ => continue,
+        };
+        let jfif_dpi = jfif.get_dpi(density).unwrap_or_default();
+        if (exif_dpi - jfif_dpi).abs() > 0.5 {
+            conflicts.push(DensityConflict {
+                tag: tag.to_string(),
+                exif_value: get_dpi_as_string(exif_dpi),
+                jfif_value: get_dpi_as_string(jfif_dpi),
+            });
+        }
+    }
+
+    conflicts
+}
@@ -0,0 +1,398 @@
+// See https://www.w3.org/Graphics/JPEG/itu-t81.pdf (Table B.1) and the Exif spec (4.5.4)
+pub const SOI: u8 = 0xD8;
+pub const EOI: u8 = 0xD9;
+pub const SOS: u8 = 0xDA;
+pub const TEM: u8 = 0x01;
+pub const APP0: u8 = 0xE0;
+pub const APP1: u8 = 0xE1;
+pub const APP2: u8 = 0xE2;
+pub const APP13: u8 = 0xED;
+pub const APP15: u8 = 0xEF;
+pub const COM: u8 = 0xFE;
+
+pub const EXIF_IDENTIFIER_CODE: [u8; 6] = [0x45, 0x78, 0x69, 0x66, 0x00, 0x00]; // Exif\0\0
+
+#[derive(Clone)]
+pub struct JPEGSegment {
+    pub marker: u8,
+    pub offset: usize, // Offset of the 0xFF byte of the marker
+    pub length: usize, // Value of the length field, which includes itself (0 if there is none)
+}
+
+impl JPEGSegment {
+    // Markers that are not followed by a length field
+    fn is_standalone(marker: u8) -> bool {
+        marker == SOI || marker == EOI || marker == TEM || (0xD0..=0xD7).contains(&marker)
+    }
+
+    pub fn is_app(&self) -> bool {
+        (APP0..=APP15).contains(&self.marker)
+    }
+
+    // Offset of the data that follows the marker and the length field
+    pub fn get_data_offset(&self) -> usize {
+        self.offset + 2 + if self.length == 0 { 0 } else { 2 }
+    }
+
+    // Offset of the first byte after the segment
+    pub fn get_end(&self) -> usize {
+        self.offset + 2 + self.length
+    }
+
+    pub fn get_data<'a>(&self, slice: &'a [u8]) -> &'a [u8] {
+        &slice[self.get_data_offset()..self.get_end()]
+    }
+
+    pub fn is_exif(&self, slice: &[u8]) -> bool {
+        self.get_exif_identifier_length(slice).is_some()
+    }
+
+    // Some writers don't pad the identifier code with a second 0, the TIFF header then follows
+    // the first one
+    pub fn get_exif_identifier_length(&self, slice: &[u8]) -> Option<usize> {
+        let data = self.get_data(slice);
+        match self.marker == APP1 {
+            true if data.starts_with(&EXIF_IDENTIFIER_CODE) => Some(EXIF_IDENTIFIER_CODE.len()),
+            true if data.starts_with(&EXIF_IDENTIFIER_CODE[0..5])
+                && (data[5..].starts_with(b"II") || data[5..].starts_with(b"MM")) =>
+            {
+                Some(5)
+            }
+            _ => None,
+        }
+    }
+
+    // Offset of the TIFF header of an APP1 Exif segment
+    pub fn get_tiff_start(&self, slice: &[u8]) -> Option<usize> {
+        self.get_exif_identifier_length(slice)
+            .map(|length| self.get_data_offset() + length)
+    }
+
+    pub fn get_marker_name(&self) -> String {
+        match self.marker {
+            SOI => String::from("SOI"),
+            EOI => String::from("EOI"),
+            SOS => String::from("SOS"),
+            TEM => String::from("TEM"),
+            COM => String::from("COM"),
+            0xC4 => String::from("DHT"),
+            0xCC => String::from("DAC"),
+            0xDB => String::from("DQT"),
+            0xDD => String::from("DRI"),
+            0xC0..=0xCF => format!("SOF{}", self.marker - 0xC0),
+            0xD0..=0xD7 => format!("RST{}", self.marker - 0xD0),
+            APP0..=APP15 => format!("APP{}", self.marker - APP0),
+            _ => format!("0x{:02X}", self.marker),
+        }
+    }
+}
+
+// Walks the segments of a JPEG file using their length, until the start of the scan (SOS), after
+// which the entropy coded data begins
+pub struct JPEGSegments<'a> {
+    slice: &'a [u8],
+    pos: usize,
+    finished: bool,
+}
+
+impl<'a> Iterator for JPEGSegments<'a> {
+    type Item = Result<JPEGSegment, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        if self.pos == 0 {
+            self.finished = !self.slice.starts_with(&[0xFF, SOI]);
+            self.pos = 2;
+            return Some(if self.finished {
+                Err(String::from("The file doesn't start with a SOI marker"))
+            } else {
+                Ok(JPEGSegment {
+                    marker: SOI,
+                    offset: 0,
+                    length: 0,
+                })
+            });
+        }
+
+        if self.slice.get(self.pos) != Some(&0xFF) {
+            self.finished = true;
+            return Some(Err(format!("Expected a marker at offset {}", self.pos)));
+        }
+
+        // Markers may be preceded by any number of 0xFF fill bytes
+        let mut offset = self.pos;
+        while self.slice.get(offset + 1) == Some(&0xFF) {
+            offset += 1;
+        }
+        let marker = match self.slice.get(offset + 1) {
+            Some(marker) => *marker,
+            None => {
+                self.finished = true;
+                return Some(Err(String::from("Unexpected end of file")));
+            }
+        };
+
+        let length = if JPEGSegment::is_standalone(marker) {
+            0
+        } else {
+            match self.slice.get(offset + 2..offset + 4) {
+                Some(length) if u16::from_be_bytes(length.try_into().unwrap()) >= 2 => {
+                    u16::from_be_bytes(length.try_into().unwrap()) as usize
+                }
+                _ => {
+                    self.finished = true;
+                    return Some(Err(format!(
+                        "Invalid length for the segment at offset {}",
+                        offset
+                    )));
+                }
+            }
+        };
+
+        let segment = JPEGSegment {
+            marker,
+            offset,
+            length,
+        };
+        if segment.get_end() > self.slice.len() {
+            self.finished = true;
+            return Some(Err(format!(
+                "The {} segment at offset {} goes past the end of the file",
+                segment.get_marker_name(),
+                offset
+            )));
+        }
+
+        self.pos = segment.get_end();
+        self.finished = marker == SOS || marker == EOI;
+        Some(Ok(segment))
+    }
+}
+
+pub fn get_segments(slice: &[u8]) -> JPEGSegments<'_> {
+    JPEGSegments {
+        slice,
+        pos: 0,
+        finished: false,
+    }
+}
+
+// Returns the first Exif APP1 segment if there is one, along with warnings about the structure of
+// the file.
+// The Exif spec requires the APP1 segment to be placed right after the SOI marker (or after the
+// JFIF APP0 segment), some readers won't find it elsewhere.
+pub fn get_exif_segment(slice: &[u8]) -> Result<(Option<JPEGSegment>, Vec<String>), String> {
+    let mut warnings = Vec::new();
+    let mut exif_segments = Vec::new();
+    // First segment that is neither a marker nor an application segment (tables, frame, ...)
+    let mut first_non_app: Option<JPEGSegment> = None;
+
+    for segment in get_segments(slice) {
+        let segment = match segment {
+            Ok(segment) => segment,
+            Err(e) => {
+                if exif_segments.is_empty() {
+                    return Err(e);
+                }
+                warnings.push(e);
+                break;
+            }
+        };
+
+        if segment.is_exif(slice) {
+            if let Some(first_non_app) = &first_non_app {
+                warnings.push(format!(
+                    "The Exif segment at offset {} is placed after the {} segment at offset {}",
+                    segment.offset,
+                    first_non_app.get_marker_name(),
+                    first_non_app.offset,
+                ));
+            }
+            exif_segments.push(segment);
+        } else if first_non_app.is_none()
+            && segment.marker != SOI
+            && segment.marker != COM
+            && !segment.is_app()
+        {
+            first_non_app = Some(segment);
+        }
+    }
+
+    if exif_segments.len() > 1 {
+        warnings.push(format!(
+            "Found {} Exif segments (at offsets {}), only the first one is used",
+            exif_segments.len(),
+            exif_segments
+                .iter()
+                .map(|segment| segment.offset.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        ));
+    }
+
+    Ok((exif_segments.into_iter().next(), warnings))
+}
+
+// Maximum size of the data of a segment, the length field counts itself
+pub const MAX_SEGMENT_DATA_SIZE: usize = u16::MAX as usize - 2;
+
+pub fn build_segment(marker: u8, data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() > MAX_SEGMENT_DATA_SIZE {
+        return Err(format!(
+            "{} bytes of data don't fit in a single segment (the maximum is {})",
+            data.len(),
+            MAX_SEGMENT_DATA_SIZE
+        ));
+    }
+
+    let mut segment = vec![0xFF, marker];
+    segment.extend_from_slice(&((data.len() + 2) as u16).to_be_bytes());
+    segment.extend_from_slice(data);
+    Ok(segment)
+}
+
+// Removes the segments for which `is_replaced` returns true, and inserts `new_segments` where the
+// first of them was. If none of them exist, the new segments are placed after the SOI marker and
+// the JFIF and Exif segments that follow it.
+pub fn replace_segments<F: Fn(&JPEGSegment, &[u8]) -> bool>(
+    slice: &[u8],
+    is_replaced: F,
+    new_segments: &[Vec<u8>],
+) -> Result<Vec<u8>, String> {
+    let segments = get_segments(slice).collect::<Result<Vec<JPEGSegment>, String>>()?;
+
+    let replaced: Vec<&JPEGSegment> = segments
+        .iter()
+        .filter(|segment| is_replaced(segment, slice))
+        .collect();
+    let insert_at = match replaced.first() {
+        Some(segment) => segment.offset,
+        None => segments
+            .iter()
+            .take_while(|segment| {
+                segment.marker == SOI || segment.marker == APP0 || segment.is_exif(slice)
+            })
+            .last()
+            .map(|segment| segment.get_end())
+            .unwrap_or(2),
+    };
+
+    let mut contents = Vec::with_capacity(slice.len());
+    let mut pos = 0;
+    for segment in replaced.iter() {
+        if segment.offset == insert_at {
+            contents.extend_from_slice(&slice[pos..insert_at]);
+            new_segments
+                .iter()
+                .for_each(|new_segment| contents.extend_from_slice(new_segment));
+        } else {
+            contents.extend_from_slice(&slice[pos..segment.offset]);
+        }
+        pos = segment.get_end();
+    }
+    if replaced.is_empty() {
+        contents.extend_from_slice(&slice[0..insert_at]);
+        new_segments
+            .iter()
+            .for_each(|new_segment| contents.extend_from_slice(new_segment));
+        pos = insert_at;
+    }
+    contents.extend_from_slice(&slice[pos..]);
+
+    Ok(contents)
+}
+
+// The text of the COM segments, which has no defined encoding. UTF-8 is tried first, as that is
+// what current software writes, with ISO 8859-1 as a fallback.
+pub fn get_comments(slice: &[u8]) -> Vec<String> {
+    get_segments(slice)
+        .map_while(Result::ok)
+        .filter(|segment| segment.marker == COM)
+        .map(|segment| {
+            let data = segment.get_data(slice);
+            let text = match std::str::from_utf8(data) {
+                Ok(text) => text.to_string(),
+                Err(_) => data.iter().map(|b| *b as char).collect(),
+            };
+            text.trim_end_matches('\0').to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments_split() {
+        let jpeg = [
+            &[0xFF, SOI][..],
+            &[0xFF, APP0, 0x00, 0x04, 0x01, 0x02],
+            // Fill bytes before the marker
+            &[0xFF, 0xFF, 0xFF, APP1, 0x00, 0x02],
+            &[0xFF, SOS, 0x00, 0x03, 0x00],
+            // Entropy coded data, which isn't made of segments
+            &[0x12, 0xFF, 0x00, 0x34, 0xFF, EOI],
+        ]
+        .concat();
+        let segments = get_segments(&jpeg)
+            .collect::<Result<Vec<JPEGSegment>, String>>()
+            .unwrap();
+        assert_eq!(
+            segments
+                .iter()
+                .map(|segment| (segment.marker, segment.offset, segment.length))
+                .collect::<Vec<(u8, usize, usize)>>(),
+            [(SOI, 0, 0), (APP0, 2, 4), (APP1, 10, 2), (SOS, 14, 3)]
+        );
+        assert_eq!(segments[1].get_data(&jpeg), [0x01, 0x02]);
+        assert!(segments[2].get_data(&jpeg).is_empty());
+
+        let truncated = &jpeg[..7];
+        match get_segments(truncated).last() {
+            Some(Err(e)) => assert!(e.contains("goes past the end of the file")),
+            _ => panic!("The truncated segment wasn't reported"),
+        }
+        assert!(get_segments(&jpeg[1..]).next().unwrap().is_err());
+    }
+
+    #[test]
+    fn segments_replace() {
+        let com = |text: &[u8]| build_segment(COM, text).unwrap();
+        let app0 = build_segment(APP0, b"JFIF\0").unwrap();
+        let dqt = build_segment(0xDB, &[0; 4]).unwrap();
+        let jpeg = [
+            vec![0xFF, SOI],
+            app0.clone(),
+            com(b"one"),
+            dqt.clone(),
+            com(b"two"),
+            vec![0xFF, EOI],
+        ]
+        .concat();
+
+        // The new segments take the place of the first replaced one
+        let is_comment = |segment: &JPEGSegment, _: &[u8]| segment.marker == COM;
+        let replaced = replace_segments(&jpeg, is_comment, &[com(b"new")]).unwrap();
+        let expected = [
+            vec![0xFF, SOI],
+            app0.clone(),
+            com(b"new"),
+            dqt.clone(),
+            vec![0xFF, EOI],
+        ]
+        .concat();
+        assert_eq!(replaced, expected);
+
+        // Without any, they go after the JFIF segment
+        let removed = replace_segments(&jpeg, is_comment, &[]).unwrap();
+        let inserted = replace_segments(&removed, is_comment, &[com(b"new")]).unwrap();
+        assert_eq!(inserted, expected);
+
+        assert!(build_segment(COM, &[0; MAX_SEGMENT_DATA_SIZE]).is_ok());
+        assert!(build_segment(COM, &[0; MAX_SEGMENT_DATA_SIZE + 1]).is_err());
+    }
+}
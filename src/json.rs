@@ -0,0 +1,338 @@
+use serde_json::{json, Map, Value};
+
+use crate::exif::{ExifImage, ExifTypes, IFDTypes, InteroperabilityField};
+use crate::gps::{is_coordinate_tag, set_coordinate_from_string, DEFAULT_SECONDS_DECIMALS};
+use crate::tags::Tag;
+use crate::writer::IFDEntry;
+
+// The raw value of a field: a string for ASCII, [numerator, denominator] pairs for rationals, and
+// arrays of numbers for everything else
+fn get_raw_value(interop: &InteroperabilityField, slice: &[u8], is_little_endian: bool) -> Value {
+    let raw = interop.get_raw_value(slice);
+    let get_chunks = |size: usize| {
+        raw.chunks_exact(size).map(move |chunk| {
+            let mut bytes = chunk.to_vec();
+            if is_little_endian {
+                bytes.reverse();
+            }
+            bytes
+        })
+    };
+
+    match interop.get_data_type() {
+        ExifTypes::Ascii => Value::from(interop.get_value_ascii(slice).unwrap_or_default()),
+        ExifTypes::Byte | ExifTypes::Undefined => Value::from(raw),
+        ExifTypes::Sbyte => raw.iter().map(|b| *b as i8).collect(),
+        ExifTypes::Short | ExifTypes::Long | ExifTypes::Ifd => {
+            Value::from(interop.get_uints(slice))
+        }
+        ExifTypes::Sshort => get_chunks(2)
+            .map(|bytes| i16::from_be_bytes([bytes[0], bytes[1]]))
+            .collect(),
+        ExifTypes::Slong => Value::from(interop.get_value_slong(slice).unwrap_or_default()),
+        ExifTypes::Rational => interop
+            .get_value_rational(slice)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(num, den)| json!([num, den]))
+            .collect(),
+        ExifTypes::Srational => interop
+            .get_value_srational(slice)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(num, den)| json!([num, den]))
+            .collect(),
+        ExifTypes::Float => get_chunks(4)
+            .map(|bytes| f32::from_be_bytes(bytes.try_into().unwrap()))
+            .collect(),
+        ExifTypes::Double => get_chunks(8)
+            .map(|bytes| f64::from_be_bytes(bytes.try_into().unwrap()))
+            .collect(),
+        ExifTypes::Error => Value::Null,
+    }
+}
+
+fn get_field_json(
+    interop: &InteroperabilityField,
+    slice: &[u8],
+    is_little_endian: bool,
+    is_interop: bool,
+) -> Value {
+    let tag = interop.get_tag();
+    // The Interoperability IFD reuses the ids of the GPS tags
+    let name = match tag.get_interop_name().filter(|_| is_interop) {
+        Some(name) => name.to_string(),
+        None => tag.to_string(),
+    };
+    json!({
+        "id": tag.0,
+        "name": name,
+        "type": interop.get_data_type().to_string(),
+        "count": interop.get_count(),
+        "value": get_raw_value(interop, slice, is_little_endian),
+        "display": interop.get_value_as_string(slice),
+    })
+}
+
+// The tags of each IFD, with their raw and decoded values, followed by the other metadata (JFIF,
+// IPTC, ICC, XMP...) as names and decoded values
+pub fn get_image_json(img: &ExifImage, path: &str) -> Value {
+    let slice = img.get_slice();
+    let mut ifds = Map::new();
+    for (name, ifd) in img.get_ifds() {
+        let fields: Vec<Value> = ifd
+            .get_interops()
+            .iter()
+            .map(|interop| {
+                get_field_json(interop, slice, img.is_little_endian(), name == "Interop")
+            })
+            .collect();
+        ifds.insert(name, Value::from(fields));
+    }
+
+    let mut sections = Map::new();
+    for (name, tags) in img.get_all_sections() {
+        if ifds.contains_key(&name) {
+            continue;
+        }
+
+        // Names that appear several times (such as PNG text keywords) get an array of values
+        let mut values = Map::new();
+        for (tag, value) in tags {
+            match values.get_mut(&tag) {
+                Some(Value::Array(array)) => array.push(Value::from(value)),
+                Some(other) => *other = json!([other.take(), value]),
+                None => {
+                    values.insert(tag, Value::from(value));
+                }
+            }
+        }
+        sections.insert(name, Value::Object(values));
+    }
+
+    json!({
+        "file": path,
+        "format": img.get_image_format().to_string(),
+        "byte_order": img
+            .has_exif()
+            .then_some(if img.is_little_endian() { "II" } else { "MM" }),
+        "ifds": ifds,
+        "sections": sections,
+        "warnings": img.get_warnings(),
+    })
+}
+
+pub fn get_error_json(path: &str, error: &str) -> Value {
+    json!({
+        "file": path,
+        "error": error,
+    })
+}
+
+pub fn to_string(objects: &[Value]) -> String {
+    serde_json::to_string_pretty(objects).unwrap_or_default()
+}
+
+// A document holds an array of objects, or a single object
+pub fn parse(text: &str) -> Result<Vec<Value>, String> {
+    match serde_json::from_str(text).map_err(|e| e.to_string())? {
+        Value::Array(objects) => Ok(objects),
+        object @ Value::Object(_) => Ok(vec![object]),
+        _ => Err(String::from("Expected an object or an array of objects")),
+    }
+}
+
+// The tag is given by its id, its name, or both as long as they agree
+fn get_tag(field: &Map<String, Value>) -> Result<Tag, String> {
+    let id = match field.get("id") {
+        Some(id) => match id.as_u64().and_then(|id| u16::try_from(id).ok()) {
+            Some(id) => Some(Tag(id as usize)),
+            None => return Err(format!("Invalid tag id {}", id)),
+        },
+        None => None,
+    };
+    let name = match field.get("name") {
+        Some(Value::String(name)) => Some(name.as_str()),
+        Some(name) => return Err(format!("Invalid tag name {}", name)),
+        None => None,
+    };
+
+    match (id, name) {
+        (Some(tag), Some(name))
+            if tag.is_known() && !tag.to_string().eq_ignore_ascii_case(name) =>
+        {
+            Err(format!("The tag {} is {}, not {}", tag.0, tag, name))
+        }
+        (Some(tag), _) => Ok(tag),
+        (None, Some(name)) => Tag::from_name(name).ok_or_else(|| format!("Unknown tag {}", name)),
+        (None, None) => Err(String::from("A tag has neither an id nor a name")),
+    }
+}
+
+// Converts the raw value of the schema to the text form of IFDEntry::from_string, checking that
+// it has the shape the type needs
+fn get_value_as_text(value: &Value, data_type: ExifTypes) -> Option<String> {
+    let values = match value {
+        Value::Array(values) => values.as_slice(),
+        value => std::slice::from_ref(value),
+    };
+    let texts = match data_type {
+        ExifTypes::Ascii => return value.as_str().map(String::from),
+        ExifTypes::Rational | ExifTypes::Srational => values
+            .iter()
+            .map(|pair| match pair.as_array()?.as_slice() {
+                [Value::Number(num), Value::Number(den)] => Some(format!("{}/{}", num, den)),
+                _ => None,
+            })
+            .collect::<Option<Vec<String>>>()?,
+        _ => values
+            .iter()
+            .map(|value| value.as_number().map(|number| number.to_string()))
+            .collect::<Option<Vec<String>>>()?,
+    };
+    Some(texts.join(" "))
+}
+
+fn get_entry(
+    img: &ExifImage,
+    ifd_type: IFDTypes,
+    tag: Tag,
+    field: &Map<String, Value>,
+) -> Result<IFDEntry, String> {
+    let data_type = match field.get("type") {
+        Some(Value::String(name)) => match ExifTypes::from_name(name) {
+            Some(data_type) => data_type,
+            None => return Err(format!("{}: Unknown type {}", tag, name)),
+        },
+        Some(name) => return Err(format!("{}: Invalid type {}", tag, name)),
+        None => match (img.get_interop(ifd_type, tag), tag.get_data_type()) {
+            (Some(interop), _) => *interop.get_data_type(),
+            (None, Some(data_type)) => data_type,
+            (None, None) => return Err(format!("{}: The type is missing", tag)),
+        },
+    };
+
+    // Some tags can be either SHORT or LONG
+    let is_integer = |data_type| matches!(data_type, ExifTypes::Short | ExifTypes::Long);
+    if let Some(spec_type) = tag.get_data_type() {
+        if data_type != spec_type && !(is_integer(data_type) && is_integer(spec_type)) {
+            return Err(format!(
+                "{} must be of type {}, not {}",
+                tag, spec_type, data_type
+            ));
+        }
+    }
+
+    let value = field.get("value").unwrap_or(&Value::Null);
+    let invalid = || format!("Invalid value for {}: {}", tag, value);
+    match data_type {
+        // The text form of UNDEFINED values can't hold arbitrary bytes
+        ExifTypes::Undefined => {
+            let data = value
+                .as_array()
+                .ok_or_else(invalid)?
+                .iter()
+                .map(|byte| byte.as_u64().and_then(|byte| u8::try_from(byte).ok()))
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(invalid)?;
+            Ok(IFDEntry {
+                tag: tag.0 as u16,
+                data_type: data_type.get_code().unwrap_or_default(),
+                count: data.len() as u32,
+                data,
+            })
+        }
+        _ => {
+            let text = get_value_as_text(value, data_type).ok_or_else(invalid)?;
+            IFDEntry::from_string(tag, data_type, &text, img.is_little_endian())
+        }
+    }
+}
+
+// Applies an object of the export format to the image. The tags of the 0th, Exif and GPS IFDs are
+// created or updated, and removed when their value is null. The other IFDs, the tags that aren't
+// listed and the ones that describe the structure of the file are left as they are, so that an
+// export can be applied back as is. The coordinates can also be given as a number or a string in
+// any of the forms of `gps::set_coordinate_from_string`, such as -33.5, which sets their Ref tag as
+// well. Returns whether the image was modified.
+pub fn apply_image_json(img: &mut ExifImage, object: &Value) -> Result<bool, String> {
+    let ifds = match object.get("ifds") {
+        Some(Value::Object(ifds)) => ifds,
+        Some(_) => return Err(String::from("\"ifds\" must be an object")),
+        None => return Ok(false),
+    };
+
+    let mut is_modified = false;
+    for (name, fields) in ifds {
+        let ifd_type = match IFDTypes::from_name(name) {
+            Some(ifd_type) => ifd_type,
+            None => continue,
+        };
+        let fields = match fields.as_array() {
+            Some(fields) => fields,
+            None => return Err(format!("{} must be an array of tags", name)),
+        };
+
+        let mut entries: Vec<IFDEntry> = Vec::new();
+        let mut removed_tags = Vec::new();
+        let mut coordinates = Vec::new();
+        for field in fields {
+            let field = match field.as_object() {
+                Some(field) => field,
+                None => return Err(format!("The tags of {} must be objects", name)),
+            };
+            let tag = get_tag(field)?;
+            if tag.get_ifd_type().is_none() {
+                continue;
+            }
+            if tag.is_known() && tag.get_ifd_type() != Some(ifd_type) {
+                return Err(format!("{} doesn't belong to {}", tag, name));
+            }
+
+            let existing = img
+                .get_interop(ifd_type, tag)
+                .map(|interop| interop.get_entry(img.get_slice()));
+            entries.retain(|entry| entry.tag as usize != tag.0);
+            removed_tags.retain(|other| *other != tag);
+            coordinates.retain(|(other, _)| *other != tag);
+            match field.get("value") {
+                Some(Value::Null) => {
+                    if existing.is_some() {
+                        removed_tags.push(tag);
+                    }
+                    continue;
+                }
+                Some(Value::Number(number)) if is_coordinate_tag(tag) => {
+                    coordinates.push((tag, number.to_string()));
+                    continue;
+                }
+                Some(Value::String(text)) if is_coordinate_tag(tag) => {
+                    coordinates.push((tag, text.clone()));
+                    continue;
+                }
+                Some(_) => (),
+                None => return Err(format!("{} has no value", tag)),
+            }
+            let entry = get_entry(img, ifd_type, tag, field)?;
+            if existing.as_ref() != Some(&entry) {
+                entries.push(entry);
+            }
+        }
+
+        if !entries.is_empty() {
+            img.set_tags(ifd_type, entries)?;
+            is_modified = true;
+        }
+        if !removed_tags.is_empty() {
+            img.remove_tags(ifd_type, &removed_tags)?;
+            is_modified = true;
+        }
+        // After their Ref tags, which they set as well
+        for (tag, text) in coordinates {
+            set_coordinate_from_string(img, tag, &text, DEFAULT_SECONDS_DECIMALS)?;
+            is_modified = true;
+        }
+    }
+
+    Ok(is_modified)
+}
@@ -0,0 +1,84 @@
+use crate::gpx::{format_time, Waypoint};
+use crate::xml::{XMLElement, XMLNode};
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// A placemark per image, in the order they are given. The thumbnails are embedded in the
+// descriptions as data URIs, so that the file stands on its own.
+pub fn write_kml(waypoints: &[Waypoint]) -> String {
+    let mut document = XMLElement::new("Document");
+    document
+        .children
+        .push(XMLNode::Element(XMLElement::with_text("name", "Images")));
+    for waypoint in waypoints {
+        let mut description = String::new();
+        if let Some(thumbnail) = &waypoint.thumbnail {
+            description.push_str(&format!(
+                "<img src=\"data:image/jpeg;base64,{}\"/><br/>",
+                encode_base64(thumbnail)
+            ));
+        }
+        description.push_str(&waypoint.path);
+
+        let position = &waypoint.position;
+        let coordinates = match position.altitude {
+            Some(altitude) => format!(
+                "{:.7},{:.7},{:.2}",
+                position.longitude, position.latitude, altitude
+            ),
+            None => format!("{:.7},{:.7}", position.longitude, position.latitude),
+        };
+        let mut point = XMLElement::new("Point");
+        point.children.push(XMLNode::Element(XMLElement::with_text(
+            "coordinates",
+            &coordinates,
+        )));
+
+        let mut placemark = XMLElement::new("Placemark");
+        let mut elements = vec![
+            XMLElement::with_text("name", &waypoint.get_file_name()),
+            XMLElement::with_text("description", &description),
+        ];
+        if let Some(timestamp) = waypoint.timestamp {
+            let mut time_stamp = XMLElement::new("TimeStamp");
+            time_stamp
+                .children
+                .push(XMLNode::Element(XMLElement::with_text(
+                    "when",
+                    &format_time(timestamp),
+                )));
+            elements.push(time_stamp);
+        }
+        elements.push(point);
+        placemark.children = elements.into_iter().map(XMLNode::Element).collect();
+        document.children.push(XMLNode::Element(placemark));
+    }
+
+    let mut kml = XMLElement::new("kml");
+    kml.attributes.push((
+        String::from("xmlns"),
+        String::from("http://www.opengis.net/kml/2.2"),
+    ));
+    kml.children.push(XMLNode::Element(document));
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{}",
+        kml.to_xml()
+    )
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| {
+            group | (*byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => s.push(BASE64_ALPHABET[(group >> (18 - 6 * i) & 0x3F) as usize] as char),
+                false => s.push('='),
+            }
+        }
+    }
+    s
+}
@@ -1,36 +1,48 @@
-use std::{env::args, fs};
+use std::{env::args, process::ExitCode};
 
 mod arrays;
+mod checksum;
+mod cli;
+mod datetime;
+mod diff;
 pub mod exif;
+mod exiftool;
 mod formats;
+mod gps;
+mod gpx;
+mod icc;
+mod iptc;
+mod jfif;
+mod jpeg;
+mod json;
+mod kml;
+mod mpf;
+mod mwg;
+mod photoshop;
+mod png;
+mod raw;
+mod spreadsheet;
 pub mod tags;
-
-use exif::ExifImage;
-use formats::get_image_format_for;
-
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = args().collect();
-    if args.len() < 2 {
-        return Ok(());
-    }
-
-    let img_path = match args.get(1) {
-        Some(img_path) => img_path,
-        None => return Ok(()),
-    };
-
-    let img_contents = fs::read(img_path)?;
-
-    let img_format = match get_image_format_for(img_contents.as_ref()) {
-        Some(img_type) => img_type,
-        None => {
-            eprintln!("Unsupported image type");
-            return Ok(());
+mod template;
+mod timeshift;
+mod tui;
+mod webp;
+mod writer;
+mod xml;
+mod xmp;
+
+use cli::CliError;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = args().skip(1).collect();
+    match cli::run(&args) {
+        Ok(exit_code) => ExitCode::from(exit_code),
+        Err(e) => {
+            eprintln!("{}", e.get_message());
+            if let CliError::Usage(_) = e {
+                eprintln!("Run ExifEdiTUI --help for the usage");
+            }
+            ExitCode::from(e.get_exit_code())
         }
-    };
-
-    let img = ExifImage::from(img_contents, img_format);
-    img.print_all_tags();
-
-    Ok(())
+    }
 }
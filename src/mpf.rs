@@ -0,0 +1,318 @@
+use crate::exif::{TIFFHeader, IFD, TIFF_HEADER_SIZE};
+use crate::jpeg::{get_segments, APP2};
+use crate::tags::Tags;
+
+// See CIPA DC-007, the MP Index IFD follows a TIFF header placed right after the identifier. The
+// offsets of the images are relative to that header.
+pub const MPF_IDENTIFIER: &[u8] = b"MPF\0";
+const MP_ENTRY_SIZE: usize = 16;
+
+pub struct MPEntry {
+    pub attributes: u32, // Flags, image data format and type code
+    pub size: u32,
+    pub offset: u32, // 0 for the first image, which is the one that contains the MPF segment
+    pub dependent_images: (u16, u16),
+}
+
+impl MPEntry {
+    fn from(data: &[u8], is_little_endian: bool) -> Self {
+        let get_u32 = |offset: usize| {
+            let bytes: [u8; 4] = data[offset..offset + 4].try_into().unwrap();
+            if is_little_endian {
+                u32::from_le_bytes(bytes)
+            } else {
+                u32::from_be_bytes(bytes)
+            }
+        };
+        let get_u16 = |offset: usize| {
+            let bytes: [u8; 2] = data[offset..offset + 2].try_into().unwrap();
+            if is_little_endian {
+                u16::from_le_bytes(bytes)
+            } else {
+                u16::from_be_bytes(bytes)
+            }
+        };
+
+        Self {
+            attributes: get_u32(0),
+            size: get_u32(4),
+            offset: get_u32(8),
+            dependent_images: (get_u16(12), get_u16(14)),
+        }
+    }
+
+    pub fn get_type_as_string(&self) -> String {
+        match self.attributes & 0x00FF_FFFF {
+            0x030000 => String::from("Baseline MP Primary Image"),
+            0x010001 => String::from("Large Thumbnail (VGA)"),
+            0x010002 => String::from("Large Thumbnail (Full HD)"),
+            0x020001 => String::from("Multi-Frame Panorama"),
+            0x020002 => String::from("Multi-Frame Disparity"),
+            0x020003 => String::from("Multi-Frame Multi-Angle"),
+            // Used for the depth and gain maps written by phones
+            0x000000 => String::from("Undefined"),
+            type_code => format!("Unknown (0x{:06X})", type_code),
+        }
+    }
+
+    pub fn get_as_string(&self) -> String {
+        let mut s = format!("{}, {} bytes", self.get_type_as_string(), self.size);
+        if (self.attributes >> 24) & 0x07 != 0 {
+            s.push_str(", not JPEG");
+        }
+        if self.attributes & 0x2000_0000 != 0 {
+            s.push_str(", representative image");
+        }
+        s
+    }
+}
+
+pub struct MPFSegment {
+    tiff_start: usize, // Offset of the TIFF header in the file
+    is_little_endian: bool,
+    ifd: IFD,
+    entries: Vec<MPEntry>,
+}
+
+impl MPFSegment {
+    // Reads the MP Index IFD of the first MPF segment of a JPEG file
+    pub fn from_jpeg(img_contents: &[u8]) -> Result<Option<Self>, String> {
+        let segment = match get_segments(img_contents)
+            .map_while(Result::ok)
+            .find(|segment| {
+                segment.marker == APP2 && segment.get_data(img_contents).starts_with(MPF_IDENTIFIER)
+            }) {
+            Some(segment) => segment,
+            None => return Ok(None),
+        };
+
+        let tiff_start = segment.get_data_offset() + MPF_IDENTIFIER.len();
+        // The images are after the segment, so the offsets are checked against the whole file
+        let slice = &img_contents[tiff_start..];
+        if segment.get_end() - tiff_start < TIFF_HEADER_SIZE {
+            return Err(String::from("The MPF segment is too small"));
+        }
+        let tiff = TIFFHeader::from(&slice[0..TIFF_HEADER_SIZE]);
+        let ifd = IFD::from_tiff(
+            slice,
+            tiff.zero_th_ifd_offset as usize,
+            tiff.is_little_endian,
+        )?;
+
+        let data = ifd
+            .get_interop_for_tag(Tags::MPEntry.0)
+            .map(|interop| interop.get_raw_value(slice))
+            .unwrap_or_default();
+        let entries = data
+            .chunks_exact(MP_ENTRY_SIZE)
+            .map(|entry| MPEntry::from(entry, tiff.is_little_endian))
+            .collect();
+
+        Ok(Some(Self {
+            tiff_start,
+            is_little_endian: tiff.is_little_endian,
+            ifd,
+            entries,
+        }))
+    }
+
+    // The secondary images, the first entry being the image the segment is in
+    pub fn get_images<'a>(&self, img_contents: &'a [u8]) -> Vec<(String, &'a [u8])> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.offset != 0)
+            .filter_map(|(i, entry)| {
+                let start = self.tiff_start + entry.offset as usize;
+                let image = img_contents.get(start..start + entry.size as usize)?;
+                Some((format!("MPImage{}", i + 1), image))
+            })
+            .collect()
+    }
+
+    pub fn get_all_tags(&self, img_contents: &[u8]) -> Vec<(String, String)> {
+        let slice = &img_contents[self.tiff_start..];
+        let mut tags: Vec<(String, String)> = self
+            .ifd
+            .get_interops()
+            .iter()
+            .filter(|interop| interop.get_tag() != Tags::MPEntry)
+            .map(|interop| {
+                (
+                    format!("{}", interop.get_tag()),
+                    interop.get_value_as_string(slice),
+                )
+            })
+            .collect();
+        for (i, entry) in self.entries.iter().enumerate() {
+            tags.push((format!("MPImage{}", i + 1), entry.get_as_string()));
+        }
+
+        tags
+    }
+
+    // Offset of the MP entries in the file
+    fn get_entries_offset(&self) -> Option<usize> {
+        self.ifd
+            .get_offset_for_tag(Tags::MPEntry)
+            .map(|offset| self.tiff_start + offset)
+    }
+}
+
+// Rewriting the metadata of the first image moves the images that follow it, whose offsets are
+// relative to the MPF segment. The images are at the end of the file, so they moved by as much as
+// the size of the file changed, and the first image grew by the same amount.
+pub fn update_mpf_offsets(old_contents: &[u8], new_contents: &mut [u8]) -> Result<(), String> {
+    let (old_mpf, new_mpf) = match (
+        MPFSegment::from_jpeg(old_contents)?,
+        MPFSegment::from_jpeg(new_contents)?,
+    ) {
+        (Some(old_mpf), Some(new_mpf)) => (old_mpf, new_mpf),
+        _ => return Ok(()),
+    };
+    let entries_offset = match new_mpf.get_entries_offset() {
+        Some(offset) => offset,
+        None => return Ok(()),
+    };
+
+    let delta = new_contents.len() as i64 - old_contents.len() as i64;
+    let mut data = Vec::with_capacity(old_mpf.entries.len() * MP_ENTRY_SIZE);
+    for entry in old_mpf.entries.iter() {
+        let (size, offset) = if entry.offset == 0 {
+            (entry.size as i64 + delta, 0)
+        } else {
+            let position = old_mpf.tiff_start as i64 + entry.offset as i64 + delta;
+            (entry.size as i64, position - new_mpf.tiff_start as i64)
+        };
+        let (size, offset) = match (u32::try_from(size), u32::try_from(offset)) {
+            (Ok(size), Ok(offset)) => (size, offset),
+            _ => {
+                return Err(String::from(
+                    "Couldn't update the offsets of the MPF images",
+                ))
+            }
+        };
+
+        for value in [entry.attributes, size, offset] {
+            data.extend_from_slice(&match new_mpf.is_little_endian {
+                true => value.to_le_bytes(),
+                false => value.to_be_bytes(),
+            });
+        }
+        for value in [entry.dependent_images.0, entry.dependent_images.1] {
+            data.extend_from_slice(&match new_mpf.is_little_endian {
+                true => value.to_le_bytes(),
+                false => value.to_be_bytes(),
+            });
+        }
+    }
+
+    match new_contents.get_mut(entries_offset..entries_offset + data.len()) {
+        Some(entries) => entries.copy_from_slice(&data),
+        None => return Err(String::from("The MP entries go past the end of the file")),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jpeg::{build_segment, COM, EOI, SOI};
+
+    const SECOND_IMAGE: [u8; 6] = [0xFF, SOI, 0x12, 0x34, 0xFF, EOI];
+
+    // A little-endian MP Index IFD with the MPFVersion, NumberOfImages and MPEntry tags, followed
+    // by the two entries
+    fn build_mpf(first_size: u32, second_offset: u32) -> Vec<u8> {
+        let mut data = MPF_IDENTIFIER.to_vec();
+        data.extend_from_slice(b"II\x2A\x00\x08\x00\x00\x00");
+        data.extend_from_slice(&3u16.to_le_bytes());
+        for (tag, field_type, count, value) in [
+            (Tags::MPFVersion.0, 7u16, 4u32, u32::from_le_bytes(*b"0100")),
+            (Tags::NumberOfImages.0, 4, 1, 2),
+            (Tags::MPEntry.0, 7, 2 * MP_ENTRY_SIZE as u32, 50),
+        ] {
+            data.extend_from_slice(&(tag as u16).to_le_bytes());
+            data.extend_from_slice(&field_type.to_le_bytes());
+            data.extend_from_slice(&count.to_le_bytes());
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&0u32.to_le_bytes());
+        for (attributes, size, offset) in [
+            (0x2003_0000u32, first_size, 0u32),
+            (0x0001_0001, SECOND_IMAGE.len() as u32, second_offset),
+        ] {
+            for value in [attributes, size, offset, 0] {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        data
+    }
+
+    fn build_jpeg(comment: &[u8], first_size: u32, second_offset: u32) -> Vec<u8> {
+        let mut jpeg = vec![0xFF, SOI];
+        jpeg.extend(build_segment(APP2, &build_mpf(first_size, second_offset)).unwrap());
+        jpeg.extend(build_segment(COM, comment).unwrap());
+        jpeg.extend_from_slice(&[0xFF, EOI]);
+        jpeg.extend_from_slice(&SECOND_IMAGE);
+        jpeg
+    }
+
+    // The TIFF header is after the SOI marker, the APP2 marker and length and the identifier
+    const TIFF_START: usize = 2 + 4 + MPF_IDENTIFIER.len();
+
+    fn get_offsets(jpeg: &[u8]) -> (u32, u32) {
+        let first_size = (jpeg.len() - SECOND_IMAGE.len()) as u32;
+        (first_size, first_size - TIFF_START as u32)
+    }
+
+    #[test]
+    fn images_and_tags() {
+        let (first_size, second_offset) = get_offsets(&build_jpeg(b"a", 0, 0));
+        let jpeg = build_jpeg(b"a", first_size, second_offset);
+        let mpf = MPFSegment::from_jpeg(&jpeg).unwrap().unwrap();
+        assert_eq!(
+            mpf.get_images(&jpeg),
+            [(String::from("MPImage2"), &SECOND_IMAGE[..])]
+        );
+        let tags = mpf.get_all_tags(&jpeg);
+        assert!(tags.contains(&(String::from("NumberOfImages"), String::from("2"))));
+        assert!(tags.contains(&(
+            String::from("MPImage1"),
+            format!(
+                "Baseline MP Primary Image, {} bytes, representative image",
+                first_size
+            )
+        )));
+        assert!(tags.contains(&(
+            String::from("MPImage2"),
+            String::from("Large Thumbnail (VGA), 6 bytes")
+        )));
+
+        assert!(MPFSegment::from_jpeg(&[0xFF, SOI, 0xFF, EOI])
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn offsets_update() {
+        let (first_size, second_offset) = get_offsets(&build_jpeg(b"a", 0, 0));
+        let old_jpeg = build_jpeg(b"a", first_size, second_offset);
+
+        // The comment grows, which moves the second image
+        let mut new_jpeg = build_jpeg(b"a longer comment", first_size, second_offset);
+        update_mpf_offsets(&old_jpeg, &mut new_jpeg).unwrap();
+        let (first_size, second_offset) = get_offsets(&new_jpeg);
+        assert_eq!(
+            new_jpeg,
+            build_jpeg(b"a longer comment", first_size, second_offset)
+        );
+        let mpf = MPFSegment::from_jpeg(&new_jpeg).unwrap().unwrap();
+        assert_eq!(mpf.get_images(&new_jpeg)[0].1, SECOND_IMAGE);
+
+        // An offset that would become negative is an error
+        let old_jpeg = build_jpeg(&[0; 100], first_size, 10);
+        let mut new_jpeg = build_jpeg(b"", first_size, 10);
+        assert!(update_mpf_offsets(&old_jpeg, &mut new_jpeg).is_err());
+    }
+}
@@ -0,0 +1,366 @@
+use crate::datetime::{format_offset, parse_offset};
+use crate::exif::{ExifImage, IFDTypes};
+use crate::tags::{Tag, Tags};
+use crate::xmp::XMPMeta;
+
+// Exif tags that have an XMP counterpart, which must be kept in sync when editing. See the Metadata
+// Working Group guidelines (https://exiftool.org/TagNames/MWG.html for a summary).
+#[derive(PartialEq, Clone, Copy)]
+pub enum SyncedField {
+    Artist,
+    Copyright,
+    ImageDescription,
+    DateTimeOriginal,
+    GPSLatitude,
+    GPSLongitude,
+    GPSAltitude,
+    GPSTimeStamp,
+}
+
+pub const SYNCED_FIELDS: [SyncedField; 8] = [
+    SyncedField::Artist,
+    SyncedField::Copyright,
+    SyncedField::ImageDescription,
+    SyncedField::DateTimeOriginal,
+    SyncedField::GPSLatitude,
+    SyncedField::GPSLongitude,
+    SyncedField::GPSAltitude,
+    SyncedField::GPSTimeStamp,
+];
+
+// Both values are normalized, so that they can be compared
+pub struct XMPConflict {
+    pub tag: String,
+    pub exif_value: String,
+    pub property: String,
+    pub xmp_value: String,
+}
+
+impl SyncedField {
+    // The tags the value is made of, the first one being the main one
+    pub fn get_tags(&self) -> (IFDTypes, &'static [Tag]) {
+        match self {
+            SyncedField::Artist => (IFDTypes::TIFF, &[Tags::Artist]),
+            SyncedField::Copyright => (IFDTypes::TIFF, &[Tags::Copyright]),
+            SyncedField::ImageDescription => (IFDTypes::TIFF, &[Tags::ImageDescription]),
+            SyncedField::DateTimeOriginal => (
+                IFDTypes::Exif,
+                &[
+                    Tags::DateTimeOriginal,
+                    Tags::SubSecTimeOriginal,
+                    Tags::OffsetTimeOriginal,
+                ],
+            ),
+            SyncedField::GPSLatitude => (IFDTypes::GPS, &[Tags::GPSLatitude, Tags::GPSLatitudeRef]),
+            SyncedField::GPSLongitude => {
+                (IFDTypes::GPS, &[Tags::GPSLongitude, Tags::GPSLongitudeRef])
+            }
+            SyncedField::GPSAltitude => (IFDTypes::GPS, &[Tags::GPSAltitude, Tags::GPSAltitudeRef]),
+            SyncedField::GPSTimeStamp => (IFDTypes::GPS, &[Tags::GPSTimeStamp, Tags::GPSDateStamp]),
+        }
+    }
+
+    // The properties holding the same value. The MWG maps DateTimeOriginal to photoshop:DateCreated,
+    // but most tools still read the property of the Exif namespace.
+    pub fn get_properties(&self) -> &'static [&'static str] {
+        match self {
+            SyncedField::Artist => &["dc:creator"],
+            SyncedField::Copyright => &["dc:rights"],
+            SyncedField::ImageDescription => &["dc:description"],
+            SyncedField::DateTimeOriginal => &["exif:DateTimeOriginal", "photoshop:DateCreated"],
+            SyncedField::GPSLatitude => &["exif:GPSLatitude"],
+            SyncedField::GPSLongitude => &["exif:GPSLongitude"],
+            SyncedField::GPSAltitude => &["exif:GPSAltitude"],
+            SyncedField::GPSTimeStamp => &["exif:GPSTimeStamp"],
+        }
+    }
+
+    // The value of the Exif tags, normalized: names separated by "; ", dates in the XMP format,
+    // coordinates in signed decimal degrees, altitudes in signed meters
+    pub fn get_exif_value(&self, img: &ExifImage) -> Option<String> {
+        let (ifd_type, tags) = self.get_tags();
+        match self {
+            SyncedField::Artist => Some(normalize_names(&img.get_ascii(ifd_type, tags[0])?)),
+            SyncedField::Copyright | SyncedField::ImageDescription => {
+                Some(img.get_ascii(ifd_type, tags[0])?.trim().to_string())
+            }
+            SyncedField::DateTimeOriginal => get_xmp_date(
+                &img.get_ascii(ifd_type, tags[0])?,
+                img.get_ascii(ifd_type, tags[1]).as_deref(),
+                img.get_ascii(ifd_type, tags[2]).as_deref(),
+            ),
+            SyncedField::GPSLatitude | SyncedField::GPSLongitude => {
+                let coordinate = get_decimal_degrees(&img.get_rationals(ifd_type, tags[0])?)?;
+                let is_negative =
+                    matches!(img.get_ascii(ifd_type, tags[1]).as_deref(), Some("S" | "W"));
+                Some(format!(
+                    "{:.6}",
+                    if is_negative { -coordinate } else { coordinate }
+                ))
+            }
+            SyncedField::GPSAltitude => {
+                let altitude = get_f64(*img.get_rationals(ifd_type, tags[0])?.first()?)?;
+                let is_below_sea_level =
+                    img.get_uints(ifd_type, tags[1]).unwrap_or_default() == [1];
+                Some(format!(
+                    "{:.2}",
+                    if is_below_sea_level {
+                        -altitude
+                    } else {
+                        altitude
+                    }
+                ))
+            }
+            SyncedField::GPSTimeStamp => {
+                let time = img.get_rationals(ifd_type, tags[0])?;
+                let date = img.get_ascii(ifd_type, tags[1])?;
+                get_gps_timestamp(&time, &date)
+            }
+        }
+    }
+
+    // The value of the property, normalized like the Exif one
+    pub fn get_xmp_value(&self, meta: &XMPMeta, property: &str) -> Option<String> {
+        let text = meta.get_text(property)?;
+        match self {
+            SyncedField::Artist => Some(normalize_names(&text)),
+            SyncedField::GPSLatitude | SyncedField::GPSLongitude => {
+                Some(format!("{:.6}", parse_xmp_coordinate(&text)?))
+            }
+            SyncedField::GPSAltitude => {
+                let altitude = parse_xmp_rational(&text)?;
+                let is_below_sea_level =
+                    meta.get_text("exif:GPSAltitudeRef").as_deref() == Some("1");
+                Some(format!(
+                    "{:.2}",
+                    if is_below_sea_level {
+                        -altitude
+                    } else {
+                        altitude
+                    }
+                ))
+            }
+            _ => Some(text.trim().to_string()),
+        }
+    }
+
+    fn is_same(&self, exif_value: &str, xmp_value: &str) -> bool {
+        match self {
+            // One of them may be less precise, or lack the time zone
+            SyncedField::DateTimeOriginal | SyncedField::GPSTimeStamp => {
+                exif_value.starts_with(xmp_value) || xmp_value.starts_with(exif_value)
+            }
+            SyncedField::GPSLatitude | SyncedField::GPSLongitude | SyncedField::GPSAltitude => {
+                let tolerance = if *self == SyncedField::GPSAltitude {
+                    0.01
+                } else {
+                    0.00001 // About a meter
+                };
+                match (exif_value.parse::<f64>(), xmp_value.parse::<f64>()) {
+                    (Ok(a), Ok(b)) => (a - b).abs() < tolerance,
+                    _ => false,
+                }
+            }
+            _ => exif_value == xmp_value,
+        }
+    }
+
+    // Properties that exist in both stores but don't hold the same value
+    pub fn get_conflicts(&self, meta: &XMPMeta, img: &ExifImage) -> Vec<XMPConflict> {
+        let exif_value = match self.get_exif_value(img) {
+            Some(exif_value) => exif_value,
+            None => return Vec::new(),
+        };
+
+        self.get_properties()
+            .iter()
+            .filter_map(|property| {
+                let xmp_value = self.get_xmp_value(meta, property)?;
+                if self.is_same(&exif_value, &xmp_value) {
+                    return None;
+                }
+                Some(XMPConflict {
+                    tag: self.get_tags().1[0].to_string(),
+                    exif_value: exif_value.clone(),
+                    property: property.to_string(),
+                    xmp_value,
+                })
+            })
+            .collect()
+    }
+
+    // Writes the value of the Exif tags to the XMP properties, or removes them if the tags are
+    // missing
+    pub fn write_xmp(&self, meta: &mut XMPMeta, img: &ExifImage) -> Result<(), String> {
+        let (ifd_type, tags) = self.get_tags();
+        let value = match self {
+            SyncedField::GPSLatitude | SyncedField::GPSLongitude => {
+                img.get_rationals(ifd_type, tags[0]).and_then(|coordinate| {
+                    let reference = img.get_ascii(ifd_type, tags[1])?;
+                    get_xmp_coordinate(&coordinate, &reference)
+                })
+            }
+            SyncedField::GPSAltitude => img
+                .get_rationals(ifd_type, tags[0])
+                .and_then(|altitude| altitude.first().copied())
+                .map(|(numerator, denominator)| format!("{}/{}", numerator, denominator)),
+            _ => self.get_exif_value(img),
+        };
+
+        for property in self.get_properties() {
+            match (&value, self) {
+                (None, _) => {
+                    meta.remove_property(property)?;
+                }
+                (Some(value), SyncedField::Artist) => meta.set_list(
+                    property,
+                    &value
+                        .split("; ")
+                        .filter(|name| !name.is_empty())
+                        .map(|name| name.to_string())
+                        .collect::<Vec<String>>(),
+                )?,
+                (Some(value), _) => meta.set_text(property, value)?,
+            }
+        }
+
+        if *self == SyncedField::GPSAltitude {
+            match (&value, img.get_uints(ifd_type, tags[1])) {
+                (Some(_), Some(reference)) if !reference.is_empty() => {
+                    meta.set_text("exif:GPSAltitudeRef", &reference[0].to_string())?
+                }
+                _ => {
+                    meta.remove_property("exif:GPSAltitudeRef")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// The MWG separates multiple names with semicolons in the Exif Artist tag
+fn normalize_names(names: &str) -> String {
+    names
+        .split(';')
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+        .collect::<Vec<&str>>()
+        .join("; ")
+}
+
+fn get_f64((numerator, denominator): (u32, u32)) -> Option<f64> {
+    if denominator == 0 {
+        None
+    } else {
+        Some(numerator as f64 / denominator as f64)
+    }
+}
+
+fn get_decimal_degrees(coordinate: &[(u32, u32)]) -> Option<f64> {
+    let mut degrees = 0.0;
+    for (i, value) in coordinate.iter().take(3).enumerate() {
+        degrees += get_f64(*value)? / 60f64.powi(i as i32);
+    }
+    Some(degrees)
+}
+
+// Converts "YYYY:MM:DD HH:MM:SS" to "YYYY-MM-DDTHH:MM:SS", adding the sub-seconds and time zone
+fn get_xmp_date(date_time: &str, sub_sec: Option<&str>, offset: Option<&str>) -> Option<String> {
+    let date_time = date_time.trim();
+    let bytes = date_time.as_bytes();
+    // The fields are sliced by bytes, so they must all be ASCII digits
+    let is_number = |start: usize, end: usize| bytes[start..end].iter().all(u8::is_ascii_digit);
+    if bytes.len() < 19
+        || bytes[4] != b':'
+        || bytes[7] != b':'
+        || bytes[10] != b' '
+        || bytes[13] != b':'
+        || bytes[16] != b':'
+        || ![(0, 4), (5, 7), (8, 10), (11, 13), (14, 16), (17, 19)]
+            .iter()
+            .all(|(start, end)| is_number(*start, *end))
+    {
+        return None;
+    }
+
+    let mut xmp_date = format!(
+        "{}-{}-{}T{}",
+        &date_time[0..4],
+        &date_time[5..7],
+        &date_time[8..10],
+        &date_time[11..19]
+    );
+    if let Some(sub_sec) = sub_sec.map(|sub_sec| sub_sec.trim()) {
+        if !sub_sec.is_empty() && sub_sec.chars().all(|c| c.is_ascii_digit()) {
+            xmp_date.push_str(format!(".{}", sub_sec).as_str());
+        }
+    }
+    if let Some(offset) = offset.and_then(|offset| parse_offset(offset.trim())) {
+        xmp_date.push_str(&format_offset(offset));
+    }
+
+    Some(xmp_date)
+}
+
+fn get_gps_timestamp(time: &[(u32, u32)], date: &str) -> Option<String> {
+    if time.len() != 3 {
+        return None;
+    }
+    let seconds = get_f64(time[2])?;
+    // The date is in the Exif format, without the time
+    let xmp_date = get_xmp_date(format!("{} 00:00:00", date.trim()).as_str(), None, None)?;
+
+    Some(format!(
+        "{}T{:02}:{:02}:{}Z",
+        &xmp_date[0..10],
+        get_f64(time[0])? as u32,
+        get_f64(time[1])? as u32,
+        if seconds.fract() == 0.0 {
+            format!("{:02}", seconds as u32)
+        } else {
+            format!("{:05.2}", seconds)
+        }
+    ))
+}
+
+// Formats the coordinate as "DDD,MM.mmk", see the XMP spec part 2 (1.2.7.4 - GPSCoordinate)
+fn get_xmp_coordinate(coordinate: &[(u32, u32)], reference: &str) -> Option<String> {
+    let degrees = get_decimal_degrees(coordinate)?;
+    let minutes = format!("{:.6}", (degrees - degrees.trunc()) * 60.0);
+    Some(format!(
+        "{},{}{}",
+        degrees.trunc() as u32,
+        minutes.trim_end_matches('0').trim_end_matches('.'),
+        reference.trim()
+    ))
+}
+
+// Parses both "DDD,MM,SSk" and "DDD,MM.mmk" into signed decimal degrees
+fn parse_xmp_coordinate(coordinate: &str) -> Option<f64> {
+    let coordinate = coordinate.trim();
+    let reference = coordinate.chars().last()?;
+    let mut degrees = 0.0;
+    for (i, value) in coordinate[..coordinate.len() - reference.len_utf8()]
+        .split(',')
+        .enumerate()
+    {
+        degrees += value.trim().parse::<f64>().ok()? / 60f64.powi(i as i32);
+    }
+
+    match reference {
+        'N' | 'E' => Some(degrees),
+        'S' | 'W' => Some(-degrees),
+        _ => None,
+    }
+}
+
+fn parse_xmp_rational(rational: &str) -> Option<f64> {
+    match rational.split_once('/') {
+        Some((numerator, denominator)) => get_f64((
+            numerator.trim().parse().ok()?,
+            denominator.trim().parse().ok()?,
+        )),
+        None => rational.trim().parse().ok(),
+    }
+}
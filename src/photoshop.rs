@@ -0,0 +1,131 @@
+use crate::jpeg::{
+    build_segment, get_segments, replace_segments, JPEGSegment, APP13, MAX_SEGMENT_DATA_SIZE,
+};
+
+// See https://www.adobe.com/devnet-apps/photoshop/fileformatashtml/ (Image Resource Blocks)
+pub const PHOTOSHOP_IDENTIFIER: &[u8] = b"Photoshop 3.0\0";
+pub const RESOURCE_SIGNATURE: &[u8; 4] = b"8BIM";
+
+pub const IPTC_RESOURCE_ID: u16 = 0x0404;
+// MD5 digest of the IPTC resource, used by Photoshop to notice that another tool modified it
+pub const IPTC_DIGEST_RESOURCE_ID: u16 = 0x0425;
+
+#[derive(Clone)]
+pub struct PhotoshopResource {
+    pub signature: [u8; 4],
+    pub id: u16,
+    pub name: Vec<u8>, // Pascal string, without its length and padding
+    pub data: Vec<u8>,
+}
+
+impl PhotoshopResource {
+    pub fn new(id: u16, data: Vec<u8>) -> Self {
+        Self {
+            signature: *RESOURCE_SIGNATURE,
+            id,
+            name: Vec::new(),
+            data,
+        }
+    }
+}
+
+// Resources are padded to an even size, as well as their name (including its length byte)
+pub fn parse_resources(data: &[u8]) -> Result<Vec<PhotoshopResource>, String> {
+    let mut resources = Vec::new();
+    let mut pos = 0;
+
+    while pos < data.len() {
+        // Some writers pad the end of the segment with zeros
+        if data[pos..].iter().all(|b| *b == 0) {
+            break;
+        }
+
+        let header = match data.get(pos..pos + 7) {
+            Some(header) => header,
+            None => return Err(format!("Truncated image resource at offset {}", pos)),
+        };
+        let name_length = header[6] as usize;
+        let name_size = (1 + name_length).next_multiple_of(2);
+        let size_offset = pos + 6 + name_size;
+        let size = match data.get(size_offset..size_offset + 4) {
+            Some(size) => u32::from_be_bytes(size.try_into().unwrap()) as usize,
+            None => return Err(format!("Truncated image resource at offset {}", pos)),
+        };
+        let data_offset = size_offset + 4;
+        if data_offset + size > data.len() {
+            return Err(format!(
+                "The image resource at offset {} goes past the end of the data",
+                pos
+            ));
+        }
+
+        resources.push(PhotoshopResource {
+            signature: header[0..4].try_into().unwrap(),
+            id: u16::from_be_bytes(header[4..6].try_into().unwrap()),
+            name: data[pos + 7..pos + 7 + name_length].to_vec(),
+            data: data[data_offset..data_offset + size].to_vec(),
+        });
+        pos = data_offset + size.next_multiple_of(2);
+    }
+
+    Ok(resources)
+}
+
+pub fn write_resources(resources: &[PhotoshopResource]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for resource in resources.iter() {
+        data.extend_from_slice(&resource.signature);
+        data.extend_from_slice(&resource.id.to_be_bytes());
+        data.push(resource.name.len() as u8);
+        data.extend_from_slice(&resource.name);
+        if resource.name.len() % 2 == 0 {
+            data.push(0);
+        }
+        data.extend_from_slice(&(resource.data.len() as u32).to_be_bytes());
+        data.extend_from_slice(&resource.data);
+        if resource.data.len() % 2 == 1 {
+            data.push(0);
+        }
+    }
+    data
+}
+
+pub fn is_photoshop_segment(segment: &JPEGSegment, slice: &[u8]) -> bool {
+    segment.marker == APP13 && segment.get_data(slice).starts_with(PHOTOSHOP_IDENTIFIER)
+}
+
+// Reads the image resources of the JPEG file. Large resources are split over several APP13
+// segments, whose data is concatenated.
+pub fn read_jpeg_resources(slice: &[u8]) -> Result<Option<Vec<PhotoshopResource>>, String> {
+    let mut data = Vec::new();
+    let mut is_found = false;
+    for segment in get_segments(slice).map_while(Result::ok) {
+        if is_photoshop_segment(&segment, slice) {
+            data.extend_from_slice(&segment.get_data(slice)[PHOTOSHOP_IDENTIFIER.len()..]);
+            is_found = true;
+        }
+    }
+
+    if is_found {
+        parse_resources(&data).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+// Replaces the APP13 segments of the JPEG file, or removes them if there are no resources left
+pub fn write_jpeg_resources(
+    slice: &[u8],
+    resources: &[PhotoshopResource],
+) -> Result<Vec<u8>, String> {
+    let data = write_resources(resources);
+    let mut segments = Vec::new();
+    for chunk in data.chunks(MAX_SEGMENT_DATA_SIZE - PHOTOSHOP_IDENTIFIER.len()) {
+        segments.push(build_segment(
+            APP13,
+            &[PHOTOSHOP_IDENTIFIER, chunk].concat(),
+        )?);
+    }
+
+    replace_segments(slice, is_photoshop_segment, &segments)
+}
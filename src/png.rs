@@ -0,0 +1,512 @@
+use std::io::Read;
+
+use flate2::read::ZlibDecoder;
+
+use crate::checksum::crc32;
+
+// See https://www.w3.org/TR/png/#5Chunk-layout
+pub const PNG_SIGNATURE: [u8; 8] = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+pub const IHDR: &[u8; 4] = b"IHDR";
+pub const IEND: &[u8; 4] = b"IEND";
+pub const EXIF: &[u8; 4] = b"eXIf";
+pub const ITXT: &[u8; 4] = b"iTXt";
+pub const TEXT: &[u8; 4] = b"tEXt";
+pub const ZTXT: &[u8; 4] = b"zTXt";
+pub const ICCP: &[u8; 4] = b"iCCP";
+
+// Keyword of the iTXt chunk holding the XMP packet
+pub const XMP_KEYWORD: &str = "XML:com.adobe.xmp";
+// See https://www.w3.org/TR/png/#11keywords
+pub const COMMENT_KEYWORD: &str = "Comment";
+// ImageMagick stores the metadata profiles as hex in text chunks, which predate the eXIf chunk
+const RAW_PROFILE_PREFIX: &str = "Raw profile type ";
+const RAW_PROFILE_EXIF_KEYWORDS: [&str; 2] = ["Raw profile type exif", "Raw profile type APP1"];
+const MAX_KEYWORD_LENGTH: usize = 79;
+
+#[derive(Clone)]
+pub struct PNGChunk {
+    pub chunk_type: [u8; 4],
+    pub offset: usize, // Offset of the length field
+    pub length: usize, // Length of the data only
+}
+
+impl PNGChunk {
+    pub fn get_data_offset(&self) -> usize {
+        self.offset + 8
+    }
+
+    // Offset of the first byte after the CRC
+    pub fn get_end(&self) -> usize {
+        self.offset + 8 + self.length + 4
+    }
+
+    pub fn get_data<'a>(&self, slice: &'a [u8]) -> &'a [u8] {
+        &slice[self.get_data_offset()..self.get_data_offset() + self.length]
+    }
+
+    pub fn get_type_as_string(&self) -> String {
+        String::from_utf8_lossy(&self.chunk_type).to_string()
+    }
+}
+
+// Walks the chunks of a PNG file, until the IEND chunk
+pub struct PNGChunks<'a> {
+    slice: &'a [u8],
+    pos: usize,
+    finished: bool,
+}
+
+impl<'a> Iterator for PNGChunks<'a> {
+    type Item = Result<PNGChunk, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished || self.pos == self.slice.len() {
+            return None;
+        }
+
+        let header = match self.slice.get(self.pos..self.pos + 8) {
+            Some(header) => header,
+            None => {
+                self.finished = true;
+                return Some(Err(format!(
+                    "Truncated chunk header at offset {}",
+                    self.pos
+                )));
+            }
+        };
+        let chunk = PNGChunk {
+            chunk_type: header[4..8].try_into().unwrap(),
+            offset: self.pos,
+            length: u32::from_be_bytes(header[0..4].try_into().unwrap()) as usize,
+        };
+        if chunk.get_end() > self.slice.len() {
+            self.finished = true;
+            return Some(Err(format!(
+                "The {} chunk at offset {} goes past the end of the file",
+                chunk.get_type_as_string(),
+                chunk.offset
+            )));
+        }
+
+        self.pos = chunk.get_end();
+        self.finished = &chunk.chunk_type == IEND;
+        Some(Ok(chunk))
+    }
+}
+
+pub fn get_chunks(slice: &[u8]) -> PNGChunks<'_> {
+    PNGChunks {
+        slice,
+        pos: PNG_SIGNATURE.len(),
+        finished: !slice.starts_with(&PNG_SIGNATURE),
+    }
+}
+
+pub fn build_chunk(chunk_type: &[u8; 4], data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() > i32::MAX as usize {
+        return Err(format!(
+            "{} bytes of data don't fit in a PNG chunk",
+            data.len()
+        ));
+    }
+
+    let mut chunk = Vec::with_capacity(12 + data.len());
+    chunk.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    chunk.extend_from_slice(chunk_type);
+    chunk.extend_from_slice(data);
+    let crc = crc32(&chunk[4..]);
+    chunk.extend_from_slice(&crc.to_be_bytes());
+    Ok(chunk)
+}
+
+// Removes the chunks for which `is_replaced` returns true, and inserts `new_chunks` where the
+// first of them was, or after the IHDR chunk if there were none
+pub fn replace_chunks<F: Fn(&PNGChunk, &[u8]) -> bool>(
+    slice: &[u8],
+    is_replaced: F,
+    new_chunks: &[Vec<u8>],
+) -> Result<Vec<u8>, String> {
+    let chunks = get_chunks(slice).collect::<Result<Vec<PNGChunk>, String>>()?;
+    let insert_at = match chunks.iter().position(|chunk| is_replaced(chunk, slice)) {
+        Some(i) => i,
+        None => match chunks.first() {
+            Some(chunk) if &chunk.chunk_type == IHDR => 1,
+            _ => {
+                return Err(String::from(
+                    "The PNG file doesn't start with an IHDR chunk",
+                ))
+            }
+        },
+    };
+
+    let mut contents = Vec::with_capacity(slice.len());
+    contents.extend_from_slice(&slice[..PNG_SIGNATURE.len()]);
+    for (i, chunk) in chunks.iter().enumerate() {
+        if i == insert_at {
+            new_chunks
+                .iter()
+                .for_each(|new_chunk| contents.extend_from_slice(new_chunk));
+        }
+        if !is_replaced(chunk, slice) {
+            contents.extend_from_slice(&slice[chunk.offset..chunk.get_end()]);
+        }
+    }
+    // Anything after the IEND chunk is kept as is
+    if let Some(last) = chunks.last() {
+        contents.extend_from_slice(&slice[last.get_end()..]);
+    }
+
+    Ok(contents)
+}
+
+// An international text chunk, see https://www.w3.org/TR/png/#11iTXt
+pub struct InternationalText {
+    pub keyword: String,
+    pub is_compressed: bool,
+    pub language: String,
+    pub translated_keyword: String,
+    pub text: Vec<u8>, // UTF-8, still compressed if `is_compressed` is true
+}
+
+impl InternationalText {
+    pub fn from(data: &[u8]) -> Option<Self> {
+        let keyword_end = data.iter().position(|b| *b == 0)?;
+        let is_compressed = *data.get(keyword_end + 1)? == 1;
+        let rest = data.get(keyword_end + 3..)?;
+        let language_end = rest.iter().position(|b| *b == 0)?;
+        let translated_end =
+            language_end + 1 + rest[language_end + 1..].iter().position(|b| *b == 0)?;
+
+        Some(Self {
+            keyword: String::from_utf8_lossy(&data[..keyword_end]).to_string(),
+            is_compressed,
+            language: String::from_utf8_lossy(&rest[..language_end]).to_string(),
+            translated_keyword: String::from_utf8_lossy(&rest[language_end + 1..translated_end])
+                .to_string(),
+            text: rest[translated_end + 1..].to_vec(),
+        })
+    }
+
+    // Uncompressed text without language
+    pub fn new(keyword: &str, text: &str) -> Self {
+        Self {
+            keyword: keyword.to_string(),
+            is_compressed: false,
+            language: String::new(),
+            translated_keyword: String::new(),
+            text: text.as_bytes().to_vec(),
+        }
+    }
+
+    pub fn get_text(&self) -> Result<String, String> {
+        let text = match self.is_compressed {
+            true => inflate(&self.text)?,
+            false => self.text.clone(),
+        };
+        Ok(String::from_utf8_lossy(&text).to_string())
+    }
+
+    pub fn get_data(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.keyword.len() + 5 + self.text.len());
+        data.extend_from_slice(self.keyword.as_bytes());
+        data.extend_from_slice(&[0, self.is_compressed as u8, 0]);
+        data.extend_from_slice(self.language.as_bytes());
+        data.push(0);
+        data.extend_from_slice(self.translated_keyword.as_bytes());
+        data.push(0);
+        data.extend_from_slice(&self.text);
+        data
+    }
+}
+
+pub fn is_xmp_chunk(chunk: &PNGChunk, slice: &[u8]) -> bool {
+    &chunk.chunk_type == ITXT
+        && chunk
+            .get_data(slice)
+            .starts_with(format!("{}\0", XMP_KEYWORD).as_bytes())
+}
+
+// Compressed chunks (iCCP, zTXt, iTXt) all use zlib
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut res = Vec::new();
+    ZlibDecoder::new(data)
+        .read_to_end(&mut res)
+        .map_err(|e| format!("Couldn't decompress the data: {}", e))?;
+    Ok(res)
+}
+
+fn get_latin1_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| *b as char).collect()
+}
+
+// Reads the keyword and the text of a tEXt, zTXt or iTXt chunk. The first two are in ISO 8859-1
+// and the last one in UTF-8.
+pub fn get_text(chunk: &PNGChunk, slice: &[u8]) -> Option<Result<(String, String), String>> {
+    let data = chunk.get_data(slice);
+    let keyword_end = data.iter().position(|b| *b == 0);
+    let (keyword, rest) = match keyword_end {
+        Some(keyword_end) => (
+            get_latin1_string(&data[..keyword_end]),
+            &data[keyword_end + 1..],
+        ),
+        None => (String::new(), &[] as &[u8]),
+    };
+
+    let text = match &chunk.chunk_type {
+        TEXT => Ok(get_latin1_string(rest)),
+        ZTXT => match rest.split_first() {
+            Some((0, compressed)) => inflate(compressed).map(|text| get_latin1_string(&text)),
+            _ => Err(format!(
+                "Invalid compression method for the {} text",
+                keyword
+            )),
+        },
+        ITXT => match InternationalText::from(data) {
+            Some(text) => text.get_text(),
+            None => Err(String::from("Invalid iTXt chunk")),
+        },
+        _ => return None,
+    };
+    if keyword_end.is_none() {
+        return Some(Err(format!(
+            "The {} chunk at offset {} has no keyword",
+            chunk.get_type_as_string(),
+            chunk.offset
+        )));
+    }
+
+    Some(text.map(|text| (keyword, text)))
+}
+
+pub fn is_text_chunk(chunk: &PNGChunk, slice: &[u8], keyword: &str) -> bool {
+    [TEXT, ZTXT, ITXT].contains(&&chunk.chunk_type)
+        && chunk
+            .get_data(slice)
+            .starts_with(format!("{}\0", keyword).as_bytes())
+}
+
+// Text that fits in ISO 8859-1 goes in a tEXt chunk, the rest in an uncompressed iTXt chunk
+pub fn build_text_chunk(keyword: &str, text: &str) -> Result<Vec<u8>, String> {
+    let is_valid_keyword = |c: char| (' '..='~').contains(&c) || ('\u{A1}'..='\u{FF}').contains(&c);
+    if keyword.is_empty()
+        || keyword.chars().count() > MAX_KEYWORD_LENGTH
+        || !keyword.chars().all(is_valid_keyword)
+        || keyword.starts_with(' ')
+        || keyword.ends_with(' ')
+    {
+        return Err(format!("Invalid PNG text keyword \"{}\"", keyword));
+    }
+
+    if text.chars().all(|c| (c as u32) < 0x100) {
+        let mut data: Vec<u8> = keyword.chars().map(|c| c as u8).collect();
+        data.push(0);
+        data.extend(text.chars().map(|c| c as u8));
+        build_chunk(TEXT, &data)
+    } else {
+        build_chunk(ITXT, &InternationalText::new(keyword, text).get_data())
+    }
+}
+
+pub fn is_raw_profile(keyword: &str) -> bool {
+    keyword.starts_with(RAW_PROFILE_PREFIX)
+}
+
+pub fn is_raw_profile_exif_chunk(chunk: &PNGChunk, slice: &[u8]) -> bool {
+    RAW_PROFILE_EXIF_KEYWORDS
+        .iter()
+        .any(|keyword| is_text_chunk(chunk, slice, keyword))
+}
+
+// The text of a raw profile is made of the name of the profile, its length and its bytes in hex
+pub fn decode_raw_profile(text: &str) -> Result<Vec<u8>, String> {
+    let mut tokens = text.split_whitespace().skip(1);
+    let length = match tokens.next().map(|length| length.parse::<usize>()) {
+        Some(Ok(length)) => length,
+        _ => return Err(String::from("Invalid raw profile length")),
+    };
+    let hex: String = tokens.collect();
+    if hex.len() < 2 * length || !hex.is_ascii() {
+        return Err(String::from("The raw profile is truncated"));
+    }
+
+    (0..length)
+        .map(|i| {
+            u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)
+                .map_err(|_| String::from("Invalid hex digits in the raw profile"))
+        })
+        .collect()
+}
+
+// Reads the Exif data that ImageMagick stores in a text chunk, without the "Exif\0\0" prefix
+pub fn get_raw_profile_exif(slice: &[u8]) -> Option<Result<Vec<u8>, String>> {
+    let chunk = get_chunks(slice)
+        .map_while(Result::ok)
+        .find(|chunk| is_raw_profile_exif_chunk(chunk, slice))?;
+
+    Some(get_text(&chunk, slice)?.and_then(|(_, text)| {
+        let profile = decode_raw_profile(&text)?;
+        match profile.strip_prefix(b"Exif\0\0".as_ref()) {
+            Some(tiff) => Ok(tiff.to_vec()),
+            None => Ok(profile),
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_png(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut png = PNG_SIGNATURE.to_vec();
+        png.extend(build_chunk(IHDR, &[0; 13]).unwrap());
+        chunks.iter().for_each(|chunk| png.extend_from_slice(chunk));
+        png.extend(build_chunk(b"IDAT", &[1, 2, 3]).unwrap());
+        png.extend(build_chunk(IEND, &[]).unwrap());
+        png
+    }
+
+    fn get_types(png: &[u8]) -> Vec<String> {
+        get_chunks(png)
+            .map(|chunk| chunk.unwrap().get_type_as_string())
+            .collect()
+    }
+
+    #[test]
+    fn chunks_walk() {
+        // The CRC covers the type and the data
+        let chunk = build_chunk(IEND, &[]).unwrap();
+        assert_eq!(
+            chunk,
+            [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]
+        );
+
+        let mut png = build_png(&[]);
+        assert_eq!(get_types(&png), ["IHDR", "IDAT", "IEND"]);
+        let idat = get_chunks(&png).nth(1).unwrap().unwrap();
+        assert_eq!(idat.get_data(&png), [1, 2, 3]);
+
+        // The walk stops at IEND, and the rest is kept by the replacement
+        png.extend_from_slice(b"trailing");
+        assert_eq!(get_types(&png), ["IHDR", "IDAT", "IEND"]);
+        let replaced = replace_chunks(&png, |_, _| false, &[]).unwrap();
+        assert_eq!(replaced, png);
+
+        let truncated = &png[..png.len() - 25];
+        assert!(get_chunks(truncated).any(|chunk| chunk.is_err()));
+        assert_eq!(get_chunks(&png[..PNG_SIGNATURE.len() - 1]).count(), 0);
+    }
+
+    #[test]
+    fn xmp_chunk_replace() {
+        let xmp = InternationalText::new(XMP_KEYWORD, "<x:xmpmeta/>").get_data();
+        let png = build_png(&[]);
+
+        // Without an XMP chunk, it goes after IHDR
+        let chunk = build_chunk(ITXT, &xmp).unwrap();
+        let png = replace_chunks(&png, is_xmp_chunk, std::slice::from_ref(&chunk)).unwrap();
+        assert_eq!(png, build_png(&[chunk]));
+        let itxt = get_chunks(&png).nth(1).unwrap().unwrap();
+        assert!(is_xmp_chunk(&itxt, &png));
+        let text = InternationalText::from(itxt.get_data(&png)).unwrap();
+        assert_eq!(text.keyword, XMP_KEYWORD);
+        assert!(!text.is_compressed);
+        assert_eq!(text.text, b"<x:xmpmeta/>");
+        assert_eq!(text.get_data(), xmp);
+
+        // An existing one is replaced in place, or removed
+        let chunk = build_chunk(ITXT, &InternationalText::new(XMP_KEYWORD, "").get_data()).unwrap();
+        let replaced = replace_chunks(&png, is_xmp_chunk, std::slice::from_ref(&chunk)).unwrap();
+        assert_eq!(replaced, build_png(&[chunk]));
+        let removed = replace_chunks(&png, is_xmp_chunk, &[]).unwrap();
+        assert_eq!(removed, build_png(&[]));
+
+        assert!(InternationalText::from(b"keyword\0\0").is_none());
+    }
+
+    fn deflate(data: &[u8]) -> Vec<u8> {
+        use std::io::Write;
+
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn get_texts(png: &[u8]) -> Vec<Result<(String, String), String>> {
+        get_chunks(png)
+            .map_while(Result::ok)
+            .filter_map(|chunk| get_text(&chunk, png))
+            .collect()
+    }
+
+    #[test]
+    fn text_chunks() {
+        let ztxt = [b"Title\0\0".to_vec(), deflate(b"Caf\xE9")].concat();
+        let itxt = InternationalText {
+            keyword: String::from("Author"),
+            is_compressed: true,
+            language: String::from("fr"),
+            translated_keyword: String::from("Auteur"),
+            text: deflate("Zoë".as_bytes()),
+        };
+        let png = build_png(&[
+            build_text_chunk(COMMENT_KEYWORD, "Ol\u{E9}").unwrap(),
+            build_chunk(ZTXT, &ztxt).unwrap(),
+            build_chunk(ITXT, &itxt.get_data()).unwrap(),
+            build_text_chunk("Source", "日本").unwrap(),
+            build_chunk(TEXT, b"no keyword").unwrap(),
+        ]);
+        assert_eq!(
+            get_types(&png),
+            ["IHDR", "tEXt", "zTXt", "iTXt", "iTXt", "tEXt", "IDAT", "IEND"]
+        );
+        let texts = get_texts(&png);
+        assert_eq!(
+            texts[..4],
+            [
+                Ok((String::from("Comment"), String::from("Olé"))),
+                Ok((String::from("Title"), String::from("Café"))),
+                Ok((String::from("Author"), String::from("Zoë"))),
+                Ok((String::from("Source"), String::from("日本"))),
+            ]
+        );
+        assert!(texts[4].is_err());
+
+        let comment = get_chunks(&png).nth(1).unwrap().unwrap();
+        assert!(is_text_chunk(&comment, &png, COMMENT_KEYWORD));
+        assert!(!is_text_chunk(&comment, &png, "Comm"));
+
+        for keyword in ["", " Comment", "Tab\tulation", &"k".repeat(80)] {
+            assert!(build_text_chunk(keyword, "text").is_err());
+        }
+        let ztxt = build_chunk(ZTXT, b"Title\0\x01data").unwrap();
+        assert!(get_texts(&build_png(&[ztxt]))[0].is_err());
+    }
+
+    #[test]
+    fn raw_profiles() {
+        let exif = b"Exif\0\0II*\0\x08\0\0\0\0\0";
+        let hex: String = exif.iter().map(|b| format!("{:02x}", b)).collect();
+        let text = format!(
+            "\nexif\n      {}\n{}\n{}\n",
+            exif.len(),
+            &hex[..20],
+            &hex[20..]
+        );
+        assert_eq!(decode_raw_profile(&text).unwrap(), exif);
+        assert!(decode_raw_profile("\nexif\n   x\n00\n").is_err());
+        assert!(decode_raw_profile("\nexif\n   2\n00\n").is_err());
+        assert!(decode_raw_profile("\nexif\n   1\nzz\n").is_err());
+
+        // The Exif prefix is removed, and the profile can be compressed
+        assert!(is_raw_profile("Raw profile type iptc"));
+        let ztxt = [
+            b"Raw profile type exif\0\0".to_vec(),
+            deflate(text.as_bytes()),
+        ]
+        .concat();
+        let png = build_png(&[build_chunk(ZTXT, &ztxt).unwrap()]);
+        assert_eq!(get_raw_profile_exif(&png), Some(Ok(exif[6..].to_vec())));
+        assert!(get_raw_profile_exif(&build_png(&[])).is_none());
+    }
+}
@@ -0,0 +1,94 @@
+use crate::exif::{TIFFHeader, IFD, TIFF_HEADER_SIZE};
+use crate::tags::Tags;
+
+// See https://exiftool.org/TagNames/ and http://lclevy.free.fr/cr2/
+pub const CR2_SIGNATURE: [u8; 4] = [0x43, 0x52, 0x02, 0x00]; // "CR" followed by the version 2.0
+pub const ORF_SIGNATURES: [[u8; 4]; 3] = [
+    [0x49, 0x49, 0x52, 0x4F], // IIRO
+    [0x49, 0x49, 0x52, 0x53], // IIRS
+    [0x4D, 0x4D, 0x4F, 0x52], // MMOR
+];
+pub const RW2_SIGNATURE: [u8; 4] = [0x49, 0x49, 0x55, 0x00]; // IIU\0
+pub const RAF_SIGNATURE: &[u8; 16] = b"FUJIFILMCCD-RAW ";
+
+// Offset of the embedded JPEG offset and length inside the RAF header
+const RAF_JPEG_OFFSET_POS: usize = 84;
+const RAF_JPEG_LENGTH_POS: usize = 88;
+
+// The CR2 signature is located right after the TIFF header
+pub fn is_cr2(slice: &[u8]) -> bool {
+    slice.len() >= TIFF_HEADER_SIZE + 4
+        && slice[TIFF_HEADER_SIZE..TIFF_HEADER_SIZE + 4] == CR2_SIGNATURE
+}
+
+pub fn is_orf(slice: &[u8]) -> bool {
+    ORF_SIGNATURES
+        .iter()
+        .any(|signature| slice.starts_with(signature))
+}
+
+pub fn is_rw2(slice: &[u8]) -> bool {
+    slice.starts_with(&RW2_SIGNATURE)
+}
+
+pub fn is_raf(slice: &[u8]) -> bool {
+    slice.starts_with(RAF_SIGNATURE)
+}
+
+// Reads the Make tag of the 0th IFD of a TIFF-structured file
+pub fn get_make(slice: &[u8]) -> Option<String> {
+    if slice.len() < TIFF_HEADER_SIZE {
+        return None;
+    }
+
+    let tiff = TIFFHeader::from(slice[0..TIFF_HEADER_SIZE].as_ref());
+    let ifd_0 = IFD::from_tiff(
+        slice,
+        tiff.zero_th_ifd_offset as usize,
+        tiff.is_little_endian,
+    )
+    .ok()?;
+
+    ifd_0
+        .get_interop_for_tag(Tags::Make.0)
+        .and_then(|interop| interop.get_value_ascii(slice))
+}
+
+// RAF files start with their own header (always big endian), which points to an embedded JPEG
+// containing the Exif data
+pub struct RAFHeader {
+    pub jpeg_offset: usize,
+    pub jpeg_length: usize,
+}
+
+impl RAFHeader {
+    pub fn from(slice: &[u8]) -> Option<Self> {
+        if !is_raf(slice) || slice.len() < RAF_JPEG_LENGTH_POS + 4 {
+            return None;
+        }
+
+        let jpeg_offset = u32::from_be_bytes(
+            slice[RAF_JPEG_OFFSET_POS..RAF_JPEG_OFFSET_POS + 4]
+                .try_into()
+                .unwrap(),
+        ) as usize;
+        let jpeg_length = u32::from_be_bytes(
+            slice[RAF_JPEG_LENGTH_POS..RAF_JPEG_LENGTH_POS + 4]
+                .try_into()
+                .unwrap(),
+        ) as usize;
+
+        if jpeg_offset + jpeg_length > slice.len() {
+            return None;
+        }
+
+        Some(Self {
+            jpeg_offset,
+            jpeg_length,
+        })
+    }
+
+    pub fn get_jpeg<'a>(&self, slice: &'a [u8]) -> &'a [u8] {
+        &slice[self.jpeg_offset..self.jpeg_offset + self.jpeg_length]
+    }
+}
@@ -0,0 +1,92 @@
+use csv::StringRecord;
+
+use crate::exif::ExifImage;
+use crate::gps::{is_coordinate_tag, set_coordinate_from_string, DEFAULT_SECONDS_DECIMALS};
+use crate::tags::Tag;
+
+// Name of the column that holds the path of each file
+pub const FILE_COLUMN: &str = "File";
+
+pub fn get_header(tags: &[Tag]) -> Vec<String> {
+    let mut header = vec![String::from(FILE_COLUMN)];
+    header.extend(tags.iter().map(|tag| tag.to_string()));
+    header
+}
+
+// The raw values of the tags, as text that can be edited and read back. Missing tags are empty.
+pub fn get_row(img: &ExifImage, path: &str, tags: &[Tag]) -> Vec<String> {
+    let mut row = vec![path.to_string()];
+    row.extend(
+        tags.iter()
+            .map(|tag| img.get_value_as_text(*tag).unwrap_or_default()),
+    );
+    row
+}
+
+// The position of the file column, and the tag of each other column
+pub fn parse_header(header: &StringRecord) -> Result<(usize, Vec<(usize, Tag)>), String> {
+    let mut file_column = None;
+    let mut columns = Vec::new();
+    for (i, name) in header.iter().enumerate() {
+        if name.eq_ignore_ascii_case(FILE_COLUMN) {
+            file_column = Some(i);
+            continue;
+        }
+        let tag = Tag::from_name(name).ok_or_else(|| format!("Unknown tag {}", name))?;
+        // Only the tags of the 0th, Exif and GPS IFDs can be written
+        if tag.get_ifd_type().is_none() {
+            return Err(format!("{} can't be imported", tag));
+        }
+        columns.push((i, tag));
+    }
+
+    match file_column {
+        Some(file_column) => Ok((file_column, columns)),
+        None => Err(format!("The header has no {} column", FILE_COLUMN)),
+    }
+}
+
+// Writes the cells that differ from the values of the image, an empty cell removing its tag.
+// Every cell is checked, so that all the errors of the row are reported at once. Returns whether
+// the image was modified.
+pub fn apply_row(
+    img: &mut ExifImage,
+    columns: &[(usize, Tag)],
+    record: &StringRecord,
+) -> Result<bool, Vec<String>> {
+    let missing: Vec<String> = columns
+        .iter()
+        .filter(|(i, _)| record.get(*i).is_none())
+        .map(|(_, tag)| tag.to_string())
+        .collect();
+    if !missing.is_empty() {
+        return Err(vec![format!("Missing cells for {}", missing.join(", "))]);
+    }
+
+    let mut errors = Vec::new();
+    let mut is_modified = false;
+    for (i, tag) in columns {
+        let cell = &record[*i];
+        let value = img.get_value_as_text(*tag);
+        if value.as_deref().unwrap_or_default() == cell {
+            continue;
+        }
+
+        let result = match (cell, tag.get_ifd_type()) {
+            ("", Some(ifd_type)) => img.remove_tag(ifd_type, *tag),
+            _ if is_coordinate_tag(*tag) => {
+                set_coordinate_from_string(img, *tag, cell, DEFAULT_SECONDS_DECIMALS)
+            }
+            _ => img.set_tag_from_string(*tag, cell),
+        };
+        match result {
+            Ok(()) => is_modified = true,
+            Err(e) => errors.push(e),
+        }
+    }
+
+    match errors.is_empty() {
+        true => Ok(is_modified),
+        false => Err(errors),
+    }
+}
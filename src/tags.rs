@@ -1,8 +1,9 @@
 use core::fmt;
 
 use crate::arrays::{get_tuples_vec_as_string, get_vec_as_string};
+use crate::exif::{ExifTypes, IFDTypes};
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy)]
 pub struct Tag(pub usize);
 
 #[allow(non_snake_case)]
@@ -14,9 +15,11 @@ pub mod Tags {
     pub const ExifOffset: Tag = Tag(34665);
     pub const GPSOffset: Tag = Tag(34853);
     pub const InteroperabilityIFD: Tag = Tag(40965);
+    pub const SubIFDs: Tag = Tag(330);
 
     // TIFF
     // Image Data Structure
+    pub const NewSubfileType: Tag = Tag(254);
     pub const ImageWidth: Tag = Tag(256);
     pub const ImageLength: Tag = Tag(257);
     pub const BitsPerSample: Tag = Tag(258);
@@ -53,6 +56,18 @@ pub mod Tags {
     pub const Software: Tag = Tag(305);
     pub const Artist: Tag = Tag(315);
     pub const Copyright: Tag = Tag(33432);
+    pub const XMLPacket: Tag = Tag(700); // XMP packet
+    pub const InterColorProfile: Tag = Tag(34675); // ICC profile
+
+    // Raw formats
+    pub const JpgFromRaw: Tag = Tag(46); // Panasonic RW2
+
+    // Multi-Picture Format (CIPA DC-007)
+    pub const MPFVersion: Tag = Tag(45056);
+    pub const NumberOfImages: Tag = Tag(45057);
+    pub const MPEntry: Tag = Tag(45058);
+    pub const ImageUIDList: Tag = Tag(45059);
+    pub const TotalFrames: Tag = Tag(45060);
 
     ////////////////////////////////////////////////////////////////////////////
     // Exif
@@ -193,169 +208,482 @@ pub mod Tags {
     pub const GPSHPositioningError: Tag = Tag(31);
 }
 
+// Names of the tags, as they are displayed and as they are given on the command line
+const TAG_NAMES: &[(Tag, &str)] = &[
+    (Tags::ExifOffset, "ExifOffset"),
+    (Tags::GPSOffset, "GPSOffset"),
+    (Tags::InteroperabilityIFD, "InteroperabilityIFD"),
+    (Tags::SubIFDs, "SubIFDs"),
+    (Tags::NewSubfileType, "NewSubfileType"),
+    (Tags::ImageWidth, "ImageWidth"),
+    (Tags::ImageLength, "ImageLength"),
+    (Tags::BitsPerSample, "BitsPerSample"),
+    (Tags::Compression, "Compression"),
+    (Tags::PhotometricInterpretation, "PhotometricInterpretation"),
+    (Tags::Orientation, "Orientation"),
+    (Tags::SamplesPerPixel, "SamplesPerPixel"),
+    (Tags::PlanarConfiguration, "PlanarConfiguration"),
+    (Tags::YCbCrSubSampling, "YCbCrSubSampling"),
+    (Tags::YCbCrPositioning, "YCbCrPositioning"),
+    (Tags::XResolution, "XResolution"),
+    (Tags::YResolution, "YResolution"),
+    (Tags::ResolutionUnit, "ResolutionUnit"),
+    (Tags::StripOffsets, "StripOffsets"),
+    (Tags::RowsPerStrip, "RowsPerStrip"),
+    (Tags::StripByteCounts, "StripByteCounts"),
+    (Tags::JPEGInterchangeFormat, "JPEGInterchangeFormat"),
+    (
+        Tags::JPEGInterchangeFormatLength,
+        "JPEGInterchangeFormatLength",
+    ),
+    (Tags::TransferFunction, "TransferFunction"),
+    (Tags::WhitePoint, "WhitePoint"),
+    (Tags::PrimaryChromaticities, "PrimaryChromaticities"),
+    (Tags::YCbCrCoefficients, "YCbCrCoefficients"),
+    (Tags::ReferenceBlackWhite, "ReferenceBlackWhite"),
+    (Tags::DateTime, "DateTime"),
+    (Tags::ImageDescription, "ImageDescription"),
+    (Tags::Make, "Make"),
+    (Tags::Model, "Model"),
+    (Tags::Software, "Software"),
+    (Tags::Artist, "Artist"),
+    (Tags::Copyright, "Copyright"),
+    (Tags::XMLPacket, "XMLPacket"),
+    (Tags::InterColorProfile, "InterColorProfile"),
+    (Tags::JpgFromRaw, "JpgFromRaw"),
+    (Tags::MPFVersion, "MPFVersion"),
+    (Tags::NumberOfImages, "NumberOfImages"),
+    (Tags::MPEntry, "MPEntry"),
+    (Tags::ImageUIDList, "ImageUIDList"),
+    (Tags::TotalFrames, "TotalFrames"),
+    (Tags::ExifVersion, "ExifVersion"),
+    (Tags::FlashpixVersion, "FlashpixVersion"),
+    (Tags::ColorSpace, "ColorSpace"),
+    (Tags::Gamma, "Gamma"),
+    (Tags::ComponentsConfiguration, "ComponentsConfiguration"),
+    (Tags::CompressedBitsPerPixel, "CompressedBitsPerPixel"),
+    (Tags::PixelXDimension, "PixelXDimension"),
+    (Tags::PixelYDimension, "PixelYDimension"),
+    (Tags::MakerNote, "MakerNote"),
+    (Tags::UserComment, "UserComment"),
+    (Tags::RelatedSoundFile, "RelatedSoundFile"),
+    (Tags::DateTimeOriginal, "DateTimeOriginal"),
+    (Tags::DateTimeDigitized, "DateTimeDigitized"),
+    (Tags::OffsetTime, "OffsetTime"),
+    (Tags::OffsetTimeOriginal, "OffsetTimeOriginal"),
+    (Tags::OffsetTimeDigitized, "OffsetTimeDigitized"),
+    (Tags::SubSecTime, "SubSecTime"),
+    (Tags::SubSecTimeOriginal, "SubSecTimeOriginal"),
+    (Tags::SubSecTimeDigitized, "SubSecTimeDigitized"),
+    (Tags::ExposureTime, "ExposureTime"),
+    (Tags::FNumber, "FNumber"),
+    (Tags::ExposureProgram, "ExposureProgram"),
+    (Tags::SpectralSensitivity, "SpectralSensitivity"),
+    (Tags::PhotographicSensitivity, "PhotographicSensitivity"),
+    (Tags::OECF, "OECF"),
+    (Tags::SensitivityType, "SensitivityType"),
+    (Tags::StandardOutputSensitivity, "StandardOutputSensitivity"),
+    (Tags::RecommendedExposureIndex, "RecommendedExposureIndex"),
+    (Tags::ISOSpeed, "ISOSpeed"),
+    (Tags::ISOSpeedLatitudeyyy, "ISOSpeedLatitudeyyy"),
+    (Tags::ISOSpeedLatitudezzz, "ISOSpeedLatitudezzz"),
+    (Tags::ShutterSpeedValue, "ShutterSpeedValue"),
+    (Tags::ApertureValue, "ApertureValue"),
+    (Tags::BrightnessValue, "BrightnessValue"),
+    (Tags::ExposureBiasValue, "ExposureBiasValue"),
+    (Tags::MaxApertureValue, "MaxApertureValue"),
+    (Tags::SubjectDistance, "SubjectDistance"),
+    (Tags::MeteringMode, "MeteringMode"),
+    (Tags::LightSource, "LightSource"),
+    (Tags::Flash, "Flash"),
+    (Tags::FocalLength, "FocalLength"),
+    (Tags::SubjectArea, "SubjectArea"),
+    (Tags::FlashEnergy, "FlashEnergy"),
+    (Tags::SpatialFrequencyResponse, "SpatialFrequencyResponse"),
+    (Tags::FocalPlaneXResolution, "FocalPlaneXResolution"),
+    (Tags::FocalPlaneYResolution, "FocalPlaneYResolution"),
+    (Tags::FocalPlaneResolutionUnit, "FocalPlaneResolutionUnit"),
+    (Tags::SubjectLocation, "SubjectLocation"),
+    (Tags::ExposureIndex, "ExposureIndex"),
+    (Tags::SensingMethod, "SensingMethod"),
+    (Tags::FileSource, "FileSource"),
+    (Tags::SceneType, "SceneType"),
+    (Tags::CFAPattern, "CFAPattern"),
+    (Tags::CustomRendered, "CustomRendered"),
+    (Tags::ExposureMode, "ExposureMode"),
+    (Tags::WhiteBalance, "WhiteBalance"),
+    (Tags::DigitalZoomRatio, "DigitalZoomRatio"),
+    (Tags::FocalLengthIn35mmFilm, "FocalLengthIn35mmFilm"),
+    (Tags::SceneCaptureType, "SceneCaptureType"),
+    (Tags::GainControl, "GainControl"),
+    (Tags::Contrast, "Contrast"),
+    (Tags::Saturation, "Saturation"),
+    (Tags::Sharpness, "Sharpness"),
+    (Tags::DeviceSettingDescription, "DeviceSettingDescription"),
+    (Tags::SubjectDistanceRange, "SubjectDistanceRange"),
+    (Tags::CompositeImage, "CompositeImage"),
+    (
+        Tags::SourceImageNumberOfCompositeImage,
+        "SourceImageNumberOfCompositeImage",
+    ),
+    (
+        Tags::SourceExposureTimesOfCompositeImage,
+        "SourceExposureTimesOfCompositeImage",
+    ),
+    (Tags::Temperature, "Temperature"),
+    (Tags::Humidity, "Humidity"),
+    (Tags::Pressure, "Pressure"),
+    (Tags::WaterDepth, "WaterDepth"),
+    (Tags::Acceleration, "Acceleration"),
+    (Tags::CameraElevationAngle, "CameraElevationAngle"),
+    (Tags::ImageUniqueID, "ImageUniqueID"),
+    (Tags::CameraOwnerName, "CameraOwnerName"),
+    (Tags::BodySerialNumber, "BodySerialNumber"),
+    (Tags::LensSpecification, "LensSpecification"),
+    (Tags::LensMake, "LensMake"),
+    (Tags::LensModel, "LensModel"),
+    (Tags::LensSerialNumber, "LensSerialNumber"),
+    (Tags::GPSVersionID, "GPSVersionID"),
+    (Tags::GPSLatitudeRef, "GPSLatitudeRef"),
+    (Tags::GPSLatitude, "GPSLatitude"),
+    (Tags::GPSLongitudeRef, "GPSLongitudeRef"),
+    (Tags::GPSLongitude, "GPSLongitude"),
+    (Tags::GPSAltitudeRef, "GPSAltitudeRef"),
+    (Tags::GPSAltitude, "GPSAltitude"),
+    (Tags::GPSTimeStamp, "GPSTimeStamp"),
+    (Tags::GPSSatellites, "GPSSatellites"),
+    (Tags::GPSStatus, "GPSStatus"),
+    (Tags::GPSMeasureMode, "GPSMeasureMode"),
+    (Tags::GPSDOP, "GPSDOP"),
+    (Tags::GPSSpeedRef, "GPSSpeedRef"),
+    (Tags::GPSSpeed, "GPSSpeed"),
+    (Tags::GPSTrackRef, "GPSTrackRef"),
+    (Tags::GPSTrack, "GPSTrack"),
+    (Tags::GPSImgDirectionRef, "GPSImgDirectionRef"),
+    (Tags::GPSImgDirection, "GPSImgDirection"),
+    (Tags::GPSMapDatum, "GPSMapDatum"),
+    (Tags::GPSDestLatitudeRef, "GPSDestLatitudeRef"),
+    (Tags::GPSDestLatitude, "GPSDestLatitude"),
+    (Tags::GPSDestLongitudeRef, "GPSDestLongitudeRef"),
+    (Tags::GPSDestLongitude, "GPSDestLongitude"),
+    (Tags::GPSDestBearingRef, "GPSDestBearingRef"),
+    (Tags::GPSDestBearing, "GPSDestBearing"),
+    (Tags::GPSDestDistanceRef, "GPSDestDistanceRef"),
+    (Tags::GPSDestDistance, "GPSDestDistance"),
+    (Tags::GPSProcessingMethod, "GPSProcessingMethod"),
+    (Tags::GPSAreaInformation, "GPSAreaInformation"),
+    (Tags::GPSDateStamp, "GPSDateStamp"),
+    (Tags::GPSDifferential, "GPSDifferential"),
+    (Tags::GPSHPositioningError, "GPSHPositioningError"),
+];
+
+// Names that exiftool gives to the tags, when they differ from the ones of the specs
+const EXIFTOOL_NAMES: &[(Tag, &str)] = &[
+    (Tags::GPSOffset, "GPSInfo"),
+    (Tags::InteroperabilityIFD, "InteropOffset"),
+    (Tags::NewSubfileType, "SubfileType"),
+    (Tags::ImageLength, "ImageHeight"),
+    (Tags::JPEGInterchangeFormat, "ThumbnailOffset"),
+    (Tags::JPEGInterchangeFormatLength, "ThumbnailLength"),
+    (Tags::DateTime, "ModifyDate"),
+    (Tags::XMLPacket, "ApplicationNotes"),
+    (Tags::InterColorProfile, "ICC_Profile"),
+    (Tags::PixelXDimension, "ExifImageWidth"),
+    (Tags::PixelYDimension, "ExifImageHeight"),
+    (Tags::DateTimeDigitized, "CreateDate"),
+    (Tags::PhotographicSensitivity, "ISO"),
+    (Tags::OECF, "Opto-ElectricConvFactor"),
+    (Tags::ExposureBiasValue, "ExposureCompensation"),
+    (Tags::FocalLengthIn35mmFilm, "FocalLengthIn35mmFormat"),
+    (
+        Tags::SourceImageNumberOfCompositeImage,
+        "CompositeImageCount",
+    ),
+    (
+        Tags::SourceExposureTimesOfCompositeImage,
+        "CompositeImageExposureTimes",
+    ),
+    (Tags::Temperature, "AmbientTemperature"),
+    (Tags::CameraOwnerName, "OwnerName"),
+    (Tags::BodySerialNumber, "SerialNumber"),
+    (Tags::LensSpecification, "LensInfo"),
+];
+
+// The alternate form (`{:#}`) follows the group:name convention of exiftool, as in
+// `EXIF:ExposureTime` or `GPS:GPSLatitude`
 impl fmt::Display for Tag {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            match *self {
-                Tags::ExifOffset => "ExifOffset",
-                Tags::GPSOffset => "GPSOffset",
-                Tags::InteroperabilityIFD => "InteroperabilityIFD",
-                Tags::ImageWidth => "ImageWidth",
-                Tags::ImageLength => "ImageLength",
-                Tags::BitsPerSample => "BitsPerSample",
-                Tags::Compression => "Compression",
-                Tags::PhotometricInterpretation => "PhotometricInterpretation",
-                Tags::Orientation => "Orientation",
-                Tags::SamplesPerPixel => "SamplesPerPixel",
-                Tags::PlanarConfiguration => "PlanarConfiguration",
-                Tags::YCbCrSubSampling => "YCbCrSubSampling",
-                Tags::YCbCrPositioning => "YCbCrPositioning",
-                Tags::XResolution => "XResolution",
-                Tags::YResolution => "YResolution",
-                Tags::ResolutionUnit => "ResolutionUnit",
-                Tags::StripOffsets => "StripOffsets",
-                Tags::RowsPerStrip => "RowsPerStrip",
-                Tags::StripByteCounts => "StripByteCounts",
-                Tags::JPEGInterchangeFormat => "JPEGInterchangeFormat",
-                Tags::JPEGInterchangeFormatLength => "JPEGInterchangeFormatLength",
-                Tags::TransferFunction => "TransferFunction",
-                Tags::WhitePoint => "WhitePoint",
-                Tags::PrimaryChromaticities => "PrimaryChromaticities",
-                Tags::YCbCrCoefficients => "YCbCrCoefficients",
-                Tags::ReferenceBlackWhite => "ReferenceBlackWhite",
-                Tags::DateTime => "DateTime",
-                Tags::ImageDescription => "ImageDescription",
-                Tags::Make => "Make",
-                Tags::Model => "Model",
-                Tags::Software => "Software",
-                Tags::Artist => "Artist",
-                Tags::Copyright => "Copyright",
-                Tags::ExifVersion => "ExifVersion",
-                Tags::FlashpixVersion => "FlashpixVersion",
-                Tags::ColorSpace => "ColorSpace",
-                Tags::Gamma => "Gamma",
-                Tags::ComponentsConfiguration => "ComponentsConfiguration",
-                Tags::CompressedBitsPerPixel => "CompressedBitsPerPixel",
-                Tags::PixelXDimension => "PixelXDimension",
-                Tags::PixelYDimension => "PixelYDimension",
-                Tags::MakerNote => "MakerNote",
-                Tags::UserComment => "UserComment",
-                Tags::RelatedSoundFile => "RelatedSoundFile",
-                Tags::DateTimeOriginal => "DateTimeOriginal",
-                Tags::DateTimeDigitized => "DateTimeDigitized",
-                Tags::OffsetTime => "OffsetTime",
-                Tags::OffsetTimeOriginal => "OffsetTimeOriginal",
-                Tags::OffsetTimeDigitized => "OffsetTimeDigitized",
-                Tags::SubSecTime => "SubSecTime",
-                Tags::SubSecTimeOriginal => "SubSecTimeOriginal",
-                Tags::SubSecTimeDigitized => "SubSecTimeDigitized",
-                Tags::ExposureTime => "ExposureTime",
-                Tags::FNumber => "FNumber",
-                Tags::ExposureProgram => "ExposureProgram",
-                Tags::SpectralSensitivity => "SpectralSensitivity",
-                Tags::PhotographicSensitivity => "PhotographicSensitivity",
-                Tags::OECF => "OECF",
-                Tags::SensitivityType => "SensitivityType",
-                Tags::StandardOutputSensitivity => "StandardOutputSensitivity",
-                Tags::RecommendedExposureIndex => "RecommendedExposureIndex",
-                Tags::ISOSpeed => "ISOSpeed",
-                Tags::ISOSpeedLatitudeyyy => "ISOSpeedLatitudeyyy",
-                Tags::ISOSpeedLatitudezzz => "ISOSpeedLatitudezzz",
-                Tags::ShutterSpeedValue => "ShutterSpeedValue",
-                Tags::ApertureValue => "ApertureValue",
-                Tags::BrightnessValue => "BrightnessValue",
-                Tags::ExposureBiasValue => "ExposureBiasValue",
-                Tags::MaxApertureValue => "MaxApertureValue",
-                Tags::SubjectDistance => "SubjectDistance",
-                Tags::MeteringMode => "MeteringMode",
-                Tags::LightSource => "LightSource",
-                Tags::Flash => "Flash",
-                Tags::FocalLength => "FocalLength",
-                Tags::SubjectArea => "SubjectArea",
-                Tags::FlashEnergy => "FlashEnergy",
-                Tags::SpatialFrequencyResponse => "SpatialFrequencyResponse",
-                Tags::FocalPlaneXResolution => "FocalPlaneXResolution",
-                Tags::FocalPlaneYResolution => "FocalPlaneYResolution",
-                Tags::FocalPlaneResolutionUnit => "FocalPlaneResolutionUnit",
-                Tags::SubjectLocation => "SubjectLocation",
-                Tags::ExposureIndex => "ExposureIndex",
-                Tags::SensingMethod => "SensingMethod",
-                Tags::FileSource => "FileSource",
-                Tags::SceneType => "SceneType",
-                Tags::CFAPattern => "CFAPattern",
-                Tags::CustomRendered => "CustomRendered",
-                Tags::ExposureMode => "ExposureMode",
-                Tags::WhiteBalance => "WhiteBalance",
-                Tags::DigitalZoomRatio => "DigitalZoomRatio",
-                Tags::FocalLengthIn35mmFilm => "FocalLengthIn35mmFilm",
-                Tags::SceneCaptureType => "SceneCaptureType",
-                Tags::GainControl => "GainControl",
-                Tags::Contrast => "Contrast",
-                Tags::Saturation => "Saturation",
-                Tags::Sharpness => "Sharpness",
-                Tags::DeviceSettingDescription => "DeviceSettingDescription",
-                Tags::SubjectDistanceRange => "SubjectDistanceRange",
-                Tags::CompositeImage => "CompositeImage",
-                Tags::SourceImageNumberOfCompositeImage => "SourceImageNumberOfCompositeImage",
-                Tags::SourceExposureTimesOfCompositeImage => "SourceExposureTimesOfCompositeImage",
-                Tags::Temperature => "Temperature",
-                Tags::Humidity => "Humidity",
-                Tags::Pressure => "Pressure",
-                Tags::WaterDepth => "WaterDepth",
-                Tags::Acceleration => "Acceleration",
-                Tags::CameraElevationAngle => "CameraElevationAngle",
-                Tags::ImageUniqueID => "ImageUniqueID",
-                Tags::CameraOwnerName => "CameraOwnerName",
-                Tags::BodySerialNumber => "BodySerialNumber",
-                Tags::LensSpecification => "LensSpecification",
-                Tags::LensMake => "LensMake",
-                Tags::LensModel => "LensModel",
-                Tags::LensSerialNumber => "LensSerialNumber",
-                Tags::GPSVersionID => "GPSVersionID",
-                Tags::GPSLatitudeRef => "GPSLatitudeRef",
-                Tags::GPSLatitude => "GPSLatitude",
-                Tags::GPSLongitudeRef => "GPSLongitudeRef",
-                Tags::GPSLongitude => "GPSLongitude",
-                Tags::GPSAltitudeRef => "GPSAltitudeRef",
-                Tags::GPSAltitude => "GPSAltitude",
-                Tags::GPSTimeStamp => "GPSTimeStamp",
-                Tags::GPSSatellites => "GPSSatellites",
-                Tags::GPSStatus => "GPSStatus",
-                Tags::GPSMeasureMode => "GPSMeasureMode",
-                Tags::GPSDOP => "GPSDOP",
-                Tags::GPSSpeedRef => "GPSSpeedRef",
-                Tags::GPSSpeed => "GPSSpeed",
-                Tags::GPSTrackRef => "GPSTrackRef",
-                Tags::GPSTrack => "GPSTrack",
-                Tags::GPSImgDirectionRef => "GPSImgDirectionRef",
-                Tags::GPSImgDirection => "GPSImgDirection",
-                Tags::GPSMapDatum => "GPSMapDatum",
-                Tags::GPSDestLatitudeRef => "GPSDestLatitudeRef",
-                Tags::GPSDestLatitude => "GPSDestLatitude",
-                Tags::GPSDestLongitudeRef => "GPSDestLongitudeRef",
-                Tags::GPSDestLongitude => "GPSDestLongitude",
-                Tags::GPSDestBearingRef => "GPSDestBearingRef",
-                Tags::GPSDestBearing => "GPSDestBearing",
-                Tags::GPSDestDistanceRef => "GPSDestDistanceRef",
-                Tags::GPSDestDistance => "GPSDestDistance",
-                Tags::GPSProcessingMethod => "GPSProcessingMethod",
-                Tags::GPSAreaInformation => "GPSAreaInformation",
-                Tags::GPSDateStamp => "GPSDateStamp",
-                Tags::GPSDifferential => "GPSDifferential",
-                Tags::GPSHPositioningError => "GPSHPositioningError",
-                _ => "unknown",
-            }
-        )
+        if f.alternate() {
+            let group = match self.get_ifd_type() {
+                Some(IFDTypes::GPS) => "GPS",
+                _ if (45056..=45060).contains(&self.0) => "MPF",
+                _ => "EXIF",
+            };
+            return match EXIFTOOL_NAMES
+                .iter()
+                .chain(TAG_NAMES)
+                .find(|(tag, _)| tag == self)
+            {
+                Some((_, name)) => write!(f, "{}:{}", group, name),
+                None => write!(f, "{}:Exif_0x{:04x}", group, self.0),
+            };
+        }
+
+        match TAG_NAMES.iter().find(|(tag, _)| tag == self) {
+            Some((_, name)) => write!(f, "{}", name),
+            None => write!(f, "Tag 0x{:04X}", self.0),
+        }
     }
 }
 
 impl Tag {
+    // The Interoperability IFD reuses the ids of the GPS tags for its own ones
+    pub fn get_interop_name(self) -> Option<&'static str> {
+        match self.0 {
+            1 => Some("InteropIndex"),
+            2 => Some("InteropVersion"),
+            _ => None,
+        }
+    }
+
+    // Case is ignored, so that `artist` works as well as `Artist`
+    pub fn from_name(name: &str) -> Option<Tag> {
+        TAG_NAMES
+            .iter()
+            .find(|(_, tag_name)| tag_name.eq_ignore_ascii_case(name))
+            .map(|(tag, _)| *tag)
+    }
+
+    // Names as exiftool writes them, with an optional group (`EXIF:ExposureTime`, `GPS:GPSLatitude`,
+    // `ExifIFD:ISO`...) that must match the IFD of the tag. The `#` suffix that asks exiftool for
+    // the raw value is ignored.
+    pub fn from_exiftool_name(name: &str) -> Option<Tag> {
+        let (group, name) = match name.split_once(':') {
+            Some((group, name)) => (Some(group), name),
+            None => (None, name),
+        };
+        let name = name.strip_suffix('#').unwrap_or(name);
+        let tag = match EXIFTOOL_NAMES
+            .iter()
+            .find(|(_, tag_name)| tag_name.eq_ignore_ascii_case(name))
+        {
+            Some((tag, _)) => *tag,
+            None => match name.get(..7) {
+                Some(prefix) if prefix.eq_ignore_ascii_case("Exif_0x") => {
+                    Tag(usize::from_str_radix(&name[7..], 16).ok()?)
+                }
+                _ => Tag::from_name(name)?,
+            },
+        };
+
+        let ifd_type = tag.get_ifd_type();
+        let is_in_group = match group.map(|group| group.to_ascii_uppercase()) {
+            None => true,
+            Some(group) => match group.as_str() {
+                "EXIF" => true,
+                "IFD0" => ifd_type == Some(IFDTypes::TIFF),
+                "EXIFIFD" => ifd_type == Some(IFDTypes::Exif),
+                "GPS" => ifd_type == Some(IFDTypes::GPS),
+                "MPF" => (45056..=45060).contains(&tag.0),
+                _ => false,
+            },
+        };
+        is_in_group.then_some(tag)
+    }
+
+    pub fn is_known(self) -> bool {
+        TAG_NAMES.iter().any(|(tag, _)| *tag == self)
+    }
+
+    // Tags whose values are offsets in the file, which change whenever it is rewritten
+    pub fn is_offset(self) -> bool {
+        matches!(
+            self,
+            Tags::ExifOffset
+                | Tags::GPSOffset
+                | Tags::InteroperabilityIFD
+                | Tags::SubIFDs
+                | Tags::StripOffsets
+                | Tags::JPEGInterchangeFormat
+        )
+    }
+
+    // The IFD the tag belongs to. The tags that describe the structure of the file (pointers to
+    // other IFDs, offsets of the image data, embedded packets) can't be edited and have none.
+    pub fn get_ifd_type(self) -> Option<IFDTypes> {
+        match self {
+            Tags::ExifOffset
+            | Tags::GPSOffset
+            | Tags::InteroperabilityIFD
+            | Tags::SubIFDs
+            | Tags::StripOffsets
+            | Tags::StripByteCounts
+            | Tags::JPEGInterchangeFormat
+            | Tags::JPEGInterchangeFormatLength
+            | Tags::XMLPacket
+            | Tags::InterColorProfile
+            | Tags::JpgFromRaw => None,
+            // The MP Index IFD is in its own APP2 segment
+            Tag(45056..=45060) => None,
+            Tag(0..=31) => Some(IFDTypes::GPS),
+            Tag(33434..=34869) | Tag(36864..=37893) | Tag(40960..=42240) => Some(IFDTypes::Exif),
+            _ => Some(IFDTypes::TIFF),
+        }
+    }
+
+    // The type the specs give to the tag, used when it is added to a file that doesn't have it.
+    // Tags that can be either SHORT or LONG are SHORT.
+    pub fn get_data_type(self) -> Option<ExifTypes> {
+        match self {
+            Tags::ImageDescription
+            | Tags::Make
+            | Tags::Model
+            | Tags::Software
+            | Tags::Artist
+            | Tags::Copyright
+            | Tags::DateTime
+            | Tags::DateTimeOriginal
+            | Tags::DateTimeDigitized
+            | Tags::OffsetTime
+            | Tags::OffsetTimeOriginal
+            | Tags::OffsetTimeDigitized
+            | Tags::SubSecTime
+            | Tags::SubSecTimeOriginal
+            | Tags::SubSecTimeDigitized
+            | Tags::SpectralSensitivity
+            | Tags::RelatedSoundFile
+            | Tags::ImageUniqueID
+            | Tags::CameraOwnerName
+            | Tags::BodySerialNumber
+            | Tags::LensMake
+            | Tags::LensModel
+            | Tags::LensSerialNumber
+            | Tags::GPSLatitudeRef
+            | Tags::GPSLongitudeRef
+            | Tags::GPSSatellites
+            | Tags::GPSStatus
+            | Tags::GPSMeasureMode
+            | Tags::GPSSpeedRef
+            | Tags::GPSTrackRef
+            | Tags::GPSImgDirectionRef
+            | Tags::GPSMapDatum
+            | Tags::GPSDestLatitudeRef
+            | Tags::GPSDestLongitudeRef
+            | Tags::GPSDestBearingRef
+            | Tags::GPSDestDistanceRef
+            | Tags::GPSDateStamp => Some(ExifTypes::Ascii),
+            Tags::ImageWidth
+            | Tags::ImageLength
+            | Tags::BitsPerSample
+            | Tags::Compression
+            | Tags::PhotometricInterpretation
+            | Tags::Orientation
+            | Tags::SamplesPerPixel
+            | Tags::RowsPerStrip
+            | Tags::PlanarConfiguration
+            | Tags::YCbCrSubSampling
+            | Tags::YCbCrPositioning
+            | Tags::ResolutionUnit
+            | Tags::TransferFunction
+            | Tags::ExposureProgram
+            | Tags::PhotographicSensitivity
+            | Tags::SensitivityType
+            | Tags::MeteringMode
+            | Tags::LightSource
+            | Tags::Flash
+            | Tags::SubjectArea
+            | Tags::FocalPlaneResolutionUnit
+            | Tags::SubjectLocation
+            | Tags::SensingMethod
+            | Tags::CustomRendered
+            | Tags::ExposureMode
+            | Tags::WhiteBalance
+            | Tags::FocalLengthIn35mmFilm
+            | Tags::SceneCaptureType
+            | Tags::GainControl
+            | Tags::Contrast
+            | Tags::Saturation
+            | Tags::Sharpness
+            | Tags::SubjectDistanceRange
+            | Tags::ColorSpace
+            | Tags::PixelXDimension
+            | Tags::PixelYDimension
+            | Tags::CompositeImage
+            | Tags::SourceImageNumberOfCompositeImage
+            | Tags::GPSDifferential => Some(ExifTypes::Short),
+            Tags::NewSubfileType
+            | Tags::StandardOutputSensitivity
+            | Tags::RecommendedExposureIndex
+            | Tags::ISOSpeed
+            | Tags::ISOSpeedLatitudeyyy
+            | Tags::ISOSpeedLatitudezzz => Some(ExifTypes::Long),
+            Tags::XResolution
+            | Tags::YResolution
+            | Tags::WhitePoint
+            | Tags::PrimaryChromaticities
+            | Tags::YCbCrCoefficients
+            | Tags::ReferenceBlackWhite
+            | Tags::ExposureTime
+            | Tags::FNumber
+            | Tags::CompressedBitsPerPixel
+            | Tags::ApertureValue
+            | Tags::MaxApertureValue
+            | Tags::SubjectDistance
+            | Tags::FocalLength
+            | Tags::FlashEnergy
+            | Tags::FocalPlaneXResolution
+            | Tags::FocalPlaneYResolution
+            | Tags::ExposureIndex
+            | Tags::DigitalZoomRatio
+            | Tags::Gamma
+            | Tags::Humidity
+            | Tags::Pressure
+            | Tags::Acceleration
+            | Tags::LensSpecification
+            | Tags::GPSLatitude
+            | Tags::GPSLongitude
+            | Tags::GPSAltitude
+            | Tags::GPSTimeStamp
+            | Tags::GPSDOP
+            | Tags::GPSSpeed
+            | Tags::GPSTrack
+            | Tags::GPSImgDirection
+            | Tags::GPSDestLatitude
+            | Tags::GPSDestLongitude
+            | Tags::GPSDestBearing
+            | Tags::GPSDestDistance
+            | Tags::GPSHPositioningError => Some(ExifTypes::Rational),
+            Tags::ShutterSpeedValue
+            | Tags::BrightnessValue
+            | Tags::ExposureBiasValue
+            | Tags::Temperature
+            | Tags::WaterDepth
+            | Tags::CameraElevationAngle => Some(ExifTypes::Srational),
+            Tags::GPSVersionID | Tags::GPSAltitudeRef => Some(ExifTypes::Byte),
+            Tags::ExifVersion
+            | Tags::FlashpixVersion
+            | Tags::ComponentsConfiguration
+            | Tags::MakerNote
+            | Tags::UserComment
+            | Tags::OECF
+            | Tags::SpatialFrequencyResponse
+            | Tags::FileSource
+            | Tags::SceneType
+            | Tags::CFAPattern
+            | Tags::DeviceSettingDescription
+            | Tags::SourceExposureTimesOfCompositeImage
+            | Tags::GPSProcessingMethod
+            | Tags::GPSAreaInformation => Some(ExifTypes::Undefined),
+            _ => None,
+        }
+    }
+
     pub fn get_byte_string(self, count: usize, values: Vec<u8>) -> String {
         let tag = self;
 
-        if tag == Tags::GPSVersionID && count == 4 && values.len() == 4 {
+        if tag == Tags::XMLPacket {
+            format!("XMP packet ({} bytes)", count)
+        } else if tag == Tags::GPSVersionID && count == 4 && values.len() == 4 {
             if values[0] == 2 && values[1] == 3 && values[2] == 0 && values[3] == 0 {
                 "Version 2.3"
             } else {
@@ -666,10 +994,10 @@ impl Tag {
                 1 => "Differential correction applied",
                 _ => "reserved",
             },
-            _=>"",
+            _=>return get_vec_as_string(values),
         }.to_string()
         } else {
-            "".to_string()
+            get_vec_as_string(values)
         }
     }
 
@@ -692,7 +1020,11 @@ impl Tag {
     ) -> String {
         let tag = self;
 
-        if tag == Tags::FlashpixVersion
+        if tag == Tags::XMLPacket {
+            format!("XMP packet ({} bytes)", count)
+        } else if tag == Tags::InterColorProfile {
+            format!("ICC profile ({} bytes)", count)
+        } else if tag == Tags::FlashpixVersion
             && count == 4
             && value_offset[0] == 48
             && value_offset[1] == 49
@@ -764,8 +1096,11 @@ impl Tag {
             .to_string()
         } else if count == 4 {
             match tag {
-                Tags::ExifVersion => String::from_iter(value_offset.iter().map(|b| *b as char)),
-                Tags::OECF => todo!(),
+                Tags::ExifVersion | Tags::MPFVersion => {
+                    String::from_iter(value_offset.iter().map(|b| *b as char))
+                }
+                // The raw bytes, as the 4 bytes can't hold the columns and rows of an OECF table
+                Tags::OECF => get_vec_as_string(value_offset.to_vec()),
                 _ => "Undefined".to_string(),
             }
             .to_string()
@@ -0,0 +1,234 @@
+use crate::datetime::DateTime;
+use crate::exif::ExifImage;
+use crate::tags::{Tag, Tags};
+
+// Characters that can't be part of a file name on some systems
+const INVALID_CHARS: [char; 9] = ['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+// The fields that give a part of the date of the image
+const DATE_FIELDS: [(&str, &str); 6] = [
+    ("year", "%Y"),
+    ("month", "%m"),
+    ("day", "%d"),
+    ("hour", "%H"),
+    ("minute", "%M"),
+    ("second", "%S"),
+];
+
+// The tags that hold when the picture was taken, in order of preference
+const DATE_TAGS: [Tag; 3] = [
+    Tags::DateTimeOriginal,
+    Tags::DateTimeDigitized,
+    Tags::DateTime,
+];
+
+enum Part {
+    Text(String),
+    Tag(Tag, Option<String>), // With the strftime format of a date
+    Date(String),             // With its strftime format
+    Counter(usize),           // With the number of digits
+}
+
+// The values of the fields that don't come from the tags
+pub struct Context {
+    pub counter: usize,
+    pub date: Option<DateTime>,
+    pub missing: Option<String>, // Replaces the missing tags, which are errors otherwise
+}
+
+// A file name built from the values of the tags, such as
+// `{DateTimeOriginal:%Y-%m-%d_%H%M%S}_{Model}_{counter:4}`. Braces are escaped by doubling them.
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut field = String::new();
+                    let mut is_closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            is_closed = true;
+                            break;
+                        }
+                        field.push(c);
+                    }
+                    if !is_closed {
+                        return Err(String::from("Unclosed { in the template"));
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut literal)));
+                    }
+                    parts.push(parse_field(&field)?);
+                }
+                '}' => return Err(String::from("Unmatched } in the template")),
+                _ if INVALID_CHARS.contains(&c) => {
+                    return Err(format!("The template can't contain {}", c))
+                }
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Text(literal));
+        }
+        Ok(Self { parts })
+    }
+
+    // The name of the file without its extension
+    pub fn render(&self, img: &ExifImage, context: &Context) -> Result<String, String> {
+        let mut name = String::new();
+        for part in self.parts.iter() {
+            match part {
+                Part::Text(text) => name.push_str(text),
+                Part::Tag(tag, format) => {
+                    let value = match (img.get_value_as_string(*tag), &context.missing) {
+                        (Some(value), _) => value,
+                        (None, Some(missing)) => {
+                            name.push_str(missing);
+                            continue;
+                        }
+                        (None, None) => return Err(format!("No {}", tag)),
+                    };
+                    let value = match format {
+                        Some(format) => DateTime::from_exif(&value)
+                            .ok_or_else(|| format!("{} isn't a valid date: {}", tag, value))?
+                            .format(format)?,
+                        None => value,
+                    };
+                    name.push_str(&get_file_name_part(&value));
+                }
+                Part::Date(format) => match context.date {
+                    Some(date) => name.push_str(&get_file_name_part(&date.format(format)?)),
+                    None => return Err(String::from("No date")),
+                },
+                Part::Counter(width) => name.push_str(&format!("{:0width$}", context.counter)),
+            }
+        }
+
+        // Names such as .. would leave the directory, and the ones starting with a dot are hidden
+        let dot_count = name.chars().take_while(|c| *c == '.').count();
+        let name = format!("{}{}", "-".repeat(dot_count), &name[dot_count..]);
+        match name.is_empty() {
+            true => Err(String::from("The name is empty")),
+            false => Ok(name),
+        }
+    }
+}
+
+// The date the picture was taken
+pub fn get_image_date(img: &ExifImage) -> Option<DateTime> {
+    DATE_TAGS
+        .iter()
+        .filter_map(|tag| img.get_value_as_string(*tag))
+        .find_map(|value| DateTime::from_exif(&value))
+}
+
+// `{Tag}`, `{Tag:format}`, `{year}` and the other date fields, `{date:format}`, `{counter}` or
+// `{counter:digits}`
+fn parse_field(field: &str) -> Result<Part, String> {
+    let (name, format) = match field.split_once(':') {
+        Some((name, format)) => (name, Some(format)),
+        None => (field, None),
+    };
+    if let Some((_, date_format)) = DATE_FIELDS
+        .iter()
+        .find(|(date_field, _)| date_field.eq_ignore_ascii_case(name))
+    {
+        return Ok(Part::Date(String::from(*date_format)));
+    }
+    if name.eq_ignore_ascii_case("date") {
+        return Ok(Part::Date(String::from(format.unwrap_or("%Y-%m-%d"))));
+    }
+    if name.eq_ignore_ascii_case("counter") {
+        return match format.map(str::parse::<usize>) {
+            Some(Ok(width)) => Ok(Part::Counter(width)),
+            Some(Err(_)) => Err(format!("Invalid counter width in {{{}}}", field)),
+            None => Ok(Part::Counter(1)),
+        };
+    }
+
+    let tag = Tag::from_name(name)
+        .or_else(|| Tag::from_exiftool_name(name))
+        .ok_or_else(|| format!("Unknown tag {} in the template", name))?;
+    Ok(Part::Tag(tag, format.map(String::from)))
+}
+
+// The spaces are removed, as in CanonEOSR5, and the characters that file names can't hold are
+// replaced
+fn get_file_name_part(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match INVALID_CHARS.contains(&c) || c.is_control() {
+            true => '-',
+            false => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields() {
+        let template = Template::parse("{DateTimeOriginal:%Y-%m-%d}_{Model}_{counter:4}").unwrap();
+        assert!(matches!(
+            template.parts.as_slice(),
+            [
+                Part::Tag(Tags::DateTimeOriginal, Some(format)),
+                Part::Text(first),
+                Part::Tag(Tags::Model, None),
+                Part::Text(second),
+                Part::Counter(4),
+            ] if format == "%Y-%m-%d" && first == "_" && second == "_"
+        ));
+
+        let template = Template::parse("{year}{DATE}{counter}{make}").unwrap();
+        assert!(matches!(
+            template.parts.as_slice(),
+            [
+                Part::Date(year),
+                Part::Date(date),
+                Part::Counter(1),
+                Part::Tag(Tags::Make, None),
+            ] if year == "%Y" && date == "%Y-%m-%d"
+        ));
+    }
+
+    #[test]
+    fn escaped_braces() {
+        let template = Template::parse("{{a}}b{{").unwrap();
+        assert!(matches!(template.parts.as_slice(), [Part::Text(text)] if text == "{a}b{"));
+        assert!(Template::parse("").unwrap().parts.is_empty());
+    }
+
+    #[test]
+    fn invalid_templates() {
+        for text in [
+            "{Model",
+            "Model}",
+            "{Unknown}",
+            "{counter:x}",
+            "a/{Model}",
+            "{Model}:b",
+            "{}",
+        ] {
+            assert!(Template::parse(text).is_err(), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use crate::datetime::DateTime;
+use crate::exif::{ExifImage, IFDTypes};
+use crate::tags::{Tag, Tags};
+use crate::writer::IFDEntry;
+
+// The dates of the image, with the tags of their time zone
+const DATE_TAGS: [(Tag, Tag); 3] = [
+    (Tags::DateTime, Tags::OffsetTime),
+    (Tags::DateTimeOriginal, Tags::OffsetTimeOriginal),
+    (Tags::DateTimeDigitized, Tags::OffsetTimeDigitized),
+];
+
+// The number of seconds of `+1d2h30m10s` (with d, h, m and s in any combination) or of
+// `-1:30[:10]`. The sign is optional.
+pub fn parse_duration(text: &str) -> Result<i64, String> {
+    let invalid = || {
+        format!(
+            "Invalid duration {}, expected something like +1d2h30m or -1:30",
+            text
+        )
+    };
+    let (sign, rest) = match text.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, text.strip_prefix('+').unwrap_or(text)),
+    };
+
+    let seconds = if rest.contains(':') {
+        let parts = rest
+            .split(':')
+            .map(|part| part.parse::<u32>().map(i64::from))
+            .collect::<Result<Vec<i64>, _>>()
+            .map_err(|_| invalid())?;
+        match parts.as_slice() {
+            [hours, minutes] => hours * 3600 + minutes * 60,
+            [hours, minutes, seconds] => hours * 3600 + minutes * 60 + seconds,
+            _ => return Err(invalid()),
+        }
+    } else {
+        let mut seconds = 0i64;
+        let mut number = String::new();
+        for c in rest.chars() {
+            if c.is_ascii_digit() {
+                number.push(c);
+                continue;
+            }
+            let unit = match c {
+                'd' => 86400,
+                'h' => 3600,
+                'm' => 60,
+                's' => 1,
+                _ => return Err(invalid()),
+            };
+            let value: i64 = number.parse().map_err(|_| invalid())?;
+            seconds = value
+                .checked_mul(unit)
+                .and_then(|value| seconds.checked_add(value))
+                .ok_or_else(invalid)?;
+            number.clear();
+        }
+        if rest.is_empty() || !number.is_empty() {
+            return Err(invalid());
+        }
+        seconds
+    };
+    Ok(sign * seconds)
+}
+
+// Adds the seconds to the dates of the image and, unless the time zone `offset` is given, to the
+// GPS date and time. A new time zone means that the pictures were taken in another one than the
+// clock of the camera was set to, so their time in UTC (that the GPS gives) is already right.
+// Returns the changes, as `Tag: old -> new`.
+pub fn shift_times(
+    img: &mut ExifImage,
+    seconds: i64,
+    offset: Option<&str>,
+) -> Result<Vec<String>, String> {
+    let mut changes = Vec::new();
+    let mut entries = Vec::new();
+    for (tag, offset_tag) in DATE_TAGS {
+        let value = match img.get_value_as_string(tag) {
+            Some(value) => value,
+            None => continue,
+        };
+        // Unknown dates are written with spaces and colons only
+        if value.chars().all(|c| matches!(c, ' ' | ':' | '\0')) {
+            continue;
+        }
+        let date = DateTime::from_exif(&value)
+            .ok_or_else(|| format!("{} isn't a valid date: {}", tag, value))?;
+        let new_date = date
+            .shift(seconds)
+            .ok_or_else(|| format!("{} can't be shifted that far", tag))?;
+        changes.push(format!("{}: {} -> {}", tag, date, new_date));
+        entries.push(img.get_entry_from_string(tag, &new_date.to_string())?);
+
+        if let Some(offset) = offset {
+            let old_offset = img.get_value_as_string(offset_tag).unwrap_or_default();
+            if old_offset != offset {
+                changes.push(format!("{}: {} -> {}", offset_tag, old_offset, offset));
+                entries.push(img.get_entry_from_string(offset_tag, offset)?);
+            }
+        }
+    }
+    if offset.is_none() {
+        shift_gps_time(img, seconds, &mut changes, &mut entries)?;
+    }
+
+    for ifd_type in [IFDTypes::TIFF, IFDTypes::Exif, IFDTypes::GPS] {
+        let ifd_entries: Vec<IFDEntry> = entries
+            .iter()
+            .filter(|(entry_ifd_type, _)| *entry_ifd_type == ifd_type)
+            .map(|(_, entry)| entry.clone())
+            .collect();
+        if !ifd_entries.is_empty() {
+            img.set_tags(ifd_type, ifd_entries)?;
+        }
+    }
+    Ok(changes)
+}
+
+// GPSTimeStamp holds the hours, minutes and seconds as rationals, the fraction of the seconds is
+// kept. Without GPSDateStamp, the time wraps around midnight.
+fn shift_gps_time(
+    img: &ExifImage,
+    seconds: i64,
+    changes: &mut Vec<String>,
+    entries: &mut Vec<(IFDTypes, IFDEntry)>,
+) -> Result<(), String> {
+    let time = match img.get_value_as_text(Tags::GPSTimeStamp) {
+        Some(time) => time,
+        None => return Ok(()),
+    };
+    let invalid = || format!("GPSTimeStamp isn't a valid time: {}", time);
+    let rationals = time
+        .split_whitespace()
+        .map(|rational| {
+            let (num, den) = rational.split_once('/')?;
+            let (num, den) = (num.parse::<u64>().ok()?, den.parse::<u64>().ok()?);
+            (den != 0).then_some((num, den))
+        })
+        .collect::<Option<Vec<(u64, u64)>>>()
+        .ok_or_else(invalid)?;
+    let (hour, minute, (second_num, second_den)) = match rationals.as_slice() {
+        [(h_num, h_den), (m_num, m_den), second] => (h_num / h_den, m_num / m_den, *second),
+        _ => return Err(invalid()),
+    };
+
+    let date_stamp = img
+        .get_value_as_string(Tags::GPSDateStamp)
+        .map(|date| date.trim_end_matches(['\0', ' ']).to_string())
+        .filter(|date| DateTime::from_exif(&format!("{} 00:00:00", date)).is_some());
+    let date = DateTime::from_exif(&format!(
+        "{} {:02}:{:02}:{:02}",
+        date_stamp.as_deref().unwrap_or("1970:01:01"),
+        hour,
+        minute,
+        second_num / second_den
+    ))
+    .ok_or_else(invalid)?;
+    let new_date = date
+        .shift(seconds)
+        .ok_or_else(|| String::from("GPSTimeStamp can't be shifted that far"))?;
+
+    changes.push(format!(
+        "{}: {} -> {}",
+        Tags::GPSTimeStamp,
+        date.format("%H:%M:%S")?,
+        new_date.format("%H:%M:%S")?
+    ));
+    let new_second = new_date.second as u64 * second_den + second_num % second_den;
+    entries.push(img.get_entry_from_string(
+        Tags::GPSTimeStamp,
+        &format!(
+            "{}/1 {}/1 {}/{}",
+            new_date.hour, new_date.minute, new_second, second_den
+        ),
+    )?);
+
+    if let Some(date_stamp) = date_stamp {
+        let new_date_stamp = new_date.format("%Y:%m:%d")?;
+        if new_date_stamp != date_stamp {
+            changes.push(format!(
+                "{}: {} -> {}",
+                Tags::GPSDateStamp,
+                date_stamp,
+                new_date_stamp
+            ));
+            entries.push(img.get_entry_from_string(Tags::GPSDateStamp, &new_date_stamp)?);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("+1d2h30m10s"), Ok(95410));
+        assert_eq!(parse_duration("-2h"), Ok(-7200));
+        assert_eq!(parse_duration("90s"), Ok(90));
+        assert_eq!(parse_duration("1:30"), Ok(5400));
+        assert_eq!(parse_duration("-1:30:10"), Ok(-5410));
+    }
+
+    #[test]
+    fn invalid_durations() {
+        for text in [
+            "",
+            "-",
+            "1h30",
+            "1x",
+            "h",
+            "1:2:3:4",
+            "1:",
+            "1:-30",
+            "99999999999999999d",
+        ] {
+            assert!(parse_duration(text).is_err(), "{}", text);
+        }
+    }
+}
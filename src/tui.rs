@@ -0,0 +1,401 @@
+use std::fs;
+
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{List, ListItem, ListState, Paragraph},
+    DefaultTerminal, Frame,
+};
+
+use crate::diff::{get_diff_rows, DiffRow};
+use crate::exif::{ExifImage, ExifTypes, Field};
+use crate::gps::{
+    get_coordinate, is_coordinate_tag, set_coordinate_from_string, DEFAULT_SECONDS_DECIMALS,
+};
+
+const HELP: &str = "↑/↓ move  e edit  d delete  c compare  s save  q quit";
+const COMPARE_HELP: &str = "↑/↓ move  e edit  d delete  f changes only  c back  s save  q quit";
+
+enum Row {
+    Section(String),
+    Tag {
+        section: String,
+        name: String,
+        value: String,
+    },
+    Diff(DiffRow),
+}
+
+struct App<'a> {
+    img: ExifImage,
+    path: &'a str,
+    output: Option<&'a str>,
+    // The image the edits are compared to: the one given, or the file as it was loaded or saved
+    reference: ExifImage,
+    reference_path: Option<&'a str>,
+    rows: Vec<Row>,
+    state: ListState,
+    is_comparing: bool,
+    is_changes_only: bool,
+    input: Option<(Field, String)>, // The value being edited and its new text
+    message: Option<String>,
+    is_modified: bool,
+    is_quitting: bool, // Quitting with unsaved changes needs a confirmation
+    should_quit: bool,
+}
+
+// Shows the metadata of the image, whose tags can be edited and saved back to `path` (or `output`).
+// With a reference image, both are shown side by side from the start.
+pub fn run(
+    img: ExifImage,
+    path: &str,
+    output: Option<&str>,
+    reference: Option<(ExifImage, &str)>,
+) -> Result<(), String> {
+    let is_comparing = reference.is_some();
+    let (reference, reference_path) = match reference {
+        Some((reference, reference_path)) => (reference, Some(reference_path)),
+        None => (get_copy(&img)?, None),
+    };
+    let mut terminal = ratatui::try_init().map_err(|e| e.to_string())?;
+    let mut app = App::new(img, path, output, reference, reference_path, is_comparing);
+    let result = app.run(&mut terminal);
+    ratatui::try_restore().map_err(|e| e.to_string())?;
+    result
+}
+
+// ExifImage can't be cloned, its contents are parsed again instead
+fn get_copy(img: &ExifImage) -> Result<ExifImage, String> {
+    ExifImage::from(img.get_contents().to_vec(), img.get_image_format())
+}
+
+impl<'a> App<'a> {
+    fn new(
+        img: ExifImage,
+        path: &'a str,
+        output: Option<&'a str>,
+        reference: ExifImage,
+        reference_path: Option<&'a str>,
+        is_comparing: bool,
+    ) -> Self {
+        let mut app = Self {
+            img,
+            path,
+            output,
+            reference,
+            reference_path,
+            rows: Vec::new(),
+            state: ListState::default(),
+            is_comparing,
+            is_changes_only: false,
+            input: None,
+            message: None,
+            is_modified: false,
+            is_quitting: false,
+            should_quit: false,
+        };
+        app.update_rows();
+        app.state.select(Some(0));
+        app
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), String> {
+        while !self.should_quit {
+            terminal
+                .draw(|frame| self.draw(frame))
+                .map_err(|e| e.to_string())?;
+            match event::read().map_err(|e| e.to_string())? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => match self.input {
+                    Some(_) => self.handle_input_key(key.code),
+                    None => self.handle_key(key.code),
+                },
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn update_rows(&mut self) {
+        self.rows.clear();
+        if self.is_comparing {
+            self.update_diff_rows();
+        } else {
+            self.update_tag_rows();
+        }
+        if let Some(selected) = self.state.selected() {
+            self.state
+                .select(Some(selected.min(self.rows.len().saturating_sub(1))));
+        }
+    }
+
+    fn update_tag_rows(&mut self) {
+        for (section, tags) in self.img.get_all_sections() {
+            self.rows.push(Row::Section(section.clone()));
+            for (name, value) in tags {
+                self.rows.push(Row::Tag {
+                    section: section.clone(),
+                    name,
+                    value,
+                });
+            }
+        }
+    }
+
+    fn update_diff_rows(&mut self) {
+        let mut section = None;
+        for row in get_diff_rows(&self.reference, &self.img) {
+            if self.is_changes_only && !row.is_changed() {
+                continue;
+            }
+            if section.as_ref() != Some(&row.section) {
+                section = Some(row.section.clone());
+                self.rows.push(Row::Section(row.section.clone()));
+            }
+            self.rows.push(Row::Diff(row));
+        }
+    }
+
+    // The selected value, if it can be edited
+    fn get_selected_field(&self) -> Option<Field> {
+        let (section, name) = match self.rows.get(self.state.selected()?)? {
+            Row::Tag { section, name, .. } => (section, name),
+            Row::Diff(row) => (&row.section, &row.tag),
+            Row::Section(_) => return None,
+        };
+        Field::from_section(section, name)
+    }
+
+    fn handle_key(&mut self, code: KeyCode) {
+        self.message = None;
+        let is_quitting = std::mem::take(&mut self.is_quitting);
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => {
+                if self.is_modified && !is_quitting {
+                    self.is_quitting = true;
+                    self.message = Some(String::from(
+                        "The changes aren't saved, press q again to quit anyway",
+                    ));
+                } else {
+                    self.should_quit = true;
+                }
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.state.select_previous(),
+            KeyCode::Down | KeyCode::Char('j') => self.state.select_next(),
+            KeyCode::PageUp => self.state.scroll_up_by(20),
+            KeyCode::PageDown => self.state.scroll_down_by(20),
+            KeyCode::Home => self.state.select_first(),
+            KeyCode::End => self.state.select_last(),
+            KeyCode::Char('e') | KeyCode::Enter => match self.get_selected_field() {
+                Some(Field::Tag(tag)) => {
+                    // Only text and coordinates can be edited as they are displayed
+                    let ifd_type = tag.get_ifd_type().unwrap();
+                    let value = match self.img.get_interop(ifd_type, tag) {
+                        Some(interop) if *interop.get_data_type() == ExifTypes::Ascii => {
+                            self.img.get_ascii(ifd_type, tag).unwrap_or_default()
+                        }
+                        Some(_) if is_coordinate_tag(tag) => get_coordinate(&self.img, tag)
+                            .map(|value| format!("{:.6}", value))
+                            .unwrap_or_default(),
+                        _ => String::new(),
+                    };
+                    self.input = Some((Field::Tag(tag), value));
+                }
+                Some(field) => {
+                    let value = field.get_value(&self.img).unwrap_or_default();
+                    self.input = Some((field, value));
+                }
+                None => self.message = Some(String::from("This value can't be edited")),
+            },
+            KeyCode::Char('d') | KeyCode::Delete => match self.get_selected_field() {
+                Some(field) => match field.remove(&mut self.img) {
+                    Ok(()) => {
+                        self.is_modified = true;
+                        self.update_rows();
+                    }
+                    Err(e) => self.message = Some(e),
+                },
+                None => self.message = Some(String::from("This value can't be removed")),
+            },
+            KeyCode::Char('c') => {
+                self.is_comparing = !self.is_comparing;
+                self.update_rows();
+            }
+            KeyCode::Char('f') if self.is_comparing => {
+                self.is_changes_only = !self.is_changes_only;
+                self.update_rows();
+            }
+            KeyCode::Char('s') => {
+                let out_path = self.output.unwrap_or(self.path);
+                self.message = Some(match fs::write(out_path, self.img.get_contents()) {
+                    Ok(()) => {
+                        self.is_modified = false;
+                        // The saved file is what the next edits are compared to
+                        if self.reference_path.is_none() {
+                            if let Ok(reference) = get_copy(&self.img) {
+                                self.reference = reference;
+                                self.update_rows();
+                            }
+                        }
+                        format!("Saved to {}", out_path)
+                    }
+                    Err(e) => format!("Couldn't write {}: {}", out_path, e),
+                });
+            }
+            _ => (),
+        }
+    }
+
+    fn handle_input_key(&mut self, code: KeyCode) {
+        self.message = None;
+        let (field, value) = match &mut self.input {
+            Some(input) => input,
+            None => return,
+        };
+        match code {
+            KeyCode::Char(c) => value.push(c),
+            KeyCode::Backspace => {
+                value.pop();
+            }
+            KeyCode::Esc => self.input = None,
+            KeyCode::Enter => {
+                let value = value.clone();
+                // A coordinate can be pasted with the other one, as in `48.8584, 2.2945`
+                let result = match field {
+                    Field::Tag(tag) if is_coordinate_tag(*tag) => set_coordinate_from_string(
+                        &mut self.img,
+                        *tag,
+                        &value,
+                        DEFAULT_SECONDS_DECIMALS,
+                    ),
+                    Field::Tag(tag) => self.img.set_tag_from_string(*tag, &value),
+                    Field::Section(section, name) => {
+                        self.img.set_section_value(section, name, &value)
+                    }
+                };
+                match result {
+                    Ok(()) => {
+                        self.input = None;
+                        self.is_modified = true;
+                        self.update_rows();
+                    }
+                    // The value is kept, so that it can be corrected
+                    Err(e) => self.message = Some(e),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [title_area, list_area, status_area] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let title = format!(
+            "{} ({}){}",
+            self.path,
+            self.img.get_image_format(),
+            if self.is_modified { " [modified]" } else { "" }
+        );
+        frame.render_widget(
+            Paragraph::new(title).style(Style::default().add_modifier(Modifier::REVERSED)),
+            title_area,
+        );
+
+        let highlight_style = Style::default().add_modifier(Modifier::REVERSED);
+        if self.is_comparing {
+            let [old_area, new_area] =
+                Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                    .areas(list_area);
+            let [old_header_area, old_list_area] =
+                Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(old_area);
+            let [new_header_area, new_list_area] =
+                Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(new_area);
+            let header_style = Style::default().add_modifier(Modifier::UNDERLINED);
+            let old_header = match self.reference_path {
+                Some(reference_path) => reference_path.to_string(),
+                None => format!("{} (saved)", self.path),
+            };
+            frame.render_widget(
+                Paragraph::new(old_header).style(header_style),
+                old_header_area,
+            );
+            frame.render_widget(
+                Paragraph::new(format!("{} (edited)", self.path)).style(header_style),
+                new_header_area,
+            );
+
+            // Both lists scroll together, as they have the same rows and height
+            let new_list = List::new(self.get_items(false)).highlight_style(highlight_style);
+            frame.render_stateful_widget(new_list, new_list_area, &mut self.state);
+            let old_list = List::new(self.get_items(true)).highlight_style(highlight_style);
+            frame.render_stateful_widget(old_list, old_list_area, &mut self.state.clone());
+        } else {
+            let list = List::new(self.get_items(false)).highlight_style(highlight_style);
+            frame.render_stateful_widget(list, list_area, &mut self.state);
+        }
+
+        let status = match (&self.input, &self.message) {
+            (_, Some(message)) => message.clone(),
+            (Some((field, value)), None) => format!("{}: {}█", field, value),
+            (None, None) if self.is_comparing => String::from(COMPARE_HELP),
+            (None, None) => String::from(HELP),
+        };
+        frame.render_widget(Paragraph::new(status), status_area);
+    }
+
+    // The items of the list, with the values of the reference image on the old side of a comparison
+    fn get_items(&self, is_old: bool) -> Vec<ListItem<'static>> {
+        let mut name_width = 0;
+        self.rows
+            .iter()
+            .enumerate()
+            .map(|(i, row)| match row {
+                Row::Section(section) => {
+                    // The names are aligned in each section
+                    name_width = self.rows[i + 1..]
+                        .iter()
+                        .map_while(|row| match row {
+                            Row::Tag { name, .. } => Some(name.chars().count()),
+                            Row::Diff(row) => Some(row.tag.chars().count()),
+                            Row::Section(_) => None,
+                        })
+                        .max()
+                        .unwrap_or(0);
+                    ListItem::new(Line::from(Span::styled(
+                        format!("[{}]", section),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    )))
+                }
+                Row::Tag { name, value, .. } => {
+                    ListItem::new(format!("  {:width$}  {}", name, value, width = name_width))
+                }
+                Row::Diff(row) => {
+                    let (value, color) = match (is_old, &row.old_value, &row.new_value) {
+                        (true, Some(value), None) => (Some(value), Some(Color::Red)),
+                        (false, None, Some(value)) => (Some(value), Some(Color::Green)),
+                        (true, value, _) | (false, _, value) => {
+                            (value.as_ref(), row.is_changed().then_some(Color::Yellow))
+                        }
+                    };
+                    let text = match value {
+                        Some(value) => {
+                            format!("  {:width$}  {}", row.tag, value, width = name_width)
+                        }
+                        None => String::new(),
+                    };
+                    let style = match color {
+                        Some(color) => Style::default().fg(color),
+                        None => Style::default(),
+                    };
+                    ListItem::new(text).style(style)
+                }
+            })
+            .collect()
+    }
+}
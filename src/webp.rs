@@ -0,0 +1,298 @@
+// See https://developers.google.com/speed/webp/docs/riff_container
+pub const RIFF: &[u8; 4] = b"RIFF";
+pub const WEBP: &[u8; 4] = b"WEBP";
+pub const VP8X: &[u8; 4] = b"VP8X";
+pub const VP8: &[u8; 4] = b"VP8 ";
+pub const VP8L: &[u8; 4] = b"VP8L";
+pub const ICCP: &[u8; 4] = b"ICCP";
+pub const EXIF: &[u8; 4] = b"EXIF";
+pub const XMP: &[u8; 4] = b"XMP ";
+
+// "RIFF", file size and "WEBP"
+const RIFF_HEADER_SIZE: usize = 12;
+const VP8X_SIZE: usize = 10;
+
+// Flags of the VP8X chunk
+const ICC_FLAG: u8 = 0x20;
+const ALPHA_FLAG: u8 = 0x10;
+const EXIF_FLAG: u8 = 0x08;
+const XMP_FLAG: u8 = 0x04;
+
+#[derive(Clone)]
+pub struct WebPChunk {
+    pub fourcc: [u8; 4],
+    pub offset: usize, // Offset of the FourCC
+    pub size: usize,   // Size of the data, without the padding byte
+}
+
+impl WebPChunk {
+    pub fn get_data_offset(&self) -> usize {
+        self.offset + 8
+    }
+
+    // Offset of the first byte after the chunk, chunks are padded to an even size
+    pub fn get_end(&self) -> usize {
+        self.get_data_offset() + self.size + self.size % 2
+    }
+
+    pub fn get_data<'a>(&self, slice: &'a [u8]) -> &'a [u8] {
+        &slice[self.get_data_offset()..self.get_data_offset() + self.size]
+    }
+
+    pub fn get_fourcc_as_string(&self) -> String {
+        String::from_utf8_lossy(&self.fourcc).to_string()
+    }
+}
+
+pub struct WebPChunks<'a> {
+    slice: &'a [u8],
+    pos: usize,
+    finished: bool,
+}
+
+impl<'a> Iterator for WebPChunks<'a> {
+    type Item = Result<WebPChunk, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished || self.pos >= self.slice.len() {
+            return None;
+        }
+
+        let header = match self.slice.get(self.pos..self.pos + 8) {
+            Some(header) => header,
+            None => {
+                self.finished = true;
+                return Some(Err(format!(
+                    "Truncated chunk header at offset {}",
+                    self.pos
+                )));
+            }
+        };
+        let chunk = WebPChunk {
+            fourcc: header[0..4].try_into().unwrap(),
+            offset: self.pos,
+            size: u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize,
+        };
+        // The padding byte of the last chunk is sometimes missing
+        if chunk.get_data_offset() + chunk.size > self.slice.len() {
+            self.finished = true;
+            return Some(Err(format!(
+                "The {} chunk at offset {} goes past the end of the file",
+                chunk.get_fourcc_as_string(),
+                chunk.offset
+            )));
+        }
+
+        self.pos = chunk.get_end();
+        Some(Ok(chunk))
+    }
+}
+
+pub fn is_webp(slice: &[u8]) -> bool {
+    slice.len() >= RIFF_HEADER_SIZE && slice.starts_with(RIFF) && &slice[8..12] == WEBP
+}
+
+pub fn get_chunks(slice: &[u8]) -> WebPChunks<'_> {
+    // Trailing data after the RIFF structure is ignored
+    let riff_end = match slice.get(4..8) {
+        Some(size) => 8 + u32::from_le_bytes(size.try_into().unwrap()) as usize,
+        None => 0,
+    };
+    WebPChunks {
+        slice: &slice[..riff_end.min(slice.len())],
+        pos: RIFF_HEADER_SIZE,
+        finished: !is_webp(slice),
+    }
+}
+
+pub fn get_chunk<'a>(slice: &'a [u8], fourcc: &[u8; 4]) -> Option<&'a [u8]> {
+    get_chunks(slice)
+        .map_while(Result::ok)
+        .find(|chunk| &chunk.fourcc == fourcc)
+        .map(|chunk| chunk.get_data(slice))
+}
+
+// The spec imposes an order on the chunks of the extended format, with the metadata at both ends
+fn get_rank(fourcc: &[u8; 4]) -> u8 {
+    match fourcc {
+        VP8X => 0,
+        ICCP => 1,
+        b"ANIM" => 2,
+        b"ALPH" | b"ANMF" | VP8 | VP8L => 3,
+        EXIF => 4,
+        XMP => 5,
+        _ => 6,
+    }
+}
+
+// Reads the canvas size and whether there is an alpha channel from the VP8 or VP8L bitstream, for
+// files in the simple format
+fn get_canvas_info(fourcc: &[u8; 4], data: &[u8]) -> Option<(u32, u32, bool)> {
+    match fourcc {
+        // See RFC 6386 (9.1), after the frame tag comes a start code and the dimensions
+        VP8 if data.get(3..6)? == [0x9D, 0x01, 0x2A] => Some((
+            u16::from_le_bytes(data.get(6..8)?.try_into().unwrap()) as u32 & 0x3FFF,
+            u16::from_le_bytes(data.get(8..10)?.try_into().unwrap()) as u32 & 0x3FFF,
+            false,
+        )),
+        // See RFC 9649 (3.2), the dimensions minus one are packed on 14 bits each
+        VP8L if *data.first()? == 0x2F => {
+            let bits = u32::from_le_bytes(data.get(1..5)?.try_into().unwrap());
+            Some((
+                (bits & 0x3FFF) + 1,
+                ((bits >> 14) & 0x3FFF) + 1,
+                (bits >> 28) & 1 == 1,
+            ))
+        }
+        _ => None,
+    }
+}
+
+// Replaces the data of every chunk with the given FourCC, or removes them if `data` is None.
+// Metadata chunks require the extended format, so a VP8X chunk is added when needed and its flags
+// are kept in sync with the chunks that are present.
+pub fn replace_chunk(
+    slice: &[u8],
+    fourcc: &[u8; 4],
+    data: Option<&[u8]>,
+) -> Result<Vec<u8>, String> {
+    let mut chunks: Vec<([u8; 4], Vec<u8>)> = get_chunks(slice)
+        .map(|chunk| chunk.map(|chunk| (chunk.fourcc, chunk.get_data(slice).to_vec())))
+        .collect::<Result<Vec<([u8; 4], Vec<u8>)>, String>>()?;
+
+    let first = chunks.iter().position(|(other, _)| other == fourcc);
+    chunks.retain(|(other, _)| other != fourcc);
+    if let Some(data) = data {
+        let insert_at = first.unwrap_or_else(|| {
+            chunks
+                .iter()
+                .position(|(other, _)| get_rank(other) > get_rank(fourcc))
+                .unwrap_or(chunks.len())
+        });
+        chunks.insert(insert_at, (*fourcc, data.to_vec()));
+    }
+
+    let has_chunk = |fourcc: &[u8; 4]| chunks.iter().any(|(other, _)| other == fourcc);
+    let flags = [(ICCP, ICC_FLAG), (EXIF, EXIF_FLAG), (XMP, XMP_FLAG)]
+        .iter()
+        .filter(|(fourcc, _)| has_chunk(fourcc))
+        .fold(0, |flags, (_, flag)| flags | flag);
+    if flags != 0 && !has_chunk(VP8X) {
+        let (width, height, has_alpha) = match chunks
+            .iter()
+            .find_map(|(other, data)| get_canvas_info(other, data))
+        {
+            Some(info) => info,
+            None => return Err(String::from("Couldn't read the size of the image")),
+        };
+
+        let mut vp8x = vec![if has_alpha { ALPHA_FLAG } else { 0 }, 0, 0, 0];
+        vp8x.extend_from_slice(&(width - 1).to_le_bytes()[0..3]);
+        vp8x.extend_from_slice(&(height - 1).to_le_bytes()[0..3]);
+        chunks.insert(0, (*VP8X, vp8x));
+    }
+
+    if let Some((_, vp8x)) = chunks.iter_mut().find(|(other, _)| other == VP8X) {
+        if vp8x.len() < VP8X_SIZE {
+            return Err(String::from("Invalid VP8X chunk"));
+        }
+        vp8x[0] = (vp8x[0] & !(ICC_FLAG | EXIF_FLAG | XMP_FLAG)) | flags;
+    }
+
+    let mut contents = Vec::with_capacity(slice.len());
+    contents.extend_from_slice(RIFF);
+    contents.extend_from_slice(&[0; 4]);
+    contents.extend_from_slice(WEBP);
+    for (fourcc, data) in chunks.iter() {
+        contents.extend_from_slice(fourcc);
+        contents.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        contents.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            contents.push(0);
+        }
+    }
+
+    if contents.len() - 8 > u32::MAX as usize {
+        return Err(String::from("The file is too big for the RIFF container"));
+    }
+    let riff_size = (contents.len() - 8) as u32;
+    contents[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A lossless image of 100x50 pixels with an alpha channel, in the simple format
+    fn build_webp() -> Vec<u8> {
+        let bits: u32 = 99 | (49 << 14) | (1 << 28);
+        let mut data = vec![0x2F];
+        data.extend_from_slice(&bits.to_le_bytes());
+        let mut webp = RIFF.to_vec();
+        webp.extend_from_slice(&(4 + 8 + data.len() as u32 + 1).to_le_bytes());
+        webp.extend_from_slice(WEBP);
+        webp.extend_from_slice(VP8L);
+        webp.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        webp.extend_from_slice(&data);
+        webp.push(0);
+        webp
+    }
+
+    fn get_fourccs(webp: &[u8]) -> Vec<String> {
+        get_chunks(webp)
+            .map(|chunk| chunk.unwrap().get_fourcc_as_string())
+            .collect()
+    }
+
+    #[test]
+    fn chunks_walk() {
+        let mut webp = build_webp();
+        assert!(is_webp(&webp));
+        assert_eq!(get_fourccs(&webp), ["VP8L"]);
+        assert_eq!(get_chunk(&webp, VP8L).unwrap().len(), 5);
+
+        // Trailing data is outside of the RIFF size
+        webp.extend_from_slice(b"trailing");
+        assert_eq!(get_fourccs(&webp), ["VP8L"]);
+
+        let webp = build_webp();
+        assert!(get_chunks(&webp[..webp.len() - 2]).any(|chunk| chunk.is_err()));
+        assert!(!is_webp(&webp[..RIFF_HEADER_SIZE - 1]));
+    }
+
+    #[test]
+    fn metadata_replace() {
+        let webp = replace_chunk(&build_webp(), XMP, Some(b"<x:xmpmeta/>")).unwrap();
+        assert_eq!(get_fourccs(&webp), ["VP8X", "VP8L", "XMP "]);
+        assert_eq!(
+            get_chunk(&webp, VP8X).unwrap(),
+            [ALPHA_FLAG | XMP_FLAG, 0, 0, 0, 99, 0, 0, 49, 0, 0]
+        );
+        assert_eq!(get_chunk(&webp, XMP).unwrap(), b"<x:xmpmeta/>");
+        assert_eq!(
+            webp.len(),
+            8 + u32::from_le_bytes(webp[4..8].try_into().unwrap()) as usize
+        );
+
+        // The chunks go where the spec puts them, and the flags follow them
+        let webp = replace_chunk(&webp, EXIF, Some(b"II*\0")).unwrap();
+        let webp = replace_chunk(&webp, ICCP, Some(b"icc")).unwrap();
+        assert_eq!(get_fourccs(&webp), ["VP8X", "ICCP", "VP8L", "EXIF", "XMP "]);
+        assert_eq!(
+            get_chunk(&webp, VP8X).unwrap()[0],
+            ALPHA_FLAG | ICC_FLAG | EXIF_FLAG | XMP_FLAG
+        );
+        assert_eq!(get_chunk(&webp, ICCP).unwrap(), b"icc");
+
+        let webp = replace_chunk(&webp, XMP, None).unwrap();
+        let webp = replace_chunk(&webp, ICCP, None).unwrap();
+        assert_eq!(get_fourccs(&webp), ["VP8X", "VP8L", "EXIF"]);
+        assert_eq!(get_chunk(&webp, VP8X).unwrap()[0], ALPHA_FLAG | EXIF_FLAG);
+
+        // The size can't be read from an unknown bitstream
+        let mut webp = build_webp();
+        webp[RIFF_HEADER_SIZE + 8] = 0;
+        assert!(replace_chunk(&webp, XMP, Some(b"<x:xmpmeta/>")).is_err());
+    }
+}
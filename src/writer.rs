@@ -0,0 +1,311 @@
+use crate::exif::{ExifTypes, INTEROPERABILITY_FIELD_SIZE};
+use crate::tags::{Tag, Tags};
+
+// Character code that starts the UserComment tag
+const USER_COMMENT_ASCII: &[u8; 8] = b"ASCII\0\0\0";
+
+// An interoperability field ready to be written, with its value already encoded in the byte
+// order of the TIFF structure it belongs to
+#[derive(Clone, PartialEq)]
+pub struct IFDEntry {
+    pub tag: u16,
+    pub data_type: u16,
+    pub count: u32,
+    pub data: Vec<u8>,
+}
+
+impl IFDEntry {
+    pub fn long(tag: Tag, value: u32, is_little_endian: bool) -> Self {
+        Self {
+            tag: tag.0 as u16,
+            data_type: 4,
+            count: 1,
+            data: if is_little_endian {
+                value.to_le_bytes()
+            } else {
+                value.to_be_bytes()
+            }
+            .to_vec(),
+        }
+    }
+
+    // Encodes a value given as text. Numbers are separated by spaces or commas (or dots for
+    // bytes, as in `2.3.0.0`), and rationals are written either as `num/den` or as decimals.
+    pub fn from_string(
+        tag: Tag,
+        data_type: ExifTypes,
+        value: &str,
+        is_little_endian: bool,
+    ) -> Result<Self, String> {
+        let invalid = || format!("Invalid value for {}: {}", tag, value);
+        let separators: &[char] = match data_type {
+            ExifTypes::Byte => &[' ', ',', '.'],
+            _ => &[' ', ','],
+        };
+        let values: Vec<&str> = value
+            .split(separators)
+            .filter(|value| !value.is_empty())
+            .collect();
+        let mut data = Vec::new();
+        let mut push = |bytes: &[u8]| {
+            if is_little_endian {
+                data.extend(bytes.iter().rev());
+            } else {
+                data.extend_from_slice(bytes);
+            }
+        };
+
+        let count = match data_type {
+            ExifTypes::Ascii => {
+                data.extend_from_slice(value.as_bytes());
+                data.push(0);
+                data.len()
+            }
+            ExifTypes::Undefined => {
+                if tag == Tags::UserComment {
+                    data.extend_from_slice(USER_COMMENT_ASCII);
+                }
+                data.extend_from_slice(value.as_bytes());
+                data.len()
+            }
+            _ if values.is_empty() => return Err(invalid()),
+            ExifTypes::Byte | ExifTypes::Sbyte => {
+                for value in values.iter() {
+                    let byte = match data_type {
+                        ExifTypes::Byte => value.parse::<u8>().ok(),
+                        _ => value.parse::<i8>().ok().map(|v| v as u8),
+                    };
+                    push(&[byte.ok_or_else(invalid)?]);
+                }
+                values.len()
+            }
+            ExifTypes::Short | ExifTypes::Sshort => {
+                for value in values.iter() {
+                    let short = match data_type {
+                        ExifTypes::Short => value.parse::<u16>().ok(),
+                        _ => value.parse::<i16>().ok().map(|v| v as u16),
+                    };
+                    push(&short.ok_or_else(invalid)?.to_be_bytes());
+                }
+                values.len()
+            }
+            ExifTypes::Long | ExifTypes::Slong => {
+                for value in values.iter() {
+                    let long = match data_type {
+                        ExifTypes::Long => value.parse::<u32>().ok(),
+                        _ => value.parse::<i32>().ok().map(|v| v as u32),
+                    };
+                    push(&long.ok_or_else(invalid)?.to_be_bytes());
+                }
+                values.len()
+            }
+            ExifTypes::Rational | ExifTypes::Srational => {
+                for value in values.iter() {
+                    let (num, den) = parse_rational(value).ok_or_else(invalid)?;
+                    let (num, den) = match data_type {
+                        ExifTypes::Rational => (u32::try_from(num).ok(), u32::try_from(den).ok()),
+                        _ => (
+                            i32::try_from(num).ok().map(|v| v as u32),
+                            i32::try_from(den).ok().map(|v| v as u32),
+                        ),
+                    };
+                    push(&num.ok_or_else(invalid)?.to_be_bytes());
+                    push(&den.ok_or_else(invalid)?.to_be_bytes());
+                }
+                values.len()
+            }
+            ExifTypes::Float => {
+                for value in values.iter() {
+                    let float = value.parse::<f32>().map_err(|_| invalid())?;
+                    push(&float.to_be_bytes());
+                }
+                values.len()
+            }
+            ExifTypes::Double => {
+                for value in values.iter() {
+                    let double = value.parse::<f64>().map_err(|_| invalid())?;
+                    push(&double.to_be_bytes());
+                }
+                values.len()
+            }
+            ExifTypes::Ifd | ExifTypes::Error => {
+                return Err(format!("{} can't be written as text", tag));
+            }
+        };
+
+        Ok(Self {
+            tag: tag.0 as u16,
+            data_type: data_type.get_code().unwrap_or_default(),
+            count: count as u32,
+            data,
+        })
+    }
+
+    // The text form of the value, as read back by `from_string`
+    pub fn get_value_as_text(&self, is_little_endian: bool) -> String {
+        let data_type = ExifTypes::from_code(self.data_type);
+        let size = match data_type {
+            ExifTypes::Ascii | ExifTypes::Undefined => {
+                let text = match self.data.strip_prefix(USER_COMMENT_ASCII) {
+                    Some(text) if Tag(self.tag as usize) == Tags::UserComment => text,
+                    _ => &self.data,
+                };
+                let text = String::from_utf8_lossy(text);
+                return text.trim_end_matches('\0').to_string();
+            }
+            ExifTypes::Byte | ExifTypes::Sbyte => 1,
+            ExifTypes::Short | ExifTypes::Sshort => 2,
+            ExifTypes::Double => 8,
+            _ => 4,
+        };
+
+        let numbers: Vec<u64> = self
+            .data
+            .chunks_exact(size)
+            .map(|chunk| {
+                let mut bytes = [0; 8];
+                bytes[8 - size..].copy_from_slice(chunk);
+                if is_little_endian {
+                    bytes[8 - size..].reverse();
+                }
+                u64::from_be_bytes(bytes)
+            })
+            .collect();
+        let texts: Vec<String> = match data_type {
+            ExifTypes::Rational => numbers
+                .chunks_exact(2)
+                .map(|pair| format!("{}/{}", pair[0], pair[1]))
+                .collect(),
+            ExifTypes::Srational => numbers
+                .chunks_exact(2)
+                .map(|pair| format!("{}/{}", pair[0] as u32 as i32, pair[1] as u32 as i32))
+                .collect(),
+            ExifTypes::Sbyte => numbers.iter().map(|n| (*n as i8).to_string()).collect(),
+            ExifTypes::Sshort => numbers.iter().map(|n| (*n as i16).to_string()).collect(),
+            ExifTypes::Slong => numbers.iter().map(|n| (*n as i32).to_string()).collect(),
+            ExifTypes::Float => numbers
+                .iter()
+                .map(|n| f32::from_bits(*n as u32).to_string())
+                .collect(),
+            ExifTypes::Double => numbers
+                .iter()
+                .map(|n| f64::from_bits(*n).to_string())
+                .collect(),
+            _ => numbers.iter().map(|n| n.to_string()).collect(),
+        };
+        texts.join(" ")
+    }
+
+    // Converts the value to the other byte order, so that it can be written to a file that
+    // doesn't use the same one
+    pub fn swap_byte_order(&mut self) {
+        let size = match ExifTypes::from_code(self.data_type) {
+            ExifTypes::Short | ExifTypes::Sshort => 2,
+            ExifTypes::Long
+            | ExifTypes::Slong
+            | ExifTypes::Float
+            | ExifTypes::Ifd
+            | ExifTypes::Rational
+            | ExifTypes::Srational => 4,
+            ExifTypes::Double => 8,
+            _ => return,
+        };
+        for value in self.data.chunks_exact_mut(size) {
+            value.reverse();
+        }
+    }
+}
+
+// Parses `num/den`, or a decimal number that is converted to a fraction without losing precision
+fn parse_rational(value: &str) -> Option<(i64, i64)> {
+    if let Some((num, den)) = value.split_once('/') {
+        return Some((num.parse().ok()?, den.parse().ok()?));
+    }
+
+    let (integer, decimals) = value.split_once('.').unwrap_or((value, ""));
+    if decimals.len() > 9 || !decimals.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let num: i64 = format!("{}{}", integer, decimals).parse().ok()?;
+    let den = 10_i64.pow(decimals.len() as u32);
+    let gcd = get_gcd(num.unsigned_abs(), den as u64) as i64;
+    Some((num / gcd, den / gcd))
+}
+
+fn get_gcd(a: u64, b: u64) -> u64 {
+    match b {
+        0 => a.max(1),
+        _ => get_gcd(b, a % b),
+    }
+}
+
+// Values that don't fit in the value_offset field are stored after the IFD, on a word boundary
+fn get_padded_len(len: usize) -> usize {
+    len + len % 2
+}
+
+pub fn get_ifd_size(entries: &[IFDEntry]) -> usize {
+    2 + entries.len() * INTEROPERABILITY_FIELD_SIZE
+        + 4
+        + entries
+            .iter()
+            .filter(|entry| entry.data.len() > 4)
+            .map(|entry| get_padded_len(entry.data.len()))
+            .sum::<usize>()
+}
+
+// Serializes the IFD followed by the values that don't fit in the fields.
+// `ifd_offset` is the offset of the IFD from the start of the TIFF header, all the value offsets
+// are computed from it
+pub fn write_ifd(
+    entries: &[IFDEntry],
+    ifd_offset: usize,
+    next_ifd_offset: u32,
+    is_little_endian: bool,
+) -> Vec<u8> {
+    let u16_bytes = |v: u16| {
+        if is_little_endian {
+            v.to_le_bytes()
+        } else {
+            v.to_be_bytes()
+        }
+    };
+    let u32_bytes = |v: u32| {
+        if is_little_endian {
+            v.to_le_bytes()
+        } else {
+            v.to_be_bytes()
+        }
+    };
+
+    // The spec requires the fields to be sorted in ascending order
+    let mut entries = entries.to_vec();
+    entries.sort_by_key(|entry| entry.tag);
+
+    let mut ifd = Vec::with_capacity(get_ifd_size(&entries));
+    let mut values = Vec::new();
+    let values_offset = ifd_offset + 2 + entries.len() * INTEROPERABILITY_FIELD_SIZE + 4;
+
+    ifd.extend_from_slice(&u16_bytes(entries.len() as u16));
+    for entry in entries.iter() {
+        ifd.extend_from_slice(&u16_bytes(entry.tag));
+        ifd.extend_from_slice(&u16_bytes(entry.data_type));
+        ifd.extend_from_slice(&u32_bytes(entry.count));
+
+        if entry.data.len() <= 4 {
+            let mut value = [0; 4];
+            value[..entry.data.len()].copy_from_slice(&entry.data);
+            ifd.extend_from_slice(&value);
+        } else {
+            ifd.extend_from_slice(&u32_bytes((values_offset + values.len()) as u32));
+            values.extend_from_slice(&entry.data);
+            if !values.len().is_multiple_of(2) {
+                values.push(0);
+            }
+        }
+    }
+    ifd.extend_from_slice(&u32_bytes(next_ifd_offset));
+    ifd.extend_from_slice(&values);
+
+    ifd
+}
@@ -0,0 +1,307 @@
+// A minimal XML parser, enough for the metadata formats (XMP, GPX, KML). DTDs are skipped and only
+// the predefined and numeric character references are resolved.
+
+#[derive(Clone)]
+pub enum XMLNode {
+    Element(XMLElement),
+    Text(String),
+}
+
+#[derive(Clone)]
+pub struct XMLElement {
+    pub name: String, // Qualified name, as written in the document
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<XMLNode>,
+}
+
+impl XMLElement {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            attributes: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    // An element holding only text
+    pub fn with_text(name: &str, text: &str) -> Self {
+        let mut element = Self::new(name);
+        element.children.push(XMLNode::Text(text.to_string()));
+        element
+    }
+
+    pub fn get_prefix(&self) -> Option<&str> {
+        self.name.split_once(':').map(|(prefix, _)| prefix)
+    }
+
+    // The name without its prefix
+    pub fn get_local_name(&self) -> &str {
+        match self.name.split_once(':') {
+            Some((_, local_name)) => local_name,
+            None => &self.name,
+        }
+    }
+
+    pub fn get_attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(attribute, _)| attribute == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn get_child_elements(&self) -> impl Iterator<Item = &XMLElement> {
+        self.children.iter().filter_map(|child| match child {
+            XMLNode::Element(element) => Some(element),
+            XMLNode::Text(_) => None,
+        })
+    }
+
+    // Concatenation of the text nodes directly inside the element
+    pub fn get_text(&self) -> String {
+        self.children
+            .iter()
+            .filter_map(|child| match child {
+                XMLNode::Text(text) => Some(text.as_str()),
+                XMLNode::Element(_) => None,
+            })
+            .collect()
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut s = String::new();
+        self.write(&mut s, 0);
+        s
+    }
+
+    // Elements that only contain text are kept on a single line
+    fn write(&self, s: &mut String, depth: usize) {
+        let indent = " ".repeat(depth);
+        s.push_str(format!("{}<{}", indent, self.name).as_str());
+        for (name, value) in self.attributes.iter() {
+            s.push_str(format!(" {}=\"{}\"", name, escape_xml(value)).as_str());
+        }
+
+        if self.children.is_empty() {
+            s.push_str("/>\n");
+        } else if self.get_child_elements().next().is_none() {
+            s.push_str(format!(">{}</{}>\n", escape_xml(&self.get_text()), self.name).as_str());
+        } else {
+            s.push_str(">\n");
+            for child in self.get_child_elements() {
+                child.write(s, depth + 1);
+            }
+            s.push_str(format!("{}</{}>\n", indent, self.name).as_str());
+        }
+    }
+}
+
+pub fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn unescape_xml(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('&') {
+        res.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => break,
+        };
+        let resolved = match &rest[1..end] {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            reference => {
+                if let Some(hex) = reference.strip_prefix("#x") {
+                    u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+                } else if let Some(dec) = reference.strip_prefix('#') {
+                    dec.parse::<u32>().ok().and_then(char::from_u32)
+                } else {
+                    None
+                }
+            }
+        };
+        match resolved {
+            Some(c) => {
+                res.push(c);
+                rest = &rest[end + 1..];
+            }
+            // Unknown entities are kept as they are
+            None => {
+                res.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    res.push_str(rest);
+
+    res
+}
+
+fn is_name_end(c: char) -> bool {
+    c.is_whitespace() || c == '/' || c == '>' || c == '='
+}
+
+// Returns the root element of the document
+pub fn parse_xml(s: &str) -> Result<XMLElement, String> {
+    let s = s.trim_start_matches('\u{FEFF}');
+    // The innermost element is at the end
+    let mut stack: Vec<XMLElement> = Vec::new();
+    let mut root: Option<XMLElement> = None;
+    let mut pos = 0;
+
+    let close = |stack: &mut Vec<XMLElement>, root: &mut Option<XMLElement>, element| {
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(XMLNode::Element(element)),
+            None if root.is_none() => *root = Some(element),
+            None => return Err(String::from("The document has more than one root element")),
+        }
+        Ok(())
+    };
+    let find_from = |pattern: &str, from: usize| {
+        s[from..]
+            .find(pattern)
+            .map(|i| from + i)
+            .ok_or(format!("Expected '{}' after offset {}", pattern, from))
+    };
+
+    while pos < s.len() {
+        let rest = &s[pos..];
+        if !rest.starts_with('<') {
+            let end = rest.find('<').map(|i| pos + i).unwrap_or(s.len());
+            if let Some(element) = stack.last_mut() {
+                element
+                    .children
+                    .push(XMLNode::Text(unescape_xml(&s[pos..end])));
+            } else if !s[pos..end].trim().is_empty() {
+                return Err(format!("Unexpected text at offset {}", pos));
+            }
+            pos = end;
+        } else if rest.starts_with("<?") {
+            pos = find_from("?>", pos)? + 2;
+        } else if rest.starts_with("<!--") {
+            pos = find_from("-->", pos)? + 3;
+        } else if rest.starts_with("<![CDATA[") {
+            let end = find_from("]]>", pos)?;
+            if let Some(element) = stack.last_mut() {
+                element
+                    .children
+                    .push(XMLNode::Text(s[pos + 9..end].to_string()));
+            }
+            pos = end + 3;
+        } else if rest.starts_with("<!") {
+            // DOCTYPE, possibly with an internal subset
+            let end = find_from(">", pos)?;
+            pos = match s[pos..end].find('[') {
+                Some(_) => find_from("]", pos).and_then(|end| find_from(">", end))? + 1,
+                None => end + 1,
+            };
+        } else if rest.starts_with("</") {
+            let end = find_from(">", pos)?;
+            let name = s[pos + 2..end].trim();
+            let element = match stack.pop() {
+                Some(element) if element.name == name => element,
+                _ => return Err(format!("Unexpected closing tag </{}>", name)),
+            };
+            close(&mut stack, &mut root, element)?;
+            pos = end + 1;
+        } else {
+            let name_end = rest[1..]
+                .find(is_name_end)
+                .map(|i| pos + 1 + i)
+                .unwrap_or(s.len());
+            let mut element = XMLElement::new(&s[pos + 1..name_end]);
+            pos = name_end;
+
+            loop {
+                pos += s[pos..].len() - s[pos..].trim_start().len();
+                let rest = &s[pos..];
+                if rest.starts_with("/>") {
+                    close(&mut stack, &mut root, element)?;
+                    pos += 2;
+                    break;
+                } else if rest.starts_with('>') {
+                    stack.push(element);
+                    pos += 1;
+                    break;
+                }
+
+                let name_end = match rest.find(is_name_end) {
+                    Some(i) if i > 0 => pos + i,
+                    _ => return Err(format!("Invalid attribute at offset {}", pos)),
+                };
+                let name = s[pos..name_end].to_string();
+                let eq = find_from("=", name_end)?;
+                let value_start = eq + 1 + (s[eq + 1..].len() - s[eq + 1..].trim_start().len());
+                let quote = match s[value_start..].chars().next() {
+                    Some(quote) if quote == '"' || quote == '\'' => quote,
+                    _ => {
+                        return Err(format!(
+                            "Unquoted attribute value at offset {}",
+                            value_start
+                        ))
+                    }
+                };
+                let value_end = find_from(quote.to_string().as_str(), value_start + 1)?;
+                element
+                    .attributes
+                    .push((name, unescape_xml(&s[value_start + 1..value_end])));
+                pos = value_end + 1;
+            }
+        }
+    }
+
+    match (stack.last(), root) {
+        (Some(element), _) => Err(format!("Unclosed element <{}>", element.name)),
+        (None, Some(root)) => Ok(root),
+        (None, None) => Err(String::from("The document has no root element")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_write() {
+        let document = "\u{FEFF}<?xpacket begin=\"\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+            <!-- A comment <with> markup -->\n\
+            <x:xmpmeta xmlns:x='adobe:ns:meta/'>\n\
+              <rdf:li xml:lang=\"x-default\">Caf&#233; &amp; &#x2603; &lt;tag&gt; &unknown;</rdf:li>\n\
+              <empty a=\"1\"/>\n\
+            </x:xmpmeta>\n\
+            <?xpacket end=\"w\"?>";
+        let root = parse_xml(document).unwrap();
+        assert_eq!(root.name, "x:xmpmeta");
+        assert_eq!(root.get_prefix(), Some("x"));
+        assert_eq!(root.get_attribute("xmlns:x"), Some("adobe:ns:meta/"));
+
+        let children: Vec<&XMLElement> = root.get_child_elements().collect();
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0].get_attribute("xml:lang"), Some("x-default"));
+        assert_eq!(children[0].get_text(), "Café & ☃ <tag> &unknown;");
+        assert_eq!(children[1].name, "empty");
+        assert!(children[1].children.is_empty());
+
+        // Writing it back gives the same tree
+        let written = parse_xml(&root.to_xml()).unwrap();
+        let children: Vec<&XMLElement> = written.get_child_elements().collect();
+        assert_eq!(children[0].get_text(), "Café & ☃ <tag> &unknown;");
+        assert_eq!(children[1].get_attribute("a"), Some("1"));
+    }
+
+    #[test]
+    fn invalid_documents() {
+        assert!(parse_xml("<a><b></a>").is_err());
+        assert!(parse_xml("<a>").is_err());
+        assert!(parse_xml("text only").is_err());
+    }
+}
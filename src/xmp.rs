@@ -0,0 +1,1240 @@
+use crate::checksum::md5;
+use crate::jpeg::{
+    build_segment, get_segments, replace_segments, JPEGSegment, APP1, MAX_SEGMENT_DATA_SIZE,
+};
+use crate::xml::{parse_xml, XMLElement, XMLNode};
+
+// See the XMP spec, part 3 (1.1.3)
+pub const XMP_IDENTIFIER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+pub const EXTENDED_XMP_IDENTIFIER: &[u8] = b"http://ns.adobe.com/xmp/extension/\0";
+pub const XMP_NOTE_NAMESPACE: &str = "http://ns.adobe.com/xmp/note/";
+
+// The GUID is the MD5 digest of the full extended XMP, as 32 uppercase hex digits
+const GUID_SIZE: usize = 32;
+// Identifier, GUID, full length and offset of the chunk
+const EXTENDED_XMP_HEADER_SIZE: usize = EXTENDED_XMP_IDENTIFIER.len() + GUID_SIZE + 4 + 4;
+// Size of the chunks written by Adobe's XMP toolkit
+const EXTENDED_XMP_CHUNK_SIZE: usize = 65400;
+// The standard packet must fit in a single APP1 segment
+const MAX_STANDARD_XMP_SIZE: usize = MAX_SEGMENT_DATA_SIZE - XMP_IDENTIFIER.len();
+
+// The XMP packets of a JPEG file. Packets too large for a single APP1 segment are split in a
+// standard packet and an extended one, spread over multiple segments.
+pub struct XMPPackets {
+    pub standard: Option<String>,
+    pub extended: Option<String>,
+    pub warnings: Vec<String>,
+}
+
+struct ExtendedXMPChunk {
+    guid: String,
+    full_length: usize,
+    offset: usize,
+    data: Vec<u8>,
+}
+
+impl ExtendedXMPChunk {
+    fn from(data: &[u8]) -> Option<Self> {
+        if data.len() < EXTENDED_XMP_HEADER_SIZE || !data.starts_with(EXTENDED_XMP_IDENTIFIER) {
+            return None;
+        }
+
+        let data = &data[EXTENDED_XMP_IDENTIFIER.len()..];
+        Some(Self {
+            guid: String::from_utf8_lossy(&data[0..GUID_SIZE]).to_string(),
+            full_length: u32::from_be_bytes(data[GUID_SIZE..GUID_SIZE + 4].try_into().unwrap())
+                as usize,
+            offset: u32::from_be_bytes(data[GUID_SIZE + 4..GUID_SIZE + 8].try_into().unwrap())
+                as usize,
+            data: data[GUID_SIZE + 8..].to_vec(),
+        })
+    }
+}
+
+pub fn is_xmp_segment(segment: &JPEGSegment, slice: &[u8]) -> bool {
+    segment.marker == APP1
+        && (segment.get_data(slice).starts_with(XMP_IDENTIFIER)
+            || segment.get_data(slice).starts_with(EXTENDED_XMP_IDENTIFIER))
+}
+
+pub fn get_md5_guid(slice: &[u8]) -> String {
+    md5(slice)
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<String>()
+}
+
+pub fn read_jpeg_xmp(slice: &[u8]) -> XMPPackets {
+    let mut warnings = Vec::new();
+    let mut standard: Option<String> = None;
+    let mut chunks = Vec::new();
+
+    for segment in get_segments(slice).map_while(Result::ok) {
+        if segment.marker != APP1 {
+            continue;
+        }
+
+        let data = segment.get_data(slice);
+        if data.starts_with(XMP_IDENTIFIER) {
+            if standard.is_some() {
+                warnings.push(format!(
+                    "Ignoring the extra XMP segment at offset {}",
+                    segment.offset
+                ));
+                continue;
+            }
+            standard = Some(String::from_utf8_lossy(&data[XMP_IDENTIFIER.len()..]).to_string());
+        } else if data.starts_with(EXTENDED_XMP_IDENTIFIER) {
+            match ExtendedXMPChunk::from(data) {
+                Some(chunk) => chunks.push(chunk),
+                None => warnings.push(format!(
+                    "Invalid extended XMP segment at offset {}",
+                    segment.offset
+                )),
+            }
+        }
+    }
+
+    let guid = standard.as_deref().and_then(get_extended_xmp_guid);
+    for chunk in chunks.iter() {
+        if guid.as_deref() != Some(chunk.guid.as_str()) {
+            warnings.push(format!(
+                "Ignoring an extended XMP chunk with the GUID {}, which is not referenced by the standard XMP",
+                chunk.guid
+            ));
+        }
+    }
+
+    let extended = match guid {
+        Some(guid) => {
+            let mut chunks: Vec<&ExtendedXMPChunk> =
+                chunks.iter().filter(|chunk| chunk.guid == guid).collect();
+            chunks.sort_by_key(|chunk| chunk.offset);
+            match assemble_extended_xmp(&guid, &chunks) {
+                Ok(extended) => Some(extended),
+                Err(e) => {
+                    warnings.push(e);
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+
+    XMPPackets {
+        standard,
+        extended,
+        warnings,
+    }
+}
+
+// The chunks must be sorted by offset
+fn assemble_extended_xmp(guid: &str, chunks: &[&ExtendedXMPChunk]) -> Result<String, String> {
+    let full_length = match chunks.first() {
+        Some(chunk) => chunk.full_length,
+        None => return Err(format!("The extended XMP {} is missing", guid)),
+    };
+    // The length is read from the file, so it is checked before being reserved
+    let chunks_size: usize = chunks.iter().map(|chunk| chunk.data.len()).sum();
+    if full_length > chunks_size {
+        return Err(format!(
+            "The chunks of the extended XMP {} only hold {} of its {} bytes",
+            guid, chunks_size, full_length
+        ));
+    }
+
+    let mut extended = Vec::with_capacity(full_length);
+    for chunk in chunks {
+        if chunk.full_length != full_length {
+            return Err(format!(
+                "The chunks of the extended XMP {} don't agree on its length",
+                guid
+            ));
+        }
+        // Duplicated chunks are allowed, as long as they cover the same bytes
+        if chunk.offset > extended.len() {
+            return Err(format!(
+                "The extended XMP {} is missing the bytes {} to {}",
+                guid,
+                extended.len(),
+                chunk.offset
+            ));
+        }
+        let end = chunk.offset + chunk.data.len();
+        if end > extended.len() {
+            extended.extend_from_slice(&chunk.data[extended.len() - chunk.offset..]);
+        }
+    }
+
+    if extended.len() != full_length {
+        return Err(format!(
+            "The extended XMP {} is {} bytes long instead of {}",
+            guid,
+            extended.len(),
+            full_length
+        ));
+    }
+
+    let digest = get_md5_guid(&extended);
+    if !digest.eq_ignore_ascii_case(guid) {
+        return Err(format!(
+            "The MD5 digest of the extended XMP ({}) doesn't match its GUID ({})",
+            digest, guid
+        ));
+    }
+
+    String::from_utf8(extended).map_err(|_| format!("The extended XMP {} is not valid UTF-8", guid))
+}
+
+// Reads the value of xmpNote:HasExtendedXMP, written either as an attribute or as an element
+pub fn get_extended_xmp_guid(packet: &str) -> Option<String> {
+    let start = packet.find("HasExtendedXMP")? + "HasExtendedXMP".len();
+    let value = packet[start..].trim_start_matches(['=', '"', '\'', '>', ' ']);
+    let guid = value.get(0..GUID_SIZE)?;
+
+    if guid.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(guid.to_string())
+    } else {
+        None
+    }
+}
+
+// Adds, updates or removes (when `guid` is None) the xmpNote:HasExtendedXMP property
+pub fn set_extended_xmp_guid(packet: &str, guid: Option<&str>) -> Result<String, String> {
+    if let Some(start) = packet.find("xmpNote:HasExtendedXMP") {
+        let is_element = packet[..start].ends_with('<');
+        let (property_start, property_end) = if is_element {
+            let closing_tag = "</xmpNote:HasExtendedXMP>";
+            match packet[start..].find(closing_tag) {
+                Some(end) => (start - 1, start + end + closing_tag.len()),
+                None => return Err(String::from("Unclosed xmpNote:HasExtendedXMP element")),
+            }
+        } else {
+            let value_start = match packet[start..].find('"') {
+                Some(quote) => start + quote + 1,
+                None => return Err(String::from("Invalid xmpNote:HasExtendedXMP attribute")),
+            };
+            match packet[value_start..].find('"') {
+                // Also remove the whitespace before the attribute
+                Some(end) => (packet[..start].trim_end().len(), value_start + end + 1),
+                None => return Err(String::from("Invalid xmpNote:HasExtendedXMP attribute")),
+            }
+        };
+
+        let property = match guid {
+            Some(guid) if is_element => {
+                format!("<xmpNote:HasExtendedXMP>{}</xmpNote:HasExtendedXMP>", guid)
+            }
+            Some(guid) => format!(" xmpNote:HasExtendedXMP=\"{}\"", guid),
+            None => String::new(),
+        };
+        return Ok(format!(
+            "{}{}{}",
+            &packet[..property_start],
+            property,
+            &packet[property_end..]
+        ));
+    }
+
+    let guid = match guid {
+        Some(guid) => guid,
+        None => return Ok(packet.to_string()),
+    };
+    let description = "<rdf:Description";
+    let insert_at = match packet.find(description) {
+        Some(start) => start + description.len(),
+        None => return Err(String::from("The XMP packet has no rdf:Description")),
+    };
+    let namespace = if packet.contains("xmlns:xmpNote=") {
+        String::new()
+    } else {
+        format!(" xmlns:xmpNote=\"{}\"", XMP_NOTE_NAMESPACE)
+    };
+
+    Ok(format!(
+        "{}{} xmpNote:HasExtendedXMP=\"{}\"{}",
+        &packet[..insert_at],
+        namespace,
+        guid,
+        &packet[insert_at..]
+    ))
+}
+
+// The index following the '>' that ends the tag starting at `start`
+fn get_tag_end(packet: &str, start: usize) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in packet[start..].char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '>') => return Some(start + i + 1),
+            _ => (),
+        }
+    }
+    None
+}
+
+// The index following the end of the element starting at `start`
+fn get_element_end(packet: &str, start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut pos = start;
+    loop {
+        pos += packet[pos..].find('<')?;
+        let rest = &packet[pos..];
+        if rest.starts_with("<!--") {
+            pos += rest.find("-->")? + 3;
+        } else if rest.starts_with("<![CDATA[") {
+            pos += rest.find("]]>")? + 3;
+        } else if rest.starts_with("<?") {
+            pos += rest.find("?>")? + 2;
+        } else if rest.starts_with("</") {
+            pos += rest.find('>')? + 1;
+            depth -= 1;
+        } else {
+            let end = get_tag_end(packet, pos)?;
+            if !packet[..end - 1].ends_with('/') {
+                depth += 1;
+            }
+            pos = end;
+        }
+        if depth == 0 {
+            return Some(pos);
+        }
+    }
+}
+
+// The byte ranges of the property elements of the rdf:Description elements of a packet
+fn get_property_elements(packet: &str) -> Result<Vec<(usize, usize)>, String> {
+    let invalid = || String::from("Invalid rdf:Description in the XMP packet");
+    let mut elements = Vec::new();
+    let mut pos = 0;
+
+    while let Some(start) = packet[pos..].find("<rdf:Description") {
+        pos = get_tag_end(packet, pos + start).ok_or_else(invalid)?;
+        if packet[..pos].ends_with("/>") {
+            continue;
+        }
+        // The nested rdf:Description of structures are skipped with their property
+        loop {
+            let rest = packet[pos..].trim_start();
+            pos = packet.len() - rest.len();
+            if rest.starts_with("</") || rest.is_empty() {
+                break;
+            } else if rest.starts_with("<!--") {
+                pos += rest.find("-->").ok_or_else(invalid)? + 3;
+            } else if rest.starts_with('<') {
+                let end = get_element_end(packet, pos).ok_or_else(invalid)?;
+                elements.push((pos, end));
+                pos = end;
+            } else {
+                pos += rest.find('<').ok_or_else(invalid)?;
+            }
+        }
+    }
+
+    Ok(elements)
+}
+
+// The xmlns attributes of the x:xmpmeta, rdf:RDF and rdf:Description tags, which the properties
+// moved to the extended packet may use
+fn get_namespace_declarations(packet: &str) -> Vec<&str> {
+    let mut declarations: Vec<&str> = Vec::new();
+    for name in ["<x:xmpmeta", "<rdf:RDF", "<rdf:Description"] {
+        for (start, _) in packet.match_indices(name) {
+            let end = match get_tag_end(packet, start) {
+                Some(end) => end,
+                None => continue,
+            };
+            let tag = &packet[start..end];
+            for (i, _) in tag.match_indices(" xmlns:") {
+                let attribute = &tag[i + 1..];
+                let value_start = match attribute.find(['"', '\'']) {
+                    Some(quote) => quote + 1,
+                    None => continue,
+                };
+                let quote = &attribute[value_start - 1..value_start];
+                if let Some(value_end) = attribute[value_start..].find(quote) {
+                    let declaration = &attribute[..value_start + value_end + 1];
+                    let prefix = &declaration[..declaration.find('=').unwrap()];
+                    if !declarations.iter().any(|other| other.starts_with(prefix)) {
+                        declarations.push(declaration);
+                    }
+                }
+            }
+        }
+    }
+    declarations
+}
+
+// Moves the largest property elements of the standard packet to the extended one until the
+// standard packet fits in a segment, as the XMP spec (part 3, 1.1.3.1) recommends. The packets
+// are otherwise written as they are.
+fn split_standard_xmp(
+    standard: &str,
+    extended: Option<&str>,
+) -> Result<(String, Option<String>), String> {
+    let standard = set_extended_xmp_guid(standard, None)?;
+    let mut elements = get_property_elements(&standard)?;
+    elements.sort_by_key(|(start, end)| end - start);
+    let mut moved: Vec<(usize, usize)> = Vec::new();
+
+    let remaining = loop {
+        let mut remaining = String::new();
+        let mut pos = 0;
+        let mut ranges = moved.clone();
+        ranges.sort();
+        for (start, end) in ranges {
+            remaining.push_str(&standard[pos..start]);
+            pos = end;
+        }
+        remaining.push_str(&standard[pos..]);
+
+        // Any GUID gives the size of the packet, they all have the same length
+        let guid = "0".repeat(GUID_SIZE);
+        let has_extended = extended.is_some() || !moved.is_empty();
+        let packet = set_extended_xmp_guid(&remaining, has_extended.then_some(guid.as_str()))?;
+        if packet.len() <= MAX_STANDARD_XMP_SIZE {
+            break remaining;
+        }
+        match elements.pop() {
+            Some(element) => moved.push(element),
+            None => return Err(String::from("The standard XMP packet is too large")),
+        }
+    };
+
+    if moved.is_empty() {
+        return Ok((remaining, extended.map(String::from)));
+    }
+
+    let description = format!(
+        "<rdf:Description rdf:about=\"\" {}>{}</rdf:Description>",
+        get_namespace_declarations(&standard).join(" "),
+        moved
+            .iter()
+            .map(|(start, end)| &standard[*start..*end])
+            .collect::<String>()
+    );
+    let extended = match extended {
+        Some(extended) => match extended.rfind("</rdf:RDF>") {
+            Some(end) => format!("{}{}{}", &extended[..end], description, &extended[end..]),
+            None => return Err(String::from("The extended XMP packet has no rdf:RDF")),
+        },
+        None => format!(
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"><rdf:RDF xmlns:rdf=\"{}\">{}</rdf:RDF></x:xmpmeta>",
+            RDF_NAMESPACE, description
+        ),
+    };
+    Ok((remaining, Some(extended)))
+}
+
+// Builds the APP1 segments for the standard XMP packet and the chunks of the extended one
+pub fn build_jpeg_xmp_segments(
+    standard: &str,
+    extended: Option<&str>,
+) -> Result<Vec<Vec<u8>>, String> {
+    let (standard, extended) = split_standard_xmp(standard, extended)?;
+    let guid = extended
+        .as_ref()
+        .map(|extended| get_md5_guid(extended.as_bytes()));
+    let standard = set_extended_xmp_guid(&standard, guid.as_deref())?;
+
+    let mut segments = vec![
+        build_segment(APP1, &[XMP_IDENTIFIER, standard.as_bytes()].concat())
+            .map_err(|e| format!("The standard XMP packet is too large: {}", e))?,
+    ];
+
+    if let (Some(extended), Some(guid)) = (extended, guid) {
+        let full_length = (extended.len() as u32).to_be_bytes();
+        for (i, chunk) in extended
+            .as_bytes()
+            .chunks(EXTENDED_XMP_CHUNK_SIZE)
+            .enumerate()
+        {
+            let offset = ((i * EXTENDED_XMP_CHUNK_SIZE) as u32).to_be_bytes();
+            segments.push(build_segment(
+                APP1,
+                &[
+                    EXTENDED_XMP_IDENTIFIER,
+                    guid.as_bytes(),
+                    &full_length,
+                    &offset,
+                    chunk,
+                ]
+                .concat(),
+            )?);
+        }
+    }
+
+    Ok(segments)
+}
+
+// Replaces all the XMP segments of the JPEG file, or removes them if `standard` is None
+pub fn write_jpeg_xmp(
+    slice: &[u8],
+    standard: Option<&str>,
+    extended: Option<&str>,
+) -> Result<Vec<u8>, String> {
+    let segments = match standard {
+        Some(standard) => build_jpeg_xmp_segments(standard, extended)?,
+        None => Vec::new(),
+    };
+    replace_segments(slice, is_xmp_segment, &segments)
+}
+
+pub const RDF_NAMESPACE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+pub const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+const X_NAMESPACE: &str = "adobe:ns:meta/";
+
+// Prefixes that can be used to edit a property that is not in the packet yet
+pub const KNOWN_NAMESPACES: [(&str, &str); 15] = [
+    ("dc", "http://purl.org/dc/elements/1.1/"),
+    ("xmp", "http://ns.adobe.com/xap/1.0/"),
+    ("xmpRights", "http://ns.adobe.com/xap/1.0/rights/"),
+    ("xmpMM", "http://ns.adobe.com/xap/1.0/mm/"),
+    ("xmpNote", XMP_NOTE_NAMESPACE),
+    ("photoshop", "http://ns.adobe.com/photoshop/1.0/"),
+    ("tiff", "http://ns.adobe.com/tiff/1.0/"),
+    ("exif", "http://ns.adobe.com/exif/1.0/"),
+    ("exifEX", "http://cipa.jp/exif/1.0/"),
+    ("aux", "http://ns.adobe.com/exif/1.0/aux/"),
+    ("crs", "http://ns.adobe.com/camera-raw-settings/1.0/"),
+    ("lr", "http://ns.adobe.com/lightroom/1.0/"),
+    (
+        "Iptc4xmpCore",
+        "http://iptc.org/std/Iptc4xmpCore/1.0/xmlns/",
+    ),
+    ("Iptc4xmpExt", "http://iptc.org/std/Iptc4xmpExt/2008-02-29/"),
+    ("plus", "http://ns.useplus.org/ldf/xmp/1.0/"),
+];
+
+// Padding added at the end of the packet, so that other tools can edit it in place
+const PACKET_PADDING: usize = 2048;
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum XMPArrayType {
+    Bag, // Unordered
+    Seq, // Ordered
+    Alt, // Alternatives, usually one per language
+}
+
+#[derive(Clone)]
+pub enum XMPValue {
+    Simple(String),
+    Uri(String),
+    Array(XMPArrayType, Vec<XMPItem>),
+    Struct(Vec<XMPProperty>),
+}
+
+#[derive(Clone)]
+pub struct XMPItem {
+    pub language: Option<String>, // xml:lang qualifier
+    pub value: XMPValue,
+}
+
+#[derive(Clone)]
+pub struct XMPProperty {
+    pub namespace: String,
+    pub name: String, // Without the prefix
+    pub value: XMPValue,
+}
+
+// How the properties that can be edited from a single value are stored
+#[derive(PartialEq)]
+enum XMPForm {
+    Simple,
+    LangAlt,
+    Array(XMPArrayType),
+}
+
+// See the XMP spec, part 2 (8.3) for the Dublin Core properties
+fn get_form(namespace: &str, name: &str) -> XMPForm {
+    match (namespace, name) {
+        ("http://purl.org/dc/elements/1.1/", "title" | "description" | "rights")
+        | ("http://ns.adobe.com/xap/1.0/rights/", "UsageTerms") => XMPForm::LangAlt,
+        ("http://purl.org/dc/elements/1.1/", "creator" | "date") => {
+            XMPForm::Array(XMPArrayType::Seq)
+        }
+        (
+            "http://purl.org/dc/elements/1.1/",
+            "subject" | "publisher" | "contributor" | "type" | "language" | "relation",
+        )
+        | ("http://ns.adobe.com/lightroom/1.0/", "hierarchicalSubject")
+        | ("http://ns.adobe.com/photoshop/1.0/", "SupplementalCategories") => {
+            XMPForm::Array(XMPArrayType::Bag)
+        }
+        _ => XMPForm::Simple,
+    }
+}
+
+// The properties of an XMP packet, as a namespace-aware tree. The rdf:Description elements are
+// merged together, and the qualifiers other than xml:lang are not kept.
+#[derive(Clone)]
+pub struct XMPMeta {
+    about: String,
+    namespaces: Vec<(String, String)>, // Prefix and URI
+    properties: Vec<XMPProperty>,
+}
+
+// Prefix and URI of the namespaces declared on the element and its ancestors, the innermost last
+type Scope = Vec<(String, String)>;
+
+fn get_scope(parent: &Scope, element: &XMLElement) -> Scope {
+    let mut scope = parent.clone();
+    for (name, value) in element.attributes.iter() {
+        if let Some(prefix) = name.strip_prefix("xmlns:") {
+            scope.push((prefix.to_string(), value.clone()));
+        }
+    }
+    scope
+}
+
+// Returns the namespace URI and the local name
+fn resolve<'a>(scope: &Scope, name: &'a str) -> Result<(String, &'a str), String> {
+    let (prefix, local_name) = match name.split_once(':') {
+        Some(split) => split,
+        None => return Err(format!("The XMP name {} has no namespace", name)),
+    };
+    if prefix == "xml" {
+        return Ok((XML_NAMESPACE.to_string(), local_name));
+    }
+
+    match scope.iter().rev().find(|(other, _)| other == prefix) {
+        Some((_, namespace)) => Ok((namespace.clone(), local_name)),
+        None => Err(format!("The namespace prefix {} is not declared", prefix)),
+    }
+}
+
+fn is_rdf(scope: &Scope, name: &str, local_name: &str) -> bool {
+    matches!(resolve(scope, name), Ok((namespace, other)) if namespace == RDF_NAMESPACE && other == local_name)
+}
+
+fn get_rdf_attribute<'a>(
+    scope: &Scope,
+    element: &'a XMLElement,
+    local_name: &str,
+) -> Option<&'a str> {
+    element
+        .attributes
+        .iter()
+        .find(|(name, _)| is_rdf(scope, name, local_name))
+        .map(|(_, value)| value.as_str())
+}
+
+// Attributes that are not properties
+fn is_syntax_attribute(scope: &Scope, name: &str) -> bool {
+    name.starts_with("xmlns")
+        || matches!(resolve(scope, name), Ok((namespace, _)) if namespace == RDF_NAMESPACE || namespace == XML_NAMESPACE)
+}
+
+impl XMPMeta {
+    pub fn new() -> Self {
+        Self {
+            about: String::new(),
+            namespaces: Vec::new(),
+            properties: Vec::new(),
+        }
+    }
+
+    pub fn from(packet: &str) -> Result<Self, String> {
+        let root = parse_xml(packet)?;
+        let mut meta = Self::new();
+
+        let (rdf, scope) = match find_rdf(&root, &Vec::new()) {
+            Some(rdf) => rdf,
+            None => return Err(String::from("The XMP packet has no rdf:RDF element")),
+        };
+        for description in rdf.get_child_elements() {
+            let scope = get_scope(&scope, description);
+            if !is_rdf(&scope, &description.name, "Description") {
+                continue;
+            }
+            if let Some(about) = get_rdf_attribute(&scope, description, "about") {
+                meta.about = about.to_string();
+            }
+            let properties = meta.parse_description(description, &scope)?;
+            meta.properties.extend(properties);
+        }
+
+        Ok(meta)
+    }
+
+    // Makes sure that every namespace has its own prefix
+    fn register_namespace(&mut self, prefix: &str, namespace: &str) {
+        if self.namespaces.iter().any(|(_, other)| other == namespace) {
+            return;
+        }
+
+        let mut prefix = prefix.to_string();
+        let mut i = 1;
+        while self.namespaces.iter().any(|(other, _)| *other == prefix) {
+            prefix = format!("ns{}", i);
+            i += 1;
+        }
+        self.namespaces.push((prefix, namespace.to_string()));
+    }
+
+    pub fn get_prefix(&self, namespace: &str) -> &str {
+        self.namespaces
+            .iter()
+            .find(|(_, other)| other == namespace)
+            .map(|(prefix, _)| prefix.as_str())
+            .unwrap_or("?")
+    }
+
+    fn parse_property(
+        &mut self,
+        element: &XMLElement,
+        scope: &Scope,
+    ) -> Result<XMPProperty, String> {
+        let scope = get_scope(scope, element);
+        let (namespace, name) = resolve(&scope, &element.name)?;
+        self.register_namespace(element.get_prefix().unwrap_or_default(), &namespace);
+
+        Ok(XMPProperty {
+            namespace,
+            name: name.to_string(),
+            value: self.parse_value(element, &scope)?,
+        })
+    }
+
+    // Properties written as attributes of the element, and as its child elements
+    fn parse_description(
+        &mut self,
+        element: &XMLElement,
+        scope: &Scope,
+    ) -> Result<Vec<XMPProperty>, String> {
+        let mut properties = Vec::new();
+        for (name, value) in element.attributes.iter() {
+            if is_syntax_attribute(scope, name) {
+                continue;
+            }
+            let (namespace, local_name) = resolve(scope, name)?;
+            self.register_namespace(name.split_once(':').unwrap().0, &namespace);
+            properties.push(XMPProperty {
+                namespace,
+                name: local_name.to_string(),
+                value: XMPValue::Simple(value.clone()),
+            });
+        }
+        for child in element.get_child_elements() {
+            properties.push(self.parse_property(child, scope)?);
+        }
+
+        Ok(properties)
+    }
+
+    // See the XMP spec, part 1 (C.2) for the forms a value can take
+    fn parse_value(&mut self, element: &XMLElement, scope: &Scope) -> Result<XMPValue, String> {
+        if let Some(uri) = get_rdf_attribute(scope, element, "resource") {
+            return Ok(XMPValue::Uri(uri.to_string()));
+        }
+        if get_rdf_attribute(scope, element, "parseType") == Some("Resource") {
+            let mut fields = Vec::new();
+            for child in element.get_child_elements() {
+                fields.push(self.parse_property(child, scope)?);
+            }
+            return Ok(XMPValue::Struct(fields));
+        }
+
+        let child = match element.get_child_elements().next() {
+            Some(child) => child,
+            None => {
+                // A struct written with its fields as attributes
+                return Ok(
+                    if element
+                        .attributes
+                        .iter()
+                        .any(|(name, _)| !is_syntax_attribute(scope, name))
+                    {
+                        XMPValue::Struct(self.parse_description(element, scope)?)
+                    } else {
+                        XMPValue::Simple(element.get_text())
+                    },
+                );
+            }
+        };
+
+        let child_scope = get_scope(scope, child);
+        let array_type = if is_rdf(&child_scope, &child.name, "Bag") {
+            Some(XMPArrayType::Bag)
+        } else if is_rdf(&child_scope, &child.name, "Seq") {
+            Some(XMPArrayType::Seq)
+        } else if is_rdf(&child_scope, &child.name, "Alt") {
+            Some(XMPArrayType::Alt)
+        } else {
+            None
+        };
+
+        match array_type {
+            Some(array_type) => {
+                let mut items = Vec::new();
+                for item in child.get_child_elements() {
+                    let item_scope = get_scope(&child_scope, item);
+                    if !is_rdf(&item_scope, &item.name, "li") {
+                        continue;
+                    }
+                    items.push(XMPItem {
+                        language: item.get_attribute("xml:lang").map(|lang| lang.to_string()),
+                        value: self.parse_value(item, &item_scope)?,
+                    });
+                }
+                Ok(XMPValue::Array(array_type, items))
+            }
+            None if is_rdf(&child_scope, &child.name, "Description") => Ok(XMPValue::Struct(
+                self.parse_description(child, &child_scope)?,
+            )),
+            None => {
+                let mut fields = Vec::new();
+                for child in element.get_child_elements() {
+                    fields.push(self.parse_property(child, scope)?);
+                }
+                Ok(XMPValue::Struct(fields))
+            }
+        }
+    }
+
+    pub fn get_properties(&self) -> &[XMPProperty] {
+        self.properties.as_ref()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.properties.is_empty()
+    }
+
+    // Resolves a name such as dc:title, using the prefixes of the packet first
+    pub fn resolve_name(&self, name: &str) -> Result<(String, String), String> {
+        let (prefix, local_name) = match name.split_once(':') {
+            Some(split) => split,
+            None => {
+                return Err(format!(
+                    "Expected a prefixed name (such as dc:title), got {}",
+                    name
+                ))
+            }
+        };
+
+        let namespace = self
+            .namespaces
+            .iter()
+            .find(|(other, _)| other == prefix)
+            .map(|(_, namespace)| namespace.as_str())
+            .or_else(|| {
+                KNOWN_NAMESPACES
+                    .iter()
+                    .find(|(other, _)| *other == prefix)
+                    .map(|(_, namespace)| *namespace)
+            });
+        match namespace {
+            Some(namespace) => Ok((namespace.to_string(), local_name.to_string())),
+            None => Err(format!("Unknown XMP namespace prefix {}", prefix)),
+        }
+    }
+
+    pub fn get_property(&self, name: &str) -> Option<&XMPValue> {
+        let (namespace, name) = self.resolve_name(name).ok()?;
+        self.properties
+            .iter()
+            .find(|property| property.namespace == namespace && property.name == name)
+            .map(|property| &property.value)
+    }
+
+    // The value of a property as text: the default language of an alternative, or the items of
+    // other arrays joined with "; "
+    pub fn get_text(&self, name: &str) -> Option<String> {
+        let get_item_text = |item: &XMPItem| match &item.value {
+            XMPValue::Simple(text) | XMPValue::Uri(text) => Some(text.clone()),
+            _ => None,
+        };
+
+        match self.get_property(name)? {
+            XMPValue::Simple(text) | XMPValue::Uri(text) => Some(text.clone()),
+            XMPValue::Array(XMPArrayType::Alt, items) => items
+                .iter()
+                .find(|item| item.language.as_deref() == Some("x-default"))
+                .or(items.first())
+                .and_then(get_item_text),
+            XMPValue::Array(_, items) => Some(
+                items
+                    .iter()
+                    .filter_map(get_item_text)
+                    .collect::<Vec<String>>()
+                    .join("; "),
+            ),
+            XMPValue::Struct(_) => None,
+        }
+    }
+
+    pub fn set_property(&mut self, name: &str, value: XMPValue) -> Result<(), String> {
+        let (namespace, local_name) = self.resolve_name(name)?;
+        self.register_namespace(name.split_once(':').unwrap().0, &namespace);
+
+        match self
+            .properties
+            .iter_mut()
+            .find(|property| property.namespace == namespace && property.name == local_name)
+        {
+            Some(property) => property.value = value,
+            None => self.properties.push(XMPProperty {
+                namespace,
+                name: local_name,
+                value,
+            }),
+        }
+        Ok(())
+    }
+
+    // Returns false if the property didn't exist
+    pub fn remove_property(&mut self, name: &str) -> Result<bool, String> {
+        let (namespace, local_name) = self.resolve_name(name)?;
+        let len = self.properties.len();
+        self.properties
+            .retain(|property| property.namespace != namespace || property.name != local_name);
+        Ok(self.properties.len() != len)
+    }
+
+    // Sets a property from a single value, using the form the spec requires for it: the default
+    // language of dc:title is replaced, dc:creator becomes a list with a single name, ...
+    pub fn set_text(&mut self, name: &str, text: &str) -> Result<(), String> {
+        let (namespace, local_name) = self.resolve_name(name)?;
+        let value = match get_form(&namespace, &local_name) {
+            XMPForm::Simple => {
+                if name == "xmp:Rating" {
+                    match text.parse::<f64>() {
+                        Ok(rating) if rating == -1.0 || (0.0..=5.0).contains(&rating) => {}
+                        _ => {
+                            return Err(format!(
+                                "Invalid rating {} (expected -1 for rejected, or 0 to 5)",
+                                text
+                            ))
+                        }
+                    }
+                }
+                XMPValue::Simple(text.to_string())
+            }
+            XMPForm::LangAlt => {
+                let mut items = match self.get_property(name) {
+                    Some(XMPValue::Array(XMPArrayType::Alt, items)) => items.clone(),
+                    _ => Vec::new(),
+                };
+                items.retain(|item| item.language.as_deref() != Some("x-default"));
+                // The default value must come first
+                items.insert(
+                    0,
+                    XMPItem {
+                        language: Some(String::from("x-default")),
+                        value: XMPValue::Simple(text.to_string()),
+                    },
+                );
+                XMPValue::Array(XMPArrayType::Alt, items)
+            }
+            XMPForm::Array(array_type) => XMPValue::Array(
+                array_type,
+                vec![XMPItem {
+                    language: None,
+                    value: XMPValue::Simple(text.to_string()),
+                }],
+            ),
+        };
+
+        self.set_property(name, value)
+    }
+
+    // Sets a list of values, such as keywords (dc:subject) or authors (dc:creator)
+    pub fn set_list(&mut self, name: &str, values: &[String]) -> Result<(), String> {
+        let (namespace, local_name) = self.resolve_name(name)?;
+        let array_type = match get_form(&namespace, &local_name) {
+            XMPForm::Array(array_type) => array_type,
+            XMPForm::Simple if self.get_property(name).is_none() => XMPArrayType::Bag,
+            _ => return Err(format!("{} is not a list", name)),
+        };
+
+        self.set_property(
+            name,
+            XMPValue::Array(
+                array_type,
+                values
+                    .iter()
+                    .map(|value| XMPItem {
+                        language: None,
+                        value: XMPValue::Simple(value.clone()),
+                    })
+                    .collect(),
+            ),
+        )
+    }
+
+    // Whether the property holds a list of values (keywords, authors...) rather than a single one
+    pub fn is_list(&self, name: &str) -> bool {
+        match self.get_property(name) {
+            Some(XMPValue::Array(array_type, _)) => *array_type != XMPArrayType::Alt,
+            Some(_) => false,
+            None => self.resolve_name(name).is_ok_and(|(namespace, name)| {
+                matches!(get_form(&namespace, &name), XMPForm::Array(_))
+            }),
+        }
+    }
+
+    // The simple values of a Bag or Seq property
+    pub fn get_list(&self, name: &str) -> Option<Vec<String>> {
+        match self.get_property(name)? {
+            XMPValue::Array(XMPArrayType::Bag | XMPArrayType::Seq, items) => Some(
+                items
+                    .iter()
+                    .filter_map(|item| match &item.value {
+                        XMPValue::Simple(text) | XMPValue::Uri(text) => Some(text.clone()),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    pub fn get_qualified_name(&self, property: &XMPProperty) -> String {
+        format!("{}:{}", self.get_prefix(&property.namespace), property.name)
+    }
+
+    // Flattens the tree into names and values, arrays of simple values are joined and the fields
+    // of structs are shown as paths (exif:Flash/exif:Fired)
+    pub fn get_all_properties(&self) -> Vec<(String, String)> {
+        let mut res = Vec::new();
+        for property in self.properties.iter() {
+            self.flatten(self.get_qualified_name(property), &property.value, &mut res);
+        }
+        res
+    }
+
+    fn flatten(&self, name: String, value: &XMPValue, res: &mut Vec<(String, String)>) {
+        match value {
+            XMPValue::Simple(text) | XMPValue::Uri(text) => res.push((name, text.clone())),
+            XMPValue::Array(_, items)
+                if items
+                    .iter()
+                    .all(|item| matches!(item.value, XMPValue::Simple(_) | XMPValue::Uri(_))) =>
+            {
+                let values: Vec<String> = items
+                    .iter()
+                    .map(|item| {
+                        let text = match &item.value {
+                            XMPValue::Simple(text) | XMPValue::Uri(text) => text.as_str(),
+                            _ => "",
+                        };
+                        match item.language.as_deref() {
+                            Some(language) if language != "x-default" => {
+                                format!("{} ({})", text, language)
+                            }
+                            _ => text.to_string(),
+                        }
+                    })
+                    .collect();
+                res.push((name, values.join(", ")));
+            }
+            XMPValue::Array(_, items) => {
+                for (i, item) in items.iter().enumerate() {
+                    self.flatten(format!("{}[{}]", name, i + 1), &item.value, res);
+                }
+            }
+            XMPValue::Struct(fields) => {
+                for field in fields.iter() {
+                    self.flatten(
+                        format!("{}/{}", name, self.get_qualified_name(field)),
+                        &field.value,
+                        res,
+                    );
+                }
+            }
+        }
+    }
+
+    fn fill_element(&self, element: &mut XMLElement, value: &XMPValue) {
+        match value {
+            XMPValue::Simple(text) => element.children.push(XMLNode::Text(text.clone())),
+            XMPValue::Uri(uri) => element
+                .attributes
+                .push((String::from("rdf:resource"), uri.clone())),
+            XMPValue::Array(array_type, items) => {
+                let mut array = XMLElement::new(match array_type {
+                    XMPArrayType::Bag => "rdf:Bag",
+                    XMPArrayType::Seq => "rdf:Seq",
+                    XMPArrayType::Alt => "rdf:Alt",
+                });
+                for item in items.iter() {
+                    let mut li = XMLElement::new("rdf:li");
+                    if let Some(language) = &item.language {
+                        li.attributes
+                            .push((String::from("xml:lang"), language.clone()));
+                    }
+                    self.fill_element(&mut li, &item.value);
+                    array.children.push(XMLNode::Element(li));
+                }
+                element.children.push(XMLNode::Element(array));
+            }
+            XMPValue::Struct(fields) => {
+                element
+                    .attributes
+                    .push((String::from("rdf:parseType"), String::from("Resource")));
+                for field in fields.iter() {
+                    element
+                        .children
+                        .push(XMLNode::Element(self.get_property_element(field)));
+                }
+            }
+        }
+    }
+
+    fn get_property_element(&self, property: &XMPProperty) -> XMLElement {
+        let mut element = XMLElement::new(&self.get_qualified_name(property));
+        self.fill_element(&mut element, &property.value);
+        element
+    }
+
+    // Serializes the properties into an x:xmpmeta element, with a single rdf:Description
+    pub fn to_xml(&self) -> String {
+        let mut description = XMLElement::new("rdf:Description");
+        description
+            .attributes
+            .push((String::from("rdf:about"), self.about.clone()));
+        for (prefix, namespace) in self.namespaces.iter() {
+            if namespace != RDF_NAMESPACE && namespace != XML_NAMESPACE {
+                description
+                    .attributes
+                    .push((format!("xmlns:{}", prefix), namespace.clone()));
+            }
+        }
+        for property in self.properties.iter() {
+            description
+                .children
+                .push(XMLNode::Element(self.get_property_element(property)));
+        }
+
+        let mut rdf = XMLElement::new("rdf:RDF");
+        rdf.attributes
+            .push((String::from("xmlns:rdf"), String::from(RDF_NAMESPACE)));
+        rdf.children.push(XMLNode::Element(description));
+        let mut xmpmeta = XMLElement::new("x:xmpmeta");
+        xmpmeta
+            .attributes
+            .push((String::from("xmlns:x"), String::from(X_NAMESPACE)));
+        xmpmeta.children.push(XMLNode::Element(rdf));
+        xmpmeta.to_xml()
+    }
+
+    // The x:xmpmeta element wrapped in a packet
+    pub fn to_packet(&self) -> String {
+        let padding = format!("{}\n", " ".repeat(99)).repeat(PACKET_PADDING / 100);
+        format!(
+            "<?xpacket begin=\"\u{FEFF}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n{}{}<?xpacket end=\"w\"?>",
+            self.to_xml(),
+            padding
+        )
+    }
+}
+
+// Looks for the rdf:RDF element, which is usually wrapped in an x:xmpmeta element
+fn find_rdf<'a>(element: &'a XMLElement, parent_scope: &Scope) -> Option<(&'a XMLElement, Scope)> {
+    let scope = get_scope(parent_scope, element);
+    if is_rdf(&scope, &element.name, "RDF") {
+        return Some((element, scope));
+    }
+    element
+        .get_child_elements()
+        .find_map(|child| find_rdf(child, &scope))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jpeg::{EOI, SOI};
+
+    fn build_jpeg(standard: &str, extended: Option<&str>) -> Vec<u8> {
+        let segments = build_jpeg_xmp_segments(standard, extended).unwrap();
+        [vec![0xFF, SOI], segments.concat(), vec![0xFF, EOI]].concat()
+    }
+
+    fn build_packet(properties: &str) -> String {
+        format!(
+            "<?xpacket begin=\"\u{FEFF}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+             <x:xmpmeta xmlns:x=\"adobe:ns:meta/\"><rdf:RDF xmlns:rdf=\"{}\">\
+             <rdf:Description rdf:about=\"\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\" \
+             xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\">{}</rdf:Description>\
+             </rdf:RDF></x:xmpmeta>\n<?xpacket end=\"w\"?>",
+            RDF_NAMESPACE, properties
+        )
+    }
+
+    #[test]
+    fn extended_xmp_round_trip() {
+        let standard = build_packet("<xmp:Rating>3</xmp:Rating>");
+        let extended = format!(
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"><!-- {} --></x:xmpmeta>",
+            "0123456789".repeat(20000)
+        );
+        let jpeg = build_jpeg(&standard, Some(&extended));
+        let guid = get_md5_guid(extended.as_bytes());
+
+        let chunks = get_segments(&jpeg)
+            .map_while(Result::ok)
+            .filter(|segment| segment.get_data(&jpeg).starts_with(EXTENDED_XMP_IDENTIFIER))
+            .count();
+        assert_eq!(chunks, extended.len().div_ceil(EXTENDED_XMP_CHUNK_SIZE));
+
+        let packets = read_jpeg_xmp(&jpeg);
+        assert!(packets.warnings.is_empty());
+        let read_standard = packets.standard.unwrap();
+        assert_eq!(get_extended_xmp_guid(&read_standard), Some(guid.clone()));
+        assert!(read_standard.contains("<xmp:Rating>3</xmp:Rating>"));
+        assert_eq!(packets.extended.as_deref(), Some(extended.as_str()));
+
+        // A corrupted chunk no longer matches the GUID
+        let mut corrupted = jpeg.clone();
+        let last_chunk = corrupted.len() - 100;
+        corrupted[last_chunk] = b'X';
+        let packets = read_jpeg_xmp(&corrupted);
+        assert_eq!(packets.extended, None);
+        assert!(packets.warnings[0].contains("MD5"));
+    }
+
+    #[test]
+    fn set_extended_xmp_guid_forms() {
+        let guid = "0123456789ABCDEF0123456789ABCDEF";
+        let packet = build_packet("<xmp:Rating>3</xmp:Rating>");
+        let with_guid = set_extended_xmp_guid(&packet, Some(guid)).unwrap();
+        assert_eq!(get_extended_xmp_guid(&with_guid).as_deref(), Some(guid));
+        assert!(with_guid.contains(XMP_NOTE_NAMESPACE));
+        let without_guid = set_extended_xmp_guid(&with_guid, None).unwrap();
+        assert_eq!(get_extended_xmp_guid(&without_guid), None);
+
+        let packet = build_packet(&format!(
+            "<xmpNote:HasExtendedXMP>{}</xmpNote:HasExtendedXMP>",
+            "F".repeat(GUID_SIZE)
+        ));
+        let with_guid = set_extended_xmp_guid(&packet, Some(guid)).unwrap();
+        assert_eq!(get_extended_xmp_guid(&with_guid).as_deref(), Some(guid));
+        assert_eq!(with_guid.matches("HasExtendedXMP").count(), 2);
+    }
+
+    #[test]
+    fn oversized_standard_xmp_is_split() {
+        let description = "x".repeat(MAX_STANDARD_XMP_SIZE);
+        let standard = build_packet(&format!(
+            "<xmp:Rating>3</xmp:Rating>\n<dc:description><rdf:Alt>\
+             <rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:description>\n\
+             <xmp:Label>Red</xmp:Label>",
+            description
+        ));
+        let jpeg = build_jpeg(&standard, None);
+
+        let packets = read_jpeg_xmp(&jpeg);
+        assert!(packets.warnings.is_empty());
+        let standard = packets.standard.unwrap();
+        assert!(standard.len() <= MAX_STANDARD_XMP_SIZE);
+        assert!(standard.contains("<xmp:Rating>3</xmp:Rating>"));
+        assert!(standard.contains("<xmp:Label>Red</xmp:Label>"));
+        assert!(!standard.contains("dc:description"));
+
+        let extended = packets.extended.unwrap();
+        assert!(extended.contains(&description));
+        assert!(extended.contains("xmlns:dc=\"http://purl.org/dc/elements/1.1/\""));
+        assert!(extended.starts_with("<x:xmpmeta"));
+    }
+}